@@ -0,0 +1,8 @@
+DROP INDEX idx_buckets_asset_owner ON buckets;
+DROP TABLE buckets;
+
+DROP TABLE users;
+DROP TABLE clients;
+
+DROP INDEX idx_asset_owner_name_id ON asset_owner;
+DROP TABLE asset_owner;
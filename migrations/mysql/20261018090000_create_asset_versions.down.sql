@@ -0,0 +1,4 @@
+DROP INDEX idx_asset_versions_path ON asset_versions;
+DROP TABLE asset_versions;
+
+ALTER TABLE buckets DROP COLUMN versioning;
@@ -0,0 +1,15 @@
+ALTER TABLE buckets ADD COLUMN versioning SMALLINT NOT NULL DEFAULT 0;
+
+CREATE TABLE asset_versions
+(
+    id            INTEGER AUTO_INCREMENT PRIMARY KEY,
+    pid           VARCHAR(64) UNIQUE,
+    bucket_id     INTEGER      NOT NULL,
+    path          VARCHAR(768) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    version       INTEGER      NOT NULL,
+    size          BIGINT       NOT NULL,
+    delete_marker SMALLINT     NOT NULL DEFAULT 0,
+    created_at    TEXT         NOT NULL,
+    FOREIGN KEY (bucket_id) REFERENCES buckets (id) ON DELETE CASCADE
+);
+CREATE UNIQUE INDEX idx_asset_versions_path on asset_versions (bucket_id, path(760), version);
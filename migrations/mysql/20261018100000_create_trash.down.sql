@@ -0,0 +1,3 @@
+DROP INDEX idx_trash_deleted_at ON trash;
+DROP INDEX idx_trash_owner ON trash;
+DROP TABLE trash;
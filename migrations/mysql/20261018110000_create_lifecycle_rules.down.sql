@@ -0,0 +1,5 @@
+DROP INDEX idx_audit_log_owner ON audit_log;
+DROP TABLE audit_log;
+
+DROP INDEX idx_lifecycle_rules_owner ON lifecycle_rules;
+DROP TABLE lifecycle_rules;
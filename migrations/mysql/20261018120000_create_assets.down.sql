@@ -0,0 +1,3 @@
+DROP INDEX idx_assets_bucket ON assets;
+DROP INDEX idx_assets_owner ON assets;
+DROP TABLE assets;
@@ -0,0 +1,18 @@
+CREATE TABLE assets
+(
+    id           INTEGER AUTO_INCREMENT PRIMARY KEY,
+    pid          VARCHAR(64) UNIQUE,
+    path         VARCHAR(768) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin UNIQUE NOT NULL,
+    asset_type   SMALLINT            NOT NULL,
+    size         BIGINT              NOT NULL DEFAULT 0,
+    content_type VARCHAR(255),
+    checksum     VARCHAR(128),
+    owner_id     INTEGER             NOT NULL,
+    bucket_id    INTEGER,
+    created_at   TEXT                NOT NULL,
+    updated_at   TEXT                NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES asset_owner (id) ON DELETE CASCADE,
+    FOREIGN KEY (bucket_id) REFERENCES buckets (id) ON DELETE CASCADE
+);
+CREATE INDEX idx_assets_owner on assets (owner_id);
+CREATE INDEX idx_assets_bucket on assets (bucket_id);
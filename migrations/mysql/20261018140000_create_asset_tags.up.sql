@@ -0,0 +1,12 @@
+CREATE TABLE asset_tags
+(
+    id        INTEGER AUTO_INCREMENT PRIMARY KEY,
+    asset_id  INTEGER      NOT NULL,
+    tag_key   VARCHAR(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    tag_value VARCHAR(256) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    UNIQUE (asset_id, tag_key),
+    FOREIGN KEY (asset_id) REFERENCES assets (id) ON DELETE CASCADE
+);
+CREATE INDEX idx_asset_tags_tag on asset_tags (tag_key, tag_value);
+
+ALTER TABLE lifecycle_rules ADD COLUMN tag TEXT;
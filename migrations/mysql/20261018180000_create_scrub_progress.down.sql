@@ -0,0 +1 @@
+DROP TABLE scrub_progress;
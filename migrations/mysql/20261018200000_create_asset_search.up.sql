@@ -0,0 +1,7 @@
+CREATE TABLE asset_search
+(
+    asset_id INTEGER PRIMARY KEY,
+    document TEXT NOT NULL,
+    FULLTEXT INDEX idx_asset_search_document (document),
+    FOREIGN KEY (asset_id) REFERENCES assets (id) ON DELETE CASCADE
+);
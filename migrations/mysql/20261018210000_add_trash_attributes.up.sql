@@ -0,0 +1,2 @@
+ALTER TABLE trash ADD COLUMN bucket_id INTEGER;
+ALTER TABLE trash ADD COLUMN attributes TEXT;
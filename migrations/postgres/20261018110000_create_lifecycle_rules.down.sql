@@ -0,0 +1,5 @@
+DROP INDEX idx_audit_log_owner;
+DROP TABLE audit_log;
+
+DROP INDEX idx_lifecycle_rules_owner;
+DROP TABLE lifecycle_rules;
@@ -0,0 +1,18 @@
+CREATE TABLE assets
+(
+    id           SERIAL PRIMARY KEY,
+    pid          TEXT UNIQUE,
+    path         TEXT UNIQUE NOT NULL,
+    asset_type   SMALLINT    NOT NULL,
+    size         BIGINT      NOT NULL DEFAULT 0,
+    content_type TEXT,
+    checksum     TEXT,
+    owner_id     INTEGER     NOT NULL,
+    bucket_id    INTEGER,
+    created_at   TEXT        NOT NULL,
+    updated_at   TEXT        NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES asset_owner (id) ON DELETE CASCADE,
+    FOREIGN KEY (bucket_id) REFERENCES buckets (id) ON DELETE CASCADE
+);
+CREATE INDEX idx_assets_owner on assets (owner_id);
+CREATE INDEX idx_assets_bucket on assets (bucket_id);
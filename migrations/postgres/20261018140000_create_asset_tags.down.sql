@@ -0,0 +1,4 @@
+ALTER TABLE lifecycle_rules DROP COLUMN tag;
+
+DROP INDEX idx_asset_tags_tag;
+DROP TABLE asset_tags;
@@ -0,0 +1,12 @@
+CREATE TABLE asset_tags
+(
+    id        SERIAL PRIMARY KEY,
+    asset_id  INTEGER NOT NULL,
+    tag_key   TEXT    NOT NULL,
+    tag_value TEXT    NOT NULL,
+    UNIQUE (asset_id, tag_key),
+    FOREIGN KEY (asset_id) REFERENCES assets (id) ON DELETE CASCADE
+);
+CREATE INDEX idx_asset_tags_tag on asset_tags (tag_key, tag_value);
+
+ALTER TABLE lifecycle_rules ADD COLUMN tag TEXT;
@@ -0,0 +1,9 @@
+CREATE TABLE download_grants
+(
+    id            SERIAL PRIMARY KEY,
+    pid           TEXT UNIQUE,
+    max_downloads INTEGER NOT NULL,
+    downloads     INTEGER NOT NULL DEFAULT 0,
+    expires_at    BIGINT  NOT NULL
+);
+CREATE INDEX idx_download_grants_expires_at on download_grants (expires_at);
@@ -0,0 +1,3 @@
+ALTER TABLE buckets ADD COLUMN visibility SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE buckets ADD COLUMN public_tag TEXT;
+ALTER TABLE assets ADD COLUMN visibility SMALLINT;
@@ -0,0 +1,5 @@
+ALTER TABLE buckets ADD COLUMN lock_mode SMALLINT;
+ALTER TABLE buckets ADD COLUMN lock_days INTEGER;
+ALTER TABLE assets ADD COLUMN retention_mode SMALLINT;
+ALTER TABLE assets ADD COLUMN retain_until BIGINT;
+ALTER TABLE assets ADD COLUMN legal_hold SMALLINT NOT NULL DEFAULT 0;
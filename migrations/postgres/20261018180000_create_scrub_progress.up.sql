@@ -0,0 +1,8 @@
+CREATE TABLE scrub_progress
+(
+    id          INTEGER PRIMARY KEY,
+    last_path   TEXT,
+    started_at  BIGINT,
+    finished_at BIGINT
+);
+INSERT INTO scrub_progress (id) VALUES (1);
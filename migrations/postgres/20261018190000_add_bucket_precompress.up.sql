@@ -0,0 +1 @@
+ALTER TABLE buckets ADD COLUMN precompress SMALLINT NOT NULL DEFAULT 0;
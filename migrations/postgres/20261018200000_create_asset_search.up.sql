@@ -0,0 +1,8 @@
+CREATE TABLE asset_search
+(
+    asset_id INTEGER PRIMARY KEY,
+    document TEXT     NOT NULL,
+    terms    TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', document)) STORED,
+    FOREIGN KEY (asset_id) REFERENCES assets (id) ON DELETE CASCADE
+);
+CREATE INDEX idx_asset_search_terms on asset_search USING GIN (terms);
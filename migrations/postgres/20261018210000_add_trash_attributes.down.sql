@@ -0,0 +1,2 @@
+ALTER TABLE trash DROP COLUMN attributes;
+ALTER TABLE trash DROP COLUMN bucket_id;
@@ -0,0 +1,8 @@
+DROP INDEX idx_buckets_asset_owner;
+DROP TABLE buckets;
+
+DROP TABLE users;
+DROP TABLE clients;
+
+DROP INDEX idx_asset_owner_name_id;
+DROP TABLE asset_owner;
\ No newline at end of file
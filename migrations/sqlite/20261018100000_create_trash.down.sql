@@ -0,0 +1,3 @@
+DROP INDEX idx_trash_deleted_at;
+DROP INDEX idx_trash_owner;
+DROP TABLE trash;
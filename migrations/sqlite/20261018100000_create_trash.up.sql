@@ -0,0 +1,13 @@
+CREATE TABLE trash
+(
+    id            INTEGER PRIMARY KEY,
+    pid           TEXT UNIQUE,
+    owner_id      INTEGER NOT NULL,
+    original_path TEXT     NOT NULL,
+    folder        SMALLINT NOT NULL DEFAULT 0,
+    size          BIGINT   NOT NULL,
+    deleted_at    BIGINT   NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES asset_owner (id) ON DELETE CASCADE
+);
+CREATE INDEX idx_trash_owner on trash (owner_id);
+CREATE INDEX idx_trash_deleted_at on trash (deleted_at);
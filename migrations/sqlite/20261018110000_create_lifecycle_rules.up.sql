@@ -0,0 +1,27 @@
+CREATE TABLE lifecycle_rules
+(
+    id         INTEGER PRIMARY KEY,
+    pid        TEXT UNIQUE,
+    owner_id   INTEGER  NOT NULL,
+    bucket_id  INTEGER,
+    prefix     TEXT,
+    glob       TEXT,
+    age        INTEGER  NOT NULL,
+    action     SMALLINT NOT NULL,
+    created_at TEXT     NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES asset_owner (id) ON DELETE CASCADE,
+    FOREIGN KEY (bucket_id) REFERENCES buckets (id) ON DELETE CASCADE
+);
+CREATE INDEX idx_lifecycle_rules_owner on lifecycle_rules (owner_id);
+
+CREATE TABLE audit_log
+(
+    id         INTEGER PRIMARY KEY,
+    owner_id   INTEGER NOT NULL,
+    action     TEXT    NOT NULL,
+    path       TEXT    NOT NULL,
+    detail     TEXT,
+    created_at TEXT    NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES asset_owner (id) ON DELETE CASCADE
+);
+CREATE INDEX idx_audit_log_owner on audit_log (owner_id);
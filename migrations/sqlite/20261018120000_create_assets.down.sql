@@ -0,0 +1,3 @@
+DROP INDEX idx_assets_bucket;
+DROP INDEX idx_assets_owner;
+DROP TABLE assets;
@@ -0,0 +1,2 @@
+DROP INDEX idx_download_grants_expires_at;
+DROP TABLE download_grants;
@@ -0,0 +1,3 @@
+ALTER TABLE assets DROP COLUMN visibility;
+ALTER TABLE buckets DROP COLUMN public_tag;
+ALTER TABLE buckets DROP COLUMN visibility;
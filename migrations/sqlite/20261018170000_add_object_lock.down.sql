@@ -0,0 +1,5 @@
+ALTER TABLE assets DROP COLUMN legal_hold;
+ALTER TABLE assets DROP COLUMN retain_until;
+ALTER TABLE assets DROP COLUMN retention_mode;
+ALTER TABLE buckets DROP COLUMN lock_days;
+ALTER TABLE buckets DROP COLUMN lock_mode;
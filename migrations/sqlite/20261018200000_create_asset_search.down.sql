@@ -0,0 +1,2 @@
+DROP TRIGGER asset_search_delete;
+DROP TABLE asset_search;
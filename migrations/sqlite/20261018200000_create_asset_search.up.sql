@@ -0,0 +1,6 @@
+CREATE VIRTUAL TABLE asset_search USING fts5(document, tokenize = 'unicode61');
+
+CREATE TRIGGER asset_search_delete AFTER DELETE ON assets
+BEGIN
+    DELETE FROM asset_search WHERE rowid = old.id;
+END;
@@ -0,0 +1 @@
+ALTER TABLE assets ADD COLUMN storage_class SMALLINT NOT NULL DEFAULT 0;
@@ -1,10 +1,20 @@
-use clap::{Parser, Subcommand};
-use shared::client::{create_client, regenerate_token};
+use clap::{Parser, Subcommand, ValueEnum};
+use shared::acl::Visibility;
+use shared::archive;
+use shared::buckets::{self, AssetOwnerName, CreateBucketData};
+use shared::client::{self, create_client, regenerate_token};
 use shared::config::AppConfig;
 use shared::db::Database;
-use shared::secrets::AppSecrets;
+use shared::fsck::{self, FsckFixes};
+use shared::lock::{DefaultRetention, RetentionMode};
+use shared::secrets::{AppSecrets, SECRETS_FILENAME};
+use shared::{mb_to_bytes, scrub};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// Environment variable the archive passphrase is read from when no `--passphrase-file` is given.
+const PASSPHRASE_ENV: &str = "PPDRIVE_PASSPHRASE";
+
 /// PPDRIVE is a free, open-source object storage service built with Rust for speed, security,
 /// and reliability.
 #[derive(Parser, Debug)]
@@ -41,6 +51,84 @@ impl Cli {
                 _ => {}
             },
 
+            CliCommand::Bucket { command } => match command {
+                BucketCommand::Create {
+                    client_id,
+                    size,
+                    accepts,
+                    versioning,
+                    public,
+                    public_tag,
+                    lock_days,
+                    compliance,
+                    precompress,
+                } => {
+                    let data = CreateBucketData {
+                        size: size.map(|size| mb_to_bytes(size) as i64),
+                        accepts: accepts.clone(),
+                        owner_type: AssetOwnerName::Client,
+                        owner_id: client::id_by_pid(&pool, client_id).await?,
+                        versioning: *versioning,
+                        visibility: match public {
+                            true => Visibility::PublicRead,
+                            false => Visibility::Private,
+                        },
+                        public_tag: public_tag.as_deref().map(str::parse).transpose()?,
+                        retention: lock_days.map(|days| DefaultRetention {
+                            mode: match compliance {
+                                true => RetentionMode::Compliance,
+                                false => RetentionMode::Governance,
+                            },
+                            days,
+                        }),
+                        precompress: *precompress,
+                    };
+
+                    let pid = buckets::create(data, &pool).await?;
+                    println!("Bucket created successfully!");
+                    println!("Bucket ID: {pid}");
+                }
+                BucketCommand::List { client_id } => {
+                    let client_id = client::id_by_pid(&pool, client_id).await?;
+                    let owner_id = client::owner_id(&pool, client_id).await?;
+                    for bucket in buckets::list(&pool, owner_id).await? {
+                        println!("{}\t{}", bucket.pid(), bucket.created_at());
+                    }
+                }
+                BucketCommand::Info { bucket_id } => {
+                    let bucket = buckets::get(bucket_id, &pool).await?;
+                    let usage = buckets::usage(&pool, bucket.id()).await?;
+                    let objects = buckets::count(&pool, bucket.id()).await?;
+
+                    println!("Bucket ID: {}", bucket.pid());
+                    println!("Created At: {}", bucket.created_at());
+                    match bucket.size() {
+                        Some(size) => println!("Usage: {usage} of {size} bytes"),
+                        None => println!("Usage: {usage} bytes"),
+                    }
+                    println!("Objects: {objects}");
+                    println!("Accepts: {}", bucket.accepts().unwrap_or("*/*"));
+                    println!("Versioning: {}", bucket.versioning());
+                    println!("Visibility: {:?}", bucket.visibility());
+                    if let Some(tag) = bucket.public_tag() {
+                        println!("Public Tag: {tag}");
+                    }
+                    if let Some(retention) = bucket.default_retention() {
+                        println!("Retention: {:?}, {} days", retention.mode, retention.days);
+                    }
+                }
+                BucketCommand::Delete {
+                    bucket_id,
+                    force,
+                    bypass_governance,
+                } => {
+                    let bucket = buckets::get(bucket_id, &pool).await?;
+                    let root_dir = config.root_dir()?;
+                    buckets::delete(&pool, &root_dir, &bucket, *force, *bypass_governance).await?;
+                    println!("Bucket deleted successfully!");
+                }
+            },
+
             CliCommand::Serve { port } => {
                 if cfg!(debug_assertions) {
                     Command::new("cargo")
@@ -55,6 +143,99 @@ impl Cli {
             CliCommand::Configure => {
                 Command::new("nano").arg("ppd_config.toml").status()?;
             }
+
+            CliCommand::Export {
+                output,
+                passphrase_file,
+            } => {
+                let passphrase = read_passphrase(passphrase_file.as_deref())?;
+                let secrets_file = shared::root_dir()?.join(SECRETS_FILENAME);
+                let manifest = archive::export(
+                    &pool,
+                    &config.root_dir()?,
+                    &secrets_file,
+                    &passphrase,
+                    output,
+                )
+                .await?;
+
+                println!("Instance exported successfully!");
+                for (table, rows) in &manifest.tables {
+                    println!("{table}: {rows} rows");
+                }
+                println!("objects: {}", manifest.objects.len());
+            }
+
+            CliCommand::Import {
+                archive: path,
+                passphrase_file,
+            } => {
+                let passphrase = read_passphrase(passphrase_file.as_deref())?;
+                let secrets_file = shared::root_dir()?.join(SECRETS_FILENAME);
+                let manifest =
+                    archive::import(&pool, &config.root_dir()?, &secrets_file, &passphrase, path)
+                        .await?;
+
+                println!("Instance imported successfully!");
+                println!("objects: {}", manifest.objects.len());
+            }
+
+            CliCommand::Scrub { restart, rate } => {
+                if *restart {
+                    scrub::restart(&pool).await?;
+                }
+
+                let rate = rate
+                    .or(config.scrub_rate)
+                    .map(|rate| mb_to_bytes(rate) as u64)
+                    .unwrap_or(scrub::DEFAULT_RATE);
+
+                let root_dir = config.root_dir()?;
+                let replicas = config.replicas()?;
+                let (mut scanned, mut damaged) = (0, 0);
+                loop {
+                    let report = scrub::scrub(&pool, &root_dir, &replicas, rate, 100).await?;
+                    for finding in &report.findings {
+                        match &finding.error {
+                            Some(err) => println!("{:?}\t{}\t{err}", finding.status, finding.path),
+                            None => println!("{:?}\t{}", finding.status, finding.path),
+                        }
+                        if finding.status != scrub::ScrubStatus::Repaired {
+                            damaged += 1;
+                        }
+                    }
+
+                    scanned += report.scanned;
+                    if report.completed {
+                        break;
+                    }
+                }
+
+                println!("Scrub completed: {scanned} file(s) checked, {damaged} damaged.");
+            }
+
+            CliCommand::Fsck { fix } => {
+                if fix.contains(&FsckFix::Adopt) && fix.contains(&FsckFix::Delete) {
+                    return Err(anyhow::anyhow!("orphans can either be adopted or deleted"));
+                }
+
+                let fixes = FsckFixes {
+                    adopt: fix.contains(&FsckFix::Adopt),
+                    delete: fix.contains(&FsckFix::Delete),
+                    reindex: fix.contains(&FsckFix::Reindex),
+                    clean_tmp: fix.contains(&FsckFix::Tmp),
+                };
+
+                let tmp_dir = shared::root_dir()?.join("tmp");
+                let findings = fsck::check(&pool, &config.root_dir()?, &tmp_dir, &fixes).await?;
+                for finding in &findings {
+                    let fixed = if finding.fixed { "\tfixed" } else { "" };
+                    println!("{:?}\t{}{fixed}", finding.issue, finding.path);
+                }
+
+                let fixed = findings.iter().filter(|f| f.fixed).count();
+                println!("{} issue(s) found, {fixed} fixed.", findings.len());
+            }
         }
 
         Ok(())
@@ -73,6 +254,61 @@ enum CliCommand {
         #[command(subcommand)]
         command: ClientCommand,
     },
+    /// manage a client's buckets
+    Bucket {
+        #[command(subcommand)]
+        command: BucketCommand,
+    },
+    /// export the database, objects and secrets to an archive.
+    Export {
+        /// Path of the archive (.tar.gz) to write.
+        #[arg(long)]
+        output: PathBuf,
+
+        /// File holding the passphrase that encrypts the secrets file, which is required to
+        /// import the archive. Defaults to the PPDRIVE_PASSPHRASE environment variable.
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// restore an exported archive into an empty instance.
+    Import {
+        /// Path of the archive to restore.
+        #[arg(long)]
+        archive: PathBuf,
+
+        /// File holding the passphrase the archive was exported with. Defaults to the
+        /// PPDRIVE_PASSPHRASE environment variable.
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// re-read stored objects and compare them with their checksums, resuming an interrupted pass.
+    Scrub {
+        /// Start a new pass instead of resuming the current one.
+        #[arg(long)]
+        restart: bool,
+
+        /// Rate (in MB per second) at which objects are read.
+        #[arg(long)]
+        rate: Option<f64>,
+    },
+    /// reconcile the asset index with the storage root.
+    Fsck {
+        /// Issues to fix, e.g. --fix adopt,reindex. Issues are only reported by default.
+        #[arg(long, value_enum, value_delimiter = ',')]
+        fix: Vec<FsckFix>,
+    },
+}
+
+#[derive(ValueEnum, Clone, PartialEq, Debug)]
+enum FsckFix {
+    /// index orphan files and folders under their namespace's owner.
+    Adopt,
+    /// delete orphan files and folders.
+    Delete,
+    /// re-index files whose size changed and forget missing ones.
+    Reindex,
+    /// remove stale upload chunks.
+    Tmp,
 }
 
 #[derive(Subcommand, Debug)]
@@ -96,3 +332,90 @@ enum ClientCommand {
 
     List,
 }
+
+#[derive(Subcommand, Debug)]
+enum BucketCommand {
+    /// create a new bucket for a client.
+    Create {
+        /// ID of the client owning the bucket.
+        #[arg(long("client"))]
+        client_id: String,
+
+        /// Maximum size (in MB) of the bucket's content.
+        #[arg(long)]
+        size: Option<f64>,
+
+        /// Content types accepted by the bucket, e.g. image/*,application/pdf
+        #[arg(long, value_delimiter = ',')]
+        accepts: Option<Vec<String>>,
+
+        /// Keep prior versions of overwritten and deleted objects.
+        #[arg(long)]
+        versioning: bool,
+
+        /// Serve the bucket's objects without authentication, unless they're made private.
+        #[arg(long)]
+        public: bool,
+
+        /// Serve objects with this tag (key=value or key) without authentication.
+        #[arg(long)]
+        public_tag: Option<String>,
+
+        /// Retain files written to the bucket for this many days.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        lock_days: Option<u32>,
+
+        /// Use compliance retention, which can't be bypassed, instead of governance.
+        #[arg(long, requires = "lock_days")]
+        compliance: bool,
+
+        /// Write brotli and gzip copies of compressible files when they're uploaded.
+        #[arg(long)]
+        precompress: bool,
+    },
+
+    /// list a client's buckets.
+    List {
+        #[arg(long("client"))]
+        client_id: String,
+    },
+
+    /// show a bucket's settings and usage.
+    Info {
+        #[arg(long("id"))]
+        bucket_id: String,
+    },
+
+    /// delete a bucket.
+    Delete {
+        #[arg(long("id"))]
+        bucket_id: String,
+
+        /// Delete the bucket even if it isn't empty, along with its objects.
+        #[arg(long)]
+        force: bool,
+
+        /// Delete objects under governance retention too. Objects under compliance retention
+        /// or legal hold always prevent deletion.
+        #[arg(long)]
+        bypass_governance: bool,
+    },
+}
+
+/// Read the archive passphrase from `file`, or from the [PASSPHRASE_ENV] environment variable.
+/// It's never taken as an argument, which would leave it in the shell history and process list.
+fn read_passphrase(file: Option<&Path>) -> anyhow::Result<String> {
+    let passphrase = match file {
+        Some(file) => std::fs::read_to_string(file)?,
+        None => std::env::var(PASSPHRASE_ENV).map_err(|_| {
+            anyhow::anyhow!("pass --passphrase-file or set {PASSPHRASE_ENV} to the passphrase")
+        })?,
+    };
+
+    let passphrase = passphrase.trim_end_matches(['\r', '\n']);
+    if passphrase.is_empty() {
+        return Err(anyhow::anyhow!("the passphrase is empty"));
+    }
+
+    Ok(passphrase.to_string())
+}
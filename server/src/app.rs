@@ -1,5 +1,10 @@
-use crate::routers::upload_routes;
+use crate::routers::{
+    asset_routes, audit_routes, bucket_routes, download_routes, events_routes, folder_routes,
+    lifecycle_routes, list_routes, live_routes, search_routes, signed_routes, trash_routes,
+    upload_routes,
+};
 use crate::state::AppState;
+use crate::{compression, tasks};
 use axum::Router;
 use axum::extract::MatchedPath;
 use axum::http::header::{
@@ -57,7 +62,21 @@ pub async fn create_app() -> anyhow::Result<(IntoMakeService<Router>, i16)> {
         ])
         .allow_methods(Any);
 
-    let mut app = Router::new().nest("/upload", upload_routes()).layer(
+    let mut app = Router::new()
+        .nest("/upload", upload_routes())
+        .nest("/asset", asset_routes())
+        .nest("/bucket", bucket_routes())
+        .nest("/list", list_routes())
+        .nest("/search", search_routes())
+        .nest("/events", events_routes())
+        .nest("/download", download_routes())
+        .nest("/signed", signed_routes())
+        .nest("/live", live_routes())
+        .nest("/folder", folder_routes())
+        .nest("/trash", trash_routes())
+        .nest("/lifecycle", lifecycle_routes())
+        .nest("/audit", audit_routes())
+        .layer(
         TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
             let matched_path = request
                 .extensions()
@@ -75,9 +94,15 @@ pub async fn create_app() -> anyhow::Result<(IntoMakeService<Router>, i16)> {
 
     for folder in state.config().static_folders.clone() {
         let path = folder.path.unwrap_or(format!("/{}", folder.name));
-        app = app.nest_service(&path, ServeDir::new(folder.name));
+        let files = ServeDir::new(folder.name)
+            .precompressed_br()
+            .precompressed_gzip()
+            .precompressed_zstd();
+
+        app = app.nest_service(&path, compression::compress(files));
     }
 
+    tasks::spawn(state.clone());
     let app = app.layer(cors).with_state(state).into_make_service();
 
     Ok((app, port))
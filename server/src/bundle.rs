@@ -0,0 +1,109 @@
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use tokio::io::DuplexStream;
+use tokio_util::io::SyncIoBridge;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Size of the buffer between the archive writer and the response.
+const PIPE_SIZE: usize = 256 * 1024;
+
+/// Archive format folders are downloaded in.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub(crate) enum BundleFormat {
+    #[default]
+    Zip,
+    TarGz,
+}
+
+impl BundleFormat {
+    pub(crate) fn content_type(&self) -> &'static str {
+        match self {
+            BundleFormat::Zip => "application/zip",
+            BundleFormat::TarGz => "application/gzip",
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            BundleFormat::Zip => "zip",
+            BundleFormat::TarGz => "tar.gz",
+        }
+    }
+}
+
+/// A file to add to a bundle.
+pub(crate) struct BundleEntry {
+    /// Path of the file within the bundle.
+    pub name: String,
+    /// Location of the file on disk.
+    pub path: PathBuf,
+}
+
+/// Start writing `entries` to a `format` archive, returning a reader of the archive as it's
+/// written. Files are read one chunk at a time as the reader consumes the archive, so nothing is
+/// buffered beyond [PIPE_SIZE]. Files removed since they were listed are left out.
+pub(crate) fn stream(format: BundleFormat, entries: Vec<BundleEntry>) -> DuplexStream {
+    let (reader, writer) = tokio::io::duplex(PIPE_SIZE);
+    let writer = SyncIoBridge::new(writer);
+
+    tokio::task::spawn_blocking(move || {
+        let written = match format {
+            BundleFormat::Zip => write_zip(writer, entries),
+            BundleFormat::TarGz => write_tar_gz(writer, entries),
+        };
+
+        // the response ends early, so the client sees an incomplete archive.
+        if let Err(err) = written {
+            tracing::error!("unable to write {format:?} bundle: {err}");
+        }
+    });
+
+    reader
+}
+
+fn write_zip(writer: impl Write, entries: Vec<BundleEntry>) -> anyhow::Result<()> {
+    let mut zip = ZipWriter::new_stream(writer);
+    for entry in entries {
+        let Some(mut file) = open(&entry)? else {
+            continue;
+        };
+
+        let size = file.metadata()?.len();
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(size >= u32::MAX as u64);
+
+        zip.start_file(&entry.name, options)?;
+        std::io::copy(&mut file, &mut zip)?;
+    }
+
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+fn write_tar_gz(writer: impl Write, entries: Vec<BundleEntry>) -> anyhow::Result<()> {
+    let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+    for entry in entries {
+        let Some(mut file) = open(&entry)? else {
+            continue;
+        };
+
+        tar.append_file(&entry.name, &mut file)?;
+    }
+
+    tar.into_inner()?.finish()?.flush()?;
+    Ok(())
+}
+
+fn open(entry: &BundleEntry) -> anyhow::Result<Option<File>> {
+    match File::open(&entry.path) {
+        Ok(file) => Ok(Some(file)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
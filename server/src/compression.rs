@@ -0,0 +1,238 @@
+use axum::http::{HeaderMap, Response, header};
+use flate2::write::GzEncoder;
+use shared::assets::Asset;
+use shared::generate_nano_id;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use tower_http::compression::predicate::{And, Predicate, SizeAbove};
+use tower_http::compression::{Compression, CompressionLayer, CompressionLevel};
+
+/// Responses smaller than this (in bytes) aren't worth compressing.
+const MIN_COMPRESS_SIZE: u64 = 256;
+
+/// Brotli quality of precompressed files, from 0 to 11. They're only written once, so this
+/// favors size over speed.
+const BROTLI_QUALITY: u32 = 11;
+
+/// Brotli window size (log2) of precompressed files.
+const BROTLI_WINDOW: u32 = 22;
+
+/// Encodings files are precompressed with, in order of preference.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    const ALL: [Encoding; 2] = [Encoding::Brotli, Encoding::Gzip];
+
+    /// Value of the `Content-Encoding` header.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+}
+
+/// Whether content of `content_type` shrinks when compressed. Media and archives are usually
+/// compressed already.
+pub(crate) fn compressible(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    match mime.split_once('/') {
+        Some(("text", "event-stream")) => false,
+        Some(("text", _)) => true,
+        Some((_, subtype)) if subtype.ends_with("+json") || subtype.ends_with("+xml") => true,
+        Some(("application", subtype)) => matches!(
+            subtype,
+            "json"
+                | "xml"
+                | "javascript"
+                | "ecmascript"
+                | "wasm"
+                | "x-javascript"
+                | "x-ndjson"
+                | "x-yaml"
+                | "yaml"
+                | "toml"
+                | "x-sh"
+                | "sql"
+                | "graphql"
+                | "rtf"
+        ),
+        Some(("image", subtype)) => matches!(subtype, "svg+xml" | "bmp" | "x-icon"),
+        Some(("font", subtype)) => matches!(subtype, "ttf" | "otf"),
+        _ => false,
+    }
+}
+
+/// Only compress responses with a [compressible] content type.
+#[derive(Clone, Copy)]
+pub(crate) struct Compressible;
+
+impl Predicate for Compressible {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: axum::body::HttpBody,
+    {
+        response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(compressible)
+    }
+}
+
+type Policy = And<SizeAbove, Compressible>;
+
+fn policy() -> Policy {
+    SizeAbove::new(MIN_COMPRESS_SIZE).and(Compressible)
+}
+
+/// Compress responses with gzip, brotli or zstd, whichever the client prefers.
+pub(crate) fn layer() -> CompressionLayer<Policy> {
+    CompressionLayer::new()
+        .quality(CompressionLevel::Default)
+        .compress_when(policy())
+}
+
+/// Compress the responses of `service` like [layer] does.
+pub(crate) fn compress<S>(service: S) -> Compression<S, Policy> {
+    Compression::new(service)
+        .quality(CompressionLevel::Default)
+        .compress_when(policy())
+}
+
+/// Location of `asset`'s copy precompressed with `encoding`, in its variants folder.
+fn precompressed_path(root_dir: &Path, asset: &Asset, encoding: Encoding) -> Option<PathBuf> {
+    let checksum = asset.checksum()?;
+    let name = format!("{checksum}.{}", encoding.extension());
+    Some(asset.variants_dir(root_dir).join(name))
+}
+
+/// Find a precompressed copy of `asset` in an encoding the request accepts, preferring the one
+/// it gives the highest quality.
+pub(crate) fn precompressed(
+    root_dir: &Path,
+    asset: &Asset,
+    headers: &HeaderMap,
+) -> Option<(PathBuf, Encoding)> {
+    let mut candidates: Vec<(f32, Encoding)> = Encoding::ALL
+        .into_iter()
+        .map(|encoding| (accept_quality(headers, encoding), encoding))
+        .filter(|(quality, _)| *quality > 0.0)
+        .collect();
+
+    // stable, so ties keep the order of preference.
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    candidates.into_iter().find_map(|(_, encoding)| {
+        precompressed_path(root_dir, asset, encoding)
+            .filter(|path| path.is_file())
+            .map(|path| (path, encoding))
+    })
+}
+
+/// Quality the `Accept-Encoding` header gives `encoding`, or `*` when it isn't listed. Zero
+/// when neither is.
+fn accept_quality(headers: &HeaderMap, encoding: Encoding) -> f32 {
+    let accept = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let mut wildcard = 0.0;
+    for coding in accept.split(',') {
+        let mut params = coding.split(';').map(str::trim);
+        let name = params.next().unwrap_or_default();
+        let quality = params
+            .find_map(|param| param.strip_prefix("q="))
+            .map(|q| q.parse::<f32>().unwrap_or_default())
+            .unwrap_or(1.0);
+
+        if name.eq_ignore_ascii_case(encoding.name()) {
+            return quality;
+        }
+
+        if name == "*" {
+            wildcard = quality;
+        }
+    }
+
+    wildcard
+}
+
+/// Write brotli and gzip copies of a compressible file to its variants folder, so downloads
+/// needn't compress it on the fly.
+pub(crate) async fn precompress(root_dir: &Path, asset: &Asset) -> anyhow::Result<()> {
+    if !asset.content_type().is_some_and(compressible) {
+        return Ok(());
+    }
+
+    let file = root_dir.join(asset.path());
+    for encoding in Encoding::ALL {
+        let Some(path) = precompressed_path(root_dir, asset, encoding) else {
+            return Ok(());
+        };
+
+        if path.is_file() {
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // stage then rename, so downloads never serve a partial copy.
+        let staged = path.with_extension(format!("{}.tmp", generate_nano_id(8)));
+        let source = file.clone();
+        let target = staged.clone();
+        let written =
+            tokio::task::spawn_blocking(move || compress_file(&source, &target, encoding)).await?;
+
+        if let Err(err) = written {
+            let _ = tokio::fs::remove_file(&staged).await;
+            return Err(err);
+        }
+
+        tokio::fs::rename(&staged, &path).await?;
+    }
+
+    Ok(())
+}
+
+fn compress_file(source: &Path, target: &Path, encoding: Encoding) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(File::open(source)?);
+    let writer = File::create(target)?;
+
+    match encoding {
+        Encoding::Brotli => {
+            let mut encoder =
+                brotli::CompressorWriter::new(writer, 64 * 1024, BROTLI_QUALITY, BROTLI_WINDOW);
+            std::io::copy(&mut reader, &mut encoder)?;
+            encoder.flush()?;
+            encoder.into_inner().sync_all()?;
+        }
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(writer, flate2::Compression::best());
+            std::io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?.sync_all()?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use futures_util::StreamExt;
+use shared::broker::MessageBroker;
+use shared::events::ObjectEvent;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Number of events kept for subscribers that fall behind.
+const BUS_CAPACITY: usize = 1024;
+
+/// How long to wait before subscribing to the message broker again after losing it.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+/// Carries object change events from the upload, delete and move code paths to event stream
+/// subscribers. With a message broker, events go through Redis pub/sub so subscribers of every
+/// server instance get them.
+#[derive(Clone)]
+pub(crate) struct EventBus {
+    sender: broadcast::Sender<ObjectEvent>,
+    broker: Option<MessageBroker>,
+}
+
+impl EventBus {
+    pub(crate) fn new(broker: Option<MessageBroker>) -> Self {
+        Self {
+            sender: broadcast::channel(BUS_CAPACITY).0,
+            broker,
+        }
+    }
+
+    /// Receive events published from now on.
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<ObjectEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Publish an event. Events that can't go through the message broker still reach this
+    /// instance's subscribers.
+    pub(crate) async fn publish(&self, event: ObjectEvent) {
+        if let Some(broker) = &self.broker {
+            match broker.publish_event(&event).await {
+                Ok(()) => return,
+                Err(err) => tracing::error!("unable to publish {} event: {err}", event.path),
+            }
+        }
+
+        // no subscribers is fine.
+        let _ = self.sender.send(event);
+    }
+
+    /// Forward events published through the message broker to this instance's subscribers, for
+    /// the lifetime of the server.
+    pub(crate) async fn relay(self) {
+        let Some(broker) = &self.broker else {
+            return;
+        };
+
+        loop {
+            match broker.subscribe_events().await {
+                Ok(mut messages) => {
+                    while let Some(msg) = messages.next().await {
+                        match serde_json::from_slice(msg.get_payload_bytes()) {
+                            Ok(event) => {
+                                let _ = self.sender.send(event);
+                            }
+                            Err(err) => tracing::error!("invalid object event: {err}"),
+                        }
+                    }
+
+                    tracing::error!("lost object events subscription");
+                }
+                Err(err) => tracing::error!("unable to subscribe to object events: {err}"),
+            }
+
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use axum::http::{HeaderMap, header};
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use shared::assets::Asset;
+use shared::generate_nano_id;
+use shared::server::{ImageFit, ImageTransform, OutputFormat};
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+
+/// Largest width or height of a source image, or of a rendered one.
+const MAX_DIMENSION: u32 = 16384;
+
+/// Quality of lossy encodings when none is requested.
+const DEFAULT_QUALITY: u8 = 80;
+
+/// AVIF encoder speed, from 1 (slowest, smallest output) to 10.
+const AVIF_SPEED: u8 = 8;
+
+/// A rendered image, stored in the cache.
+pub(crate) struct Rendered {
+    pub path: PathBuf,
+    pub content_type: &'static str,
+    /// Identifies the rendered image's content, for use as an entity tag.
+    pub tag: String,
+}
+
+/// Format of an image asset, if it's one that can be transformed.
+pub(crate) fn format(asset: &Asset) -> Option<ImageFormat> {
+    let format = ImageFormat::from_mime_type(asset.content_type()?)?;
+    match format {
+        ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP | ImageFormat::Gif => Some(format),
+        _ => None,
+    }
+}
+
+/// Format `transform` converts an image in `source` format to. `Auto` picks the first of AVIF
+/// and WebP the request explicitly accepts.
+pub(crate) fn output_format(
+    transform: &ImageTransform,
+    source: ImageFormat,
+    headers: &HeaderMap,
+) -> ImageFormat {
+    match transform.format {
+        None => source,
+        Some(OutputFormat::Jpeg) => ImageFormat::Jpeg,
+        Some(OutputFormat::Png) => ImageFormat::Png,
+        Some(OutputFormat::WebP) => ImageFormat::WebP,
+        Some(OutputFormat::Avif) => ImageFormat::Avif,
+        Some(OutputFormat::Auto) => [ImageFormat::Avif, ImageFormat::WebP]
+            .into_iter()
+            .find(|format| accepts(headers, format.to_mime_type()))
+            .unwrap_or(source),
+    }
+}
+
+/// Whether the `Accept` header lists `mime` with a non-zero quality. Wildcards don't count, as
+/// clients sending them may not decode newer formats.
+fn accepts(headers: &HeaderMap, mime: &str) -> bool {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    accept.split(',').any(|range| {
+        let mut params = range.split(';').map(str::trim);
+        let matches = params
+            .next()
+            .is_some_and(|media| media.eq_ignore_ascii_case(mime));
+        let rejected = params.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q <= 0.0)
+        });
+
+        matches && !rejected
+    })
+}
+
+/// Get the image asset `asset` transformed with `transform`, in `output` format. Rendered images
+/// are cached in the asset's variants folder, keyed by the source checksum and the
+/// transformation, so that an image is only rendered once until its content changes.
+pub(crate) async fn render(
+    root_dir: &Path,
+    cold_volume: Option<&Path>,
+    asset: &Asset,
+    source: ImageFormat,
+    output: ImageFormat,
+    transform: &ImageTransform,
+) -> anyhow::Result<Rendered> {
+    let checksum = asset
+        .checksum()
+        .ok_or(anyhow::anyhow!("image has no checksum"))?;
+
+    let key = transform.key()?;
+    let extension = extension(output);
+    let path = asset
+        .variants_dir(root_dir)
+        .join(format!("{checksum}-{key}.{extension}"));
+
+    if !path.is_file() {
+        let file = asset.stored_path(root_dir, cold_volume)?;
+        let transform = transform.clone();
+        let bytes =
+            tokio::task::spawn_blocking(move || transform_image(&file, source, output, &transform))
+                .await??;
+
+        // stage then rename, so concurrent requests never serve a partial image.
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let staged = path.with_extension(format!("{}.tmp", generate_nano_id(8)));
+        tokio::fs::write(&staged, bytes).await?;
+        tokio::fs::rename(&staged, &path).await?;
+    }
+
+    Ok(Rendered {
+        path,
+        content_type: output.to_mime_type(),
+        tag: format!("{checksum}-{key}-{extension}"),
+    })
+}
+
+/// Usual file extension of images in `format`.
+pub(crate) fn extension(format: ImageFormat) -> &'static str {
+    format.extensions_str().first().copied().unwrap_or("img")
+}
+
+fn transform_image(
+    file: &Path,
+    source: ImageFormat,
+    output: ImageFormat,
+    transform: &ImageTransform,
+) -> anyhow::Result<Vec<u8>> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+
+    let mut reader = ImageReader::new(BufReader::new(std::fs::File::open(file)?));
+    reader.set_format(source);
+    reader.limits(limits);
+    let mut image = reader.decode()?;
+
+    if let Some((x, y, width, height)) = transform.crop_region()? {
+        image = image.crop_imm(x, y, width, height);
+    }
+
+    image = match transform.rotate {
+        Some(90) => image.rotate90(),
+        Some(180) => image.rotate180(),
+        Some(270) => image.rotate270(),
+        _ => image,
+    };
+
+    image = resize(image, transform);
+
+    let quality = transform.quality.unwrap_or(DEFAULT_QUALITY);
+    let mut bytes = Cursor::new(vec![]);
+    match output {
+        ImageFormat::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut bytes, quality);
+            image.to_rgb8().write_with_encoder(encoder)?;
+        }
+        ImageFormat::Avif => {
+            let encoder = AvifEncoder::new_with_speed_quality(&mut bytes, AVIF_SPEED, quality);
+            image.to_rgba8().write_with_encoder(encoder)?;
+        }
+        // WebP images are encoded losslessly.
+        ImageFormat::WebP => image.to_rgba8().write_to(&mut bytes, output)?,
+        _ => image.write_to(&mut bytes, output)?,
+    }
+
+    Ok(bytes.into_inner())
+}
+
+/// Resize an image to the requested box, scaled by the device pixel ratio. When only one side
+/// is given, the other follows the image's aspect ratio.
+fn resize(image: DynamicImage, transform: &ImageTransform) -> DynamicImage {
+    let dpr = transform.dpr.unwrap_or(1.0);
+    let scale = |side: u32| ((side as f32 * dpr).round() as u32).clamp(1, MAX_DIMENSION);
+    let (width, height) = (image.width().max(1), image.height().max(1));
+    let follow = |side: u32, from: u32, to: u32| {
+        (side as u64 * to as u64 / from as u64).clamp(1, MAX_DIMENSION as u64) as u32
+    };
+
+    let (box_width, box_height) = match (transform.w.map(scale), transform.h.map(scale)) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, follow(height, width, w)),
+        (None, Some(h)) => (follow(width, height, h), h),
+        (None, None) => return image,
+    };
+
+    let filter = FilterType::CatmullRom;
+    match transform.fit.unwrap_or_default() {
+        ImageFit::Contain => image.resize(box_width, box_height, filter),
+        ImageFit::Cover => image.resize_to_fill(box_width, box_height, filter),
+        ImageFit::Fill => image.resize_exact(box_width, box_height, filter),
+        ImageFit::Inside if width <= box_width && height <= box_height => image,
+        ImageFit::Inside => image.resize(box_width, box_height, filter),
+    }
+}
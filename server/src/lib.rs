@@ -1,4 +1,10 @@
 pub mod app;
 pub mod routers;
 pub mod state;
+mod bundle;
+mod compression;
+mod events;
+mod images;
+mod live;
+mod tasks;
 pub mod utils;
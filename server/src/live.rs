@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Tells readers streaming a resumable upload when new chunks are staged. Readers also poll the
+/// staged file, so chunks received by another server instance are picked up as well.
+#[derive(Clone, Default)]
+pub(crate) struct LiveUploads {
+    sessions: Arc<Mutex<HashMap<String, watch::Sender<u64>>>>,
+}
+
+impl LiveUploads {
+    /// Watch the number of bytes staged for the upload session `session_id`. The receiver is
+    /// closed once the session completes or fails.
+    pub(crate) fn subscribe(&self, session_id: &str) -> watch::Receiver<u64> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        sessions
+            .entry(session_id.to_string())
+            .or_insert_with(|| watch::channel(0).0)
+            .subscribe()
+    }
+
+    /// Record that `staged` bytes of the upload session `session_id` are staged.
+    pub(crate) fn notify(&self, session_id: &str, staged: u64) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(sender) = sessions.get(session_id) {
+            sender.send_replace(staged);
+
+            // nobody is watching anymore.
+            if sender.receiver_count() == 0 {
+                sessions.remove(session_id);
+            }
+        }
+    }
+
+    /// Close the session's watchers after it completed or failed.
+    pub(crate) fn finish(&self, session_id: &str) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        sessions.remove(session_id);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, ResponseError, api_error, api_response};
+use crate::state::AppState;
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use shared::acl::{self, Visibility};
+use shared::assets::{self, Asset, AssetType, METADATA_HEADER_PREFIX, Metadata, StorageClass};
+use shared::client;
+use shared::lifecycle;
+use shared::lock::{self, Retention};
+use shared::tags::{self, TagFilter, TagSet};
+
+#[derive(Deserialize)]
+pub(super) struct AssetQuery {
+    path: String,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdateMetadataOptions {
+    path: String,
+    metadata: Metadata,
+}
+
+#[derive(Deserialize)]
+pub(super) struct PutTagsOptions {
+    path: String,
+    tags: TagSet,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdateVisibilityOptions {
+    path: String,
+    /// Leave empty for the asset to inherit its bucket's visibility.
+    visibility: Option<Visibility>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdateRetentionOptions {
+    path: String,
+    #[serde(flatten)]
+    retention: Retention,
+    /// Shorten or weaken a governance retention.
+    bypass_governance: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdateLegalHoldOptions {
+    path: String,
+    hold: bool,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdateStorageClassOptions {
+    path: String,
+    storage_class: StorageClass,
+}
+
+#[derive(Deserialize)]
+pub(super) struct TaggedQuery {
+    /// Comma separated tag filters, e.g. `project=alpha,status`.
+    tags: String,
+}
+
+/// Get an indexed asset. Its metadata is also returned as `x-ppdrive-meta-*` headers.
+#[axum::debug_handler]
+pub(super) async fn get_asset(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Query(query): Query<AssetQuery>,
+) -> Result<Response, ResponseError> {
+    let asset = client.asset(&state, &query.path).await?;
+    let headers = metadata_headers(asset.metadata());
+
+    Ok((headers, api_response(asset)?).into_response())
+}
+
+/// Replace the metadata of an asset.
+#[axum::debug_handler]
+pub(super) async fn update_metadata(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<UpdateMetadataOptions>,
+) -> ApiResponse<Asset> {
+    let asset = client.asset(&state, &options.path).await?;
+    options
+        .metadata
+        .check()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    let asset = assets::set_metadata(state.db(), asset.path(), &options.metadata).await?;
+    api_response(asset)
+}
+
+/// Change who may read an asset.
+#[axum::debug_handler]
+pub(super) async fn update_visibility(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<UpdateVisibilityOptions>,
+) -> ApiResponse<Asset> {
+    let asset = client.asset(&state, &options.path).await?;
+    let asset = acl::set(state.db(), asset.path(), options.visibility).await?;
+
+    api_response(asset)
+}
+
+/// Change the retention of a file.
+#[axum::debug_handler]
+pub(super) async fn update_retention(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<UpdateRetentionOptions>,
+) -> ApiResponse<Asset> {
+    let asset = client.asset(&state, &options.path).await?;
+    let bypass = options.bypass_governance.unwrap_or_default();
+    let asset = lock::set_retention(state.db(), &asset, options.retention, bypass)
+        .await
+        .map_err(|err| api_error(err).with_status_code(StatusCode::CONFLICT))?;
+
+    api_response(asset)
+}
+
+/// Place or lift a legal hold on a file.
+#[axum::debug_handler]
+pub(super) async fn update_legal_hold(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<UpdateLegalHoldOptions>,
+) -> ApiResponse<Asset> {
+    let asset = client.asset(&state, &options.path).await?;
+    let asset = lock::set_legal_hold(state.db(), &asset, options.hold)
+        .await
+        .map_err(|err| api_error(err).with_status_code(StatusCode::CONFLICT))?;
+
+    api_response(asset)
+}
+
+/// Move a file to the cold volume, or bring a file or folder's files back from it.
+#[axum::debug_handler]
+pub(super) async fn update_storage_class(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<UpdateStorageClassOptions>,
+) -> ApiResponse<Asset> {
+    let asset = client.asset(&state, &options.path).await?;
+    match options.storage_class {
+        StorageClass::Standard => restore_cold(&state, asset.path()).await?,
+        StorageClass::Cold => {
+            let bad_request = |msg: &str| api_error(msg).with_status_code(StatusCode::BAD_REQUEST);
+            if asset.asset_type() != AssetType::File {
+                return Err(bad_request("only files can be moved to cold storage"));
+            }
+
+            let cold_volume = state.config().cold_volume()?;
+            let cold_volume = cold_volume.ok_or(bad_request("cold_volume is not configured"))?;
+            if asset.storage_class() == StorageClass::Standard {
+                let root_dir = state.config().root_dir()?;
+                lifecycle::transition(state.db(), &root_dir, &cold_volume, asset.path()).await?;
+            }
+        }
+    }
+
+    let asset = assets::get(state.db(), asset.path())
+        .await?
+        .ok_or(api_error("asset not found").with_status_code(StatusCode::NOT_FOUND))?;
+
+    api_response(asset)
+}
+
+/// Whether the file at `path` is stored in the cold volume. Its path doesn't exist under the
+/// storage root then.
+pub(super) async fn is_cold(state: &AppState, path: &str) -> Result<bool, ResponseError> {
+    let asset = assets::get(state.db(), path).await?;
+    Ok(asset.is_some_and(|asset| asset.storage_class() == StorageClass::Cold))
+}
+
+/// Bring files at or under `path` back from the cold volume, before they're changed in place.
+pub(super) async fn restore_cold(state: &AppState, path: &str) -> Result<(), ResponseError> {
+    let root_dir = state.config().root_dir()?;
+    let cold_volume = state.config().cold_volume()?;
+    lifecycle::restore_cold(state.db(), &root_dir, cold_volume.as_deref(), path).await?;
+    Ok(())
+}
+
+/// Fail with `423 Locked` when a file at or under `path` is retained or held.
+pub(super) async fn check_unlocked(
+    state: &AppState,
+    path: &str,
+    bypass_governance: bool,
+) -> Result<(), ResponseError> {
+    match lock::find_locked(state.db(), path, bypass_governance).await? {
+        Some(locked) => Err(api_error(locked).with_status_code(StatusCode::LOCKED)),
+        None => Ok(()),
+    }
+}
+
+/// Replace the tag set of an asset.
+#[axum::debug_handler]
+pub(super) async fn put_tags(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<PutTagsOptions>,
+) -> ApiResponse<TagSet> {
+    let asset = client.asset(&state, &options.path).await?;
+    tags::check(&options.tags)
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    tags::put(state.db(), asset.id(), &options.tags).await?;
+    api_response(options.tags)
+}
+
+/// Get the tag set of an asset.
+#[axum::debug_handler]
+pub(super) async fn get_tags(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Query(query): Query<AssetQuery>,
+) -> ApiResponse<TagSet> {
+    let asset = client.asset(&state, &query.path).await?;
+    let tags = tags::get(state.db(), asset.id()).await?;
+
+    api_response(tags)
+}
+
+/// Remove every tag of an asset.
+#[axum::debug_handler]
+pub(super) async fn delete_tags(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Query(query): Query<AssetQuery>,
+) -> ApiResponse<()> {
+    let asset = client.asset(&state, &query.path).await?;
+    tags::delete(state.db(), asset.id()).await?;
+
+    api_response(())
+}
+
+/// List the client's assets carrying every given tag.
+#[axum::debug_handler]
+pub(super) async fn list_tagged(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Query(query): Query<TaggedQuery>,
+) -> ApiResponse<Vec<Asset>> {
+    let filters = parse_tag_filters(&query.tags)?;
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    let assets = tags::find(state.db(), owner_id, &filters).await?;
+
+    api_response(assets)
+}
+
+/// Parse comma separated tag filters. No asset carries more than [tags::MAX_TAGS] tags, so no
+/// asset could match more filters.
+pub(super) fn parse_tag_filters(filters: &str) -> Result<Vec<TagFilter>, ResponseError> {
+    let filters: Vec<TagFilter> = filters
+        .split(',')
+        .filter(|filter| !filter.is_empty())
+        .map(|filter| filter.parse())
+        .collect::<anyhow::Result<_>>()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    if filters.len() > tags::MAX_TAGS {
+        return Err(
+            api_error(format!("provide at most {} tag filters", tags::MAX_TAGS))
+                .with_status_code(StatusCode::BAD_REQUEST),
+        );
+    }
+
+    Ok(filters)
+}
+
+/// Collect user-defined metadata from `x-ppdrive-meta-*` request headers.
+pub(super) fn metadata_from_headers(headers: &HeaderMap) -> Result<Metadata, ResponseError> {
+    let mut metadata = Metadata::new();
+    for (name, value) in headers {
+        if name.as_str().starts_with(METADATA_HEADER_PREFIX) {
+            let value = value.to_str().map_err(|_| {
+                api_error(format!("{name} must be printable ASCII"))
+                    .with_status_code(StatusCode::BAD_REQUEST)
+            })?;
+
+            metadata.insert(name.as_str(), value);
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// Convert metadata to `x-ppdrive-meta-*` response headers.
+pub(super) fn metadata_headers(metadata: &Metadata) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (key, value) in metadata.iter() {
+        let name = HeaderName::try_from(format!("{METADATA_HEADER_PREFIX}{key}"));
+        let value = HeaderValue::from_str(value);
+
+        match (name, value) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => tracing::warn!("skipping metadata \"{key}\" which isn't a valid header"),
+        }
+    }
+
+    headers
+}
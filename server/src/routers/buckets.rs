@@ -0,0 +1,272 @@
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, ResponseError, api_error, api_response};
+use crate::state::AppState;
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use shared::acl::Visibility;
+use shared::buckets::{self, AssetOwnerName, Bucket, CreateBucketData};
+use shared::client;
+use shared::lock::{self, DefaultRetention};
+use shared::tags::TagFilter;
+use validator::Validate;
+
+#[derive(Deserialize, Validate)]
+pub(super) struct CreateBucketOptions {
+    /// Maximum size (in bytes) of the bucket's content.
+    #[validate(range(min = 1))]
+    size: Option<i64>,
+    /// Content types the bucket accepts, e.g. `image/*`.
+    accepts: Option<Vec<String>>,
+    versioning: Option<bool>,
+    /// Default visibility of the bucket's objects. Defaults to private.
+    visibility: Option<Visibility>,
+    /// Make objects tagged `key=value` (or just `key`) public.
+    public_tag: Option<String>,
+    /// Retention applied to files written to the bucket.
+    retention: Option<DefaultRetention>,
+    /// Write brotli and gzip copies of compressible files when they're uploaded.
+    precompress: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdateVisibilityOptions {
+    visibility: Visibility,
+    public_tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdateRetentionOptions {
+    /// Leave empty to stop retaining new files.
+    retention: Option<DefaultRetention>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdateVersioningOptions {
+    versioning: bool,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UpdatePrecompressOptions {
+    precompress: bool,
+}
+
+#[derive(Deserialize)]
+pub(super) struct DeleteBucketOptions {
+    /// Delete the bucket along with its objects. Deleting a non-empty bucket fails otherwise.
+    force: Option<bool>,
+    /// Delete objects under governance retention along with the bucket.
+    bypass_governance: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub(super) struct BucketInfo {
+    #[serde(flatten)]
+    bucket: Bucket,
+    /// Bytes stored in the bucket.
+    usage: i64,
+    /// Number of files and folders in the bucket.
+    objects: i64,
+    retention: Option<DefaultRetention>,
+}
+
+/// Create a bucket for the client.
+#[axum::debug_handler]
+pub(super) async fn create_bucket(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<CreateBucketOptions>,
+) -> ApiResponse<String> {
+    options
+        .validate()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    let data = CreateBucketData {
+        size: options.size,
+        accepts: options.accepts,
+        owner_type: AssetOwnerName::Client,
+        owner_id: client.id(),
+        versioning: options.versioning.unwrap_or_default(),
+        visibility: options.visibility.unwrap_or_default(),
+        public_tag: parse_public_tag(options.public_tag.as_deref())?,
+        retention: check_retention(options.retention)?,
+        precompress: options.precompress.unwrap_or_default(),
+    };
+
+    let pid = buckets::create(data, state.db())
+        .await
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    api_response(pid)
+}
+
+/// List the client's buckets.
+#[axum::debug_handler]
+pub(super) async fn list_buckets(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+) -> ApiResponse<Vec<Bucket>> {
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    let buckets = buckets::list(state.db(), owner_id).await?;
+
+    api_response(buckets)
+}
+
+/// Get a bucket along with its usage.
+#[axum::debug_handler]
+pub(super) async fn get_bucket(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+) -> ApiResponse<BucketInfo> {
+    let bucket = client.bucket(&state, &pid).await?;
+    let usage = buckets::usage(state.db(), bucket.id()).await?;
+    let objects = buckets::count(state.db(), bucket.id()).await?;
+
+    let retention = bucket.default_retention();
+    api_response(BucketInfo {
+        bucket,
+        usage,
+        objects,
+        retention,
+    })
+}
+
+/// Change a bucket's default visibility and public tag.
+#[axum::debug_handler]
+pub(super) async fn update_bucket_visibility(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+    Json(options): Json<UpdateVisibilityOptions>,
+) -> ApiResponse<()> {
+    let bucket = client.bucket(&state, &pid).await?;
+    let public_tag = parse_public_tag(options.public_tag.as_deref())?;
+    buckets::set_visibility(
+        bucket.pid(),
+        options.visibility,
+        public_tag.as_ref(),
+        state.db(),
+    )
+    .await?;
+
+    api_response(())
+}
+
+/// Change the retention applied to files written to a bucket.
+#[axum::debug_handler]
+pub(super) async fn update_bucket_retention(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+    Json(options): Json<UpdateRetentionOptions>,
+) -> ApiResponse<()> {
+    let bucket = client.bucket(&state, &pid).await?;
+    let retention = check_retention(options.retention)?;
+    buckets::set_retention(bucket.pid(), retention, state.db()).await?;
+
+    api_response(())
+}
+
+/// Turn object versioning on or off for a bucket. Existing versions are kept when it's turned
+/// off.
+#[axum::debug_handler]
+pub(super) async fn update_bucket_versioning(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+    Json(options): Json<UpdateVersioningOptions>,
+) -> ApiResponse<()> {
+    let bucket = client.bucket(&state, &pid).await?;
+    buckets::set_versioning(bucket.pid(), options.versioning, state.db()).await?;
+
+    api_response(())
+}
+
+/// Turn precompression of uploaded files on or off for a bucket.
+#[axum::debug_handler]
+pub(super) async fn update_bucket_precompress(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+    Json(options): Json<UpdatePrecompressOptions>,
+) -> ApiResponse<()> {
+    let bucket = client.bucket(&state, &pid).await?;
+    buckets::set_precompress(bucket.pid(), options.precompress, state.db()).await?;
+
+    api_response(())
+}
+
+/// Delete a bucket. Non-empty buckets are only deleted when `force` is set, and never while
+/// they hold locked objects.
+#[axum::debug_handler]
+pub(super) async fn delete_bucket(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+    Query(options): Query<DeleteBucketOptions>,
+) -> ApiResponse<()> {
+    let bucket = client.bucket(&state, &pid).await?;
+    let force = options.force.unwrap_or_default();
+    if !force && buckets::count(state.db(), bucket.id()).await? > 0 {
+        return Err(
+            api_error("bucket is not empty. Set force to delete its content.")
+                .with_status_code(StatusCode::CONFLICT),
+        );
+    }
+
+    let bypass = options.bypass_governance.unwrap_or_default();
+    let path = buckets::object_path(bucket.pid(), "");
+    if let Some(locked) = lock::find_locked(state.db(), &path, bypass).await? {
+        return Err(api_error(format!("bucket holds a locked object. {locked}"))
+            .with_status_code(StatusCode::CONFLICT));
+    }
+
+    let root_dir = state.config().root_dir()?;
+    buckets::delete(state.db(), &root_dir, &bucket, force, bypass).await?;
+
+    api_response(())
+}
+
+fn parse_public_tag(tag: Option<&str>) -> Result<Option<TagFilter>, ResponseError> {
+    tag.map(str::parse)
+        .transpose()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))
+}
+
+fn check_retention(
+    retention: Option<DefaultRetention>,
+) -> Result<Option<DefaultRetention>, ResponseError> {
+    match retention {
+        Some(DefaultRetention { days: 0, .. }) => {
+            Err(api_error("retention days must be positive")
+                .with_status_code(StatusCode::BAD_REQUEST))
+        }
+        retention => Ok(retention),
+    }
+}
+
+/// Check that a bucket accepts a file of `content_type` and has room for `bytes` more.
+pub(super) async fn check_content(
+    state: &AppState,
+    bucket: &Bucket,
+    content_type: Option<&str>,
+    bytes: u64,
+) -> Result<(), ResponseError> {
+    if !bucket.accepts_type(content_type) {
+        return Err(api_error(format!(
+            "bucket does not accept {}",
+            content_type.unwrap_or("files of unknown type")
+        ))
+        .with_status_code(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+    }
+
+    if !bucket.has_room(state.db(), bytes).await? {
+        return Err(
+            api_error("bucket size exceeded").with_status_code(StatusCode::INSUFFICIENT_STORAGE)
+        );
+    }
+
+    Ok(())
+}
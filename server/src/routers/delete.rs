@@ -0,0 +1,136 @@
+use crate::routers::assets::{check_unlocked, restore_cold};
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, ResponseError, api_error, api_response};
+use crate::state::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde::Deserialize;
+use shared::assets::{self, Asset, AssetType};
+use shared::events::{ObjectEvent, ObjectEventKind};
+use shared::{audit, buckets, root_dir, trash, versions};
+
+#[derive(Deserialize)]
+pub(super) struct DeleteOptions {
+    /// Delete a folder along with its content. Deleting a non-empty folder fails otherwise.
+    recursive: Option<bool>,
+    /// Delete the asset right away instead of moving it to the trash.
+    permanent: Option<bool>,
+    /// Delete files under governance retention.
+    bypass_governance: Option<bool>,
+}
+
+/// Delete a file or folder. Assets are moved to the owner's trash unless `permanent` is set,
+/// while files in versioned buckets get a delete marker.
+#[axum::debug_handler]
+pub(super) async fn delete_asset(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path((asset_type, asset_path)): Path<(AssetType, String)>,
+    Query(options): Query<DeleteOptions>,
+) -> ApiResponse<()> {
+    assets::check_path(&asset_path)
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    let asset = client.asset(&state, &asset_path).await?;
+    if asset.asset_type() != asset_type {
+        return Err(api_error("asset not found").with_status_code(StatusCode::NOT_FOUND));
+    }
+
+    let db = state.db();
+    let recursive = options.recursive.unwrap_or_default();
+    if let AssetType::Folder = asset_type {
+        let descendants = assets::descendants(db, asset.path()).await?;
+        if !descendants.is_empty() && !recursive {
+            return Err(
+                api_error("folder is not empty. Set recursive to delete its content.")
+                    .with_status_code(StatusCode::CONFLICT),
+            );
+        }
+
+        if descendants.iter().any(|d| d.owner_id() != asset.owner_id()) {
+            return Err(api_error("folder contains assets owned by someone else")
+                .with_status_code(StatusCode::CONFLICT));
+        }
+    }
+
+    let bypass = options.bypass_governance.unwrap_or_default();
+    check_unlocked(&state, asset.path(), bypass).await?;
+    cancel_uploads(&state, asset.path(), recursive).await?;
+    restore_cold(&state, asset.path()).await?;
+
+    let root_dir = state.config().root_dir()?;
+    let versioned = match asset.bucket_id() {
+        Some(id) => buckets::get_by_id(id, db).await?.versioning(),
+        None => false,
+    };
+
+    let target = root_dir.join(asset.path());
+    if versioned {
+        mark_deleted(&state, &asset).await?;
+        if target.is_dir() {
+            tokio::fs::remove_dir_all(&target).await?;
+        }
+    } else if options.permanent.unwrap_or_default() {
+        match asset_type {
+            AssetType::File => tokio::fs::remove_file(&target).await?,
+            AssetType::Folder => tokio::fs::remove_dir_all(&target).await?,
+        }
+    } else {
+        trash::move_to_trash(db, &root_dir, asset.owner_id(), asset.path()).await?;
+    }
+
+    assets::remove(db, &root_dir, asset.path()).await?;
+    audit::record(db, asset.owner_id(), "delete", asset.path(), None).await?;
+    let event = ObjectEvent::new(ObjectEventKind::Deleted, &asset)?;
+    state.events().publish(event).await;
+
+    api_response(())
+}
+
+/// Record delete markers for a versioned file, or for every file of a versioned folder.
+async fn mark_deleted(state: &AppState, asset: &Asset) -> Result<(), ResponseError> {
+    let db = state.db();
+    let root_dir = state.config().root_dir()?;
+    let bucket_id = asset.bucket_id().ok_or(api_error("asset has no bucket"))?;
+
+    let mut files = match asset.asset_type() {
+        AssetType::File => vec![],
+        AssetType::Folder => assets::descendants(db, asset.path()).await?,
+    };
+
+    if let AssetType::File = asset.asset_type() {
+        files.push(asset.clone());
+    }
+
+    for file in files.iter().filter(|f| f.asset_type() == AssetType::File) {
+        versions::mark_deleted(db, &root_dir, bucket_id, file.path()).await?;
+    }
+
+    Ok(())
+}
+
+/// Cancel in-flight resumable uploads targeting a deleted path and clean up their chunks.
+async fn cancel_uploads(
+    state: &AppState,
+    path: &str,
+    recursive: bool,
+) -> Result<(), ResponseError> {
+    if state.config().message_broker.is_none() {
+        return Ok(());
+    }
+
+    let sessions = state.broker()?.cancel_uploads(path, recursive).await?;
+    let tmp_dir = root_dir()?.join("tmp");
+    for id in sessions {
+        // live readers of the upload stop waiting for it.
+        state.live_uploads().finish(&id);
+        let tmp_path = tmp_dir.join(&id);
+        if tmp_path.exists()
+            && let Err(err) = tokio::fs::remove_file(tmp_path).await
+        {
+            tracing::error!("unable to clean up cancelled upload {id}: {err}");
+        }
+    }
+
+    Ok(())
+}
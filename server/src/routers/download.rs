@@ -0,0 +1,724 @@
+use crate::compression::{self, Encoding};
+use crate::images;
+use crate::routers::assets::metadata_headers;
+use crate::routers::middlewares::{ClientExtractor, DownloadMiddleware};
+use crate::routers::resp::{ApiResponse, ResponseError, api_error, api_response};
+use crate::state::AppState;
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use shared::acl::{self, Visibility};
+use shared::assets::{self, Asset, AssetType};
+use shared::hasher::errors::PayloadVerificationError;
+use shared::server::{
+    DownloadInfo, ImageTransform, OutputFormat, ResponseOverrides, TransformInfo, seconds_from_now,
+};
+use shared::{client, generate_nano_id, grants};
+use std::io::{Cursor, SeekFrom};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use validator::Validate;
+
+/// Requests with more ranges than this are served in full.
+const MAX_RANGES: usize = 16;
+
+#[derive(Deserialize, Validate)]
+pub(super) struct DownloadTokenOptions {
+    /// File the token grants access to.
+    path: Option<String>,
+    /// Grant access to every file under this prefix instead of a single file.
+    prefix: Option<String>,
+    #[validate(range(min = 30))]
+    expires: i64,
+    /// Number of downloads after which the token stops working.
+    #[validate(range(min = 1))]
+    max_downloads: Option<u32>,
+    #[serde(flatten)]
+    overrides: ResponseOverrides,
+}
+
+#[derive(Deserialize, Validate)]
+pub(super) struct TransformTokenOptions {
+    /// Image the token allows transforming.
+    path: String,
+    #[validate(range(min = 30))]
+    expires: i64,
+    #[validate(nested)]
+    transform: ImageTransform,
+}
+
+#[derive(Deserialize)]
+pub(super) struct TransformSignature {
+    /// Token created with [create_transform_token].
+    sig: Option<String>,
+}
+
+/// Download a file. Clients may download any file they own, with `path` resolved within their
+/// namespace. Public files are served without authentication given their storage path, and
+/// only their `disposition` and `filename` can then be overridden.
+///
+/// Images are transformed when [ImageTransform] parameters are given. Unauthenticated requests
+/// must then be signed with a token allowing that exact transformation.
+#[axum::debug_handler]
+pub(super) async fn download_asset(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(overrides): Query<ResponseOverrides>,
+    Query(transform): Query<ImageTransform>,
+    Query(signature): Query<TransformSignature>,
+    headers: HeaderMap,
+) -> Result<Response, ResponseError> {
+    let authenticated = headers.contains_key(&state.config().client_header_key);
+    let (asset, overrides) = match authenticated {
+        true => {
+            let client = ClientExtractor::from_headers(&state, &headers).await?;
+            (client.asset(&state, &path).await?, overrides)
+        }
+        // anyone may name a download, but not change how it's interpreted or cached.
+        false => {
+            let overrides = ResponseOverrides {
+                content_type: None,
+                cache_control: None,
+                ..overrides
+            };
+            (public_asset(&state, &path).await?, overrides)
+        }
+    };
+
+    if transform.is_empty() {
+        return serve(&state, &asset, &headers, &overrides).await;
+    }
+
+    transform
+        .validate()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    if !authenticated {
+        check_transform_signature(&state, &asset, &transform, signature.sig.as_deref()).await?;
+    }
+
+    serve_transformed(&state, &asset, &transform, &headers, &overrides).await
+}
+
+/// Check that `sig` allows applying `transform` to `asset`, and was signed by its owner.
+async fn check_transform_signature(
+    state: &AppState,
+    asset: &Asset,
+    transform: &ImageTransform,
+    sig: Option<&str>,
+) -> Result<(), ResponseError> {
+    let sig = sig.ok_or(
+        api_error("image transformations require a signed URL")
+            .with_status_code(StatusCode::FORBIDDEN),
+    )?;
+
+    let info = TransformInfo::verify(sig, state.db(), state.hasher())
+        .await
+        .map_err(|err| {
+            let resp = match err {
+                PayloadVerificationError::Error(err) => api_error(err),
+                PayloadVerificationError::Expired => api_error("transformation link expired"),
+            };
+
+            resp.with_status_code(StatusCode::FORBIDDEN)
+        })?;
+
+    // only the owner may sign transformations of an asset.
+    let signer = client::id_by_pid(state.db(), &info.client_id).await?;
+    let owner_id = client::owner_id(state.db(), signer).await?;
+    if owner_id != asset.owner_id() || info.path != asset.path() || info.transform != *transform {
+        return Err(api_error("signature does not allow this transformation")
+            .with_status_code(StatusCode::FORBIDDEN));
+    }
+
+    Ok(())
+}
+
+/// Serve an image with `transform` applied, rendering it unless cached.
+async fn serve_transformed(
+    state: &AppState,
+    asset: &Asset,
+    transform: &ImageTransform,
+    headers: &HeaderMap,
+    overrides: &ResponseOverrides,
+) -> Result<Response, ResponseError> {
+    if asset.asset_type() != AssetType::File {
+        return Err(api_error("asset not found").with_status_code(StatusCode::NOT_FOUND));
+    }
+
+    let source = images::format(asset).ok_or(
+        api_error("only JPEG, PNG, WebP and GIF images can be transformed")
+            .with_status_code(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+    )?;
+
+    let output = images::output_format(transform, source, headers);
+    let root_dir = state.config().root_dir()?;
+    let cold_volume = state.config().cold_volume()?;
+    let cold_volume = cold_volume.as_deref();
+    let rendered = images::render(&root_dir, cold_volume, asset, source, output, transform)
+        .await
+        .map_err(|err| api_error(err).with_status_code(StatusCode::UNPROCESSABLE_ENTITY))?;
+
+    // converted images are named after their format.
+    let name = asset_name(asset);
+    let name = match output == source {
+        true => name.to_string(),
+        false => {
+            let stem = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(name);
+            format!("{stem}.{}", images::extension(output))
+        }
+    };
+
+    let content = Content {
+        path: rendered.path,
+        name,
+        etag: Some(format!("\"{}\"", rendered.tag)),
+        content_type: rendered.content_type.to_string(),
+        vary: matches!(transform.format, Some(OutputFormat::Auto)).then_some(header::ACCEPT),
+        encoding: None,
+    };
+
+    send(asset, content, headers, overrides).await
+}
+
+/// Download a file with a signed token. Response overrides are part of the token; query
+/// parameters can't change them.
+#[axum::debug_handler]
+pub(super) async fn signed_download(
+    State(state): State<AppState>,
+    DownloadMiddleware(info): DownloadMiddleware,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ResponseError> {
+    assets::check_path(&path)
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    let client = ClientExtractor::new(client::id_by_pid(state.db(), &info.client_id).await?);
+    let path = client.resolve(&state, &path).await?;
+    if !info.allows(&path) {
+        return Err(api_error("token does not grant access to this asset")
+            .with_status_code(StatusCode::FORBIDDEN));
+    }
+
+    let asset = client.asset(&state, &path).await?;
+    serve_granted(&state, &info, &asset, &headers).await
+}
+
+/// Serve a file with the response overrides of a download token, counting the download against
+/// the token's limit. Conditional requests answered with `304 Not Modified` aren't counted.
+pub(super) async fn serve_granted(
+    state: &AppState,
+    info: &DownloadInfo,
+    asset: &Asset,
+    headers: &HeaderMap,
+) -> Result<Response, ResponseError> {
+    if info.max_downloads.is_some() && !grants::available(state.db(), &info.id).await? {
+        return Err(download_limit_reached());
+    }
+
+    let resp = serve(state, asset, headers, &info.overrides).await?;
+    if resp.status() != StatusCode::NOT_MODIFIED {
+        consume_grant(state, info).await?;
+    }
+
+    Ok(resp)
+}
+
+/// Count a download against the limit of a download token, if it has one.
+pub(super) async fn consume_grant(
+    state: &AppState,
+    info: &DownloadInfo,
+) -> Result<(), ResponseError> {
+    if info.max_downloads.is_some() && !grants::consume(state.db(), &info.id).await? {
+        return Err(download_limit_reached());
+    }
+
+    Ok(())
+}
+
+fn download_limit_reached() -> ResponseError {
+    api_error("download limit reached").with_status_code(StatusCode::GONE)
+}
+
+/// Get an asset anyone may read. Private assets require authentication.
+async fn public_asset(state: &AppState, path: &str) -> Result<Asset, ResponseError> {
+    assets::check_path(path)
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    let asset = assets::get(state.db(), path)
+        .await?
+        .ok_or(api_error("asset not found").with_status_code(StatusCode::NOT_FOUND))?;
+
+    match acl::effective(state.db(), &asset).await? {
+        Visibility::PublicRead => Ok(asset),
+        Visibility::Private => Err(api_error("authentication required to access this asset")
+            .with_status_code(StatusCode::UNAUTHORIZED)),
+    }
+}
+
+/// Create a signed, expiring download token for a file or a prefix.
+#[axum::debug_handler]
+pub(super) async fn create_download_token(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<DownloadTokenOptions>,
+) -> ApiResponse<String> {
+    options
+        .validate()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    let (path, prefix) = match (&options.path, &options.prefix) {
+        (Some(path), None) => (
+            Some(client.asset(&state, path).await?.path().to_string()),
+            None,
+        ),
+        (None, Some(prefix)) => {
+            // a folder, so that `docs` doesn't grant `docs-secret/…`.
+            let folder = client.resolve(&state, prefix).await?;
+            (None, Some(format!("{folder}/")))
+        }
+        _ => {
+            return Err(api_error("provide either a path or a prefix")
+                .with_status_code(StatusCode::BAD_REQUEST));
+        }
+    };
+
+    content_disposition("", &options.overrides)?;
+
+    let id = generate_nano_id(32);
+    let exp = seconds_from_now(options.expires)?;
+    if let Some(max_downloads) = options.max_downloads {
+        grants::create(state.db(), &id, max_downloads, exp).await?;
+    }
+
+    let (pid, key) = client::get_claims_data(state.db(), &client.id()).await?;
+    let info = DownloadInfo {
+        id,
+        client_id: pid,
+        path,
+        prefix,
+        max_downloads: options.max_downloads,
+        overrides: options.overrides,
+        exp,
+    };
+
+    let token = info.sign(&key, state.hasher())?;
+    api_response(token)
+}
+
+/// Create a signed, expiring token allowing anyone to download an image with a transformation
+/// applied. The token is passed as the `sig` query parameter, along with the transformation's.
+#[axum::debug_handler]
+pub(super) async fn create_transform_token(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<TransformTokenOptions>,
+) -> ApiResponse<String> {
+    options
+        .validate()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    if options.transform.is_empty() {
+        return Err(api_error("provide at least one transformation")
+            .with_status_code(StatusCode::BAD_REQUEST));
+    }
+
+    let asset = client.asset(&state, &options.path).await?;
+    let (pid, key) = client::get_claims_data(state.db(), &client.id()).await?;
+    let info = TransformInfo {
+        client_id: pid,
+        path: asset.path().to_string(),
+        transform: options.transform,
+        exp: seconds_from_now(options.expires)?,
+    };
+
+    let token = info.sign(&key, state.hasher())?;
+    api_response(token)
+}
+
+/// What a download responds with: a stored file, or one derived from it.
+struct Content {
+    path: PathBuf,
+    /// File name suggested to the browser.
+    name: String,
+    etag: Option<String>,
+    content_type: String,
+    /// Request header the content was negotiated with.
+    vary: Option<HeaderName>,
+    /// Encoding of a precompressed file.
+    encoding: Option<Encoding>,
+}
+
+/// Serve an indexed file, honoring conditional and range request headers. A precompressed copy
+/// is sent instead when the request accepts its encoding and isn't for a range.
+pub(super) async fn serve(
+    state: &AppState,
+    asset: &Asset,
+    headers: &HeaderMap,
+    overrides: &ResponseOverrides,
+) -> Result<Response, ResponseError> {
+    if asset.asset_type() != AssetType::File {
+        return Err(api_error("asset not found").with_status_code(StatusCode::NOT_FOUND));
+    }
+
+    let root_dir = state.config().root_dir()?;
+    let cold_volume = state.config().cold_volume()?;
+    let mut content = Content {
+        path: asset.stored_path(&root_dir, cold_volume.as_deref())?,
+        name: asset_name(asset).to_string(),
+        etag: asset.checksum().map(|checksum| format!("\"{checksum}\"")),
+        content_type: asset
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string(),
+        vary: None,
+        encoding: None,
+    };
+
+    if !headers.contains_key(header::RANGE)
+        && let Some((path, encoding)) = compression::precompressed(&root_dir, asset, headers)
+    {
+        content.path = path;
+        content.etag = asset
+            .checksum()
+            .map(|checksum| format!("\"{checksum}-{}\"", encoding.extension()));
+        content.vary = Some(header::ACCEPT_ENCODING);
+        content.encoding = Some(encoding);
+    }
+
+    send(asset, content, headers, overrides).await
+}
+
+fn asset_name(asset: &Asset) -> &str {
+    asset.path().rsplit('/').next().unwrap_or_default()
+}
+
+/// Respond with `content` on behalf of `asset`.
+async fn send(
+    asset: &Asset,
+    content: Content,
+    headers: &HeaderMap,
+    overrides: &ResponseOverrides,
+) -> Result<Response, ResponseError> {
+    let file_path = content.path;
+    let file = tokio::fs::File::open(&file_path)
+        .await
+        .map_err(|_| api_error("asset not found").with_status_code(StatusCode::NOT_FOUND))?;
+
+    let file_meta = file.metadata().await?;
+    let size = file_meta.len();
+    let modified = file_meta.modified().ok().map(truncate_to_seconds);
+    let etag = content.etag;
+
+    let mut resp_headers = metadata_headers(asset.metadata());
+    resp_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    resp_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    if let Some(vary) = content.vary {
+        resp_headers.insert(header::VARY, HeaderValue::from_name(vary));
+    }
+    if let Some(encoding) = content.encoding {
+        resp_headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.name()),
+        );
+    }
+    if let Some(etag) = &etag {
+        resp_headers.insert(
+            header::ETAG,
+            HeaderValue::from_str(etag).map_err(api_error)?,
+        );
+    }
+
+    if let Some(modified) = modified {
+        let value = httpdate::fmt_http_date(modified);
+        resp_headers.insert(
+            header::LAST_MODIFIED,
+            HeaderValue::from_str(&value).map_err(api_error)?,
+        );
+    }
+
+    if not_modified(headers, etag.as_deref(), modified) {
+        return Ok((StatusCode::NOT_MODIFIED, resp_headers).into_response());
+    }
+
+    let content_type = overrides
+        .content_type
+        .clone()
+        .unwrap_or(content.content_type);
+
+    resp_headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&content_disposition(&content.name, overrides)?)
+            .map_err(api_error)?,
+    );
+
+    if let Some(cache_control) = &overrides.cache_control {
+        resp_headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_str(cache_control)
+                .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?,
+        );
+    }
+
+    let ranges = match headers.get(header::RANGE) {
+        Some(range) if range_applies(headers, etag.as_deref(), modified) => {
+            match parse_ranges(range.to_str().unwrap_or_default(), size) {
+                Some(ranges) => ranges,
+                None => {
+                    let value = format!("bytes */{size}");
+                    resp_headers.insert(
+                        header::CONTENT_RANGE,
+                        HeaderValue::from_str(&value).map_err(api_error)?,
+                    );
+                    return Ok((StatusCode::RANGE_NOT_SATISFIABLE, resp_headers).into_response());
+                }
+            }
+        }
+        _ => vec![],
+    };
+
+    match ranges.len() {
+        0 => {
+            resp_headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(&content_type).map_err(api_error)?,
+            );
+            resp_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+
+            let body = Body::from_stream(ReaderStream::new(file));
+            Ok((StatusCode::OK, resp_headers, body).into_response())
+        }
+        1 => {
+            let range = &ranges[0];
+            let value = format!("bytes {}-{}/{size}", range.start(), range.end());
+            let length = range.end() - range.start() + 1;
+
+            resp_headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(&content_type).map_err(api_error)?,
+            );
+            resp_headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&value).map_err(api_error)?,
+            );
+            resp_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+
+            let mut file = file;
+            file.seek(SeekFrom::Start(*range.start())).await?;
+            let body = Body::from_stream(ReaderStream::new(file.take(length)));
+
+            Ok((StatusCode::PARTIAL_CONTENT, resp_headers, body).into_response())
+        }
+        _ => {
+            let boundary = generate_nano_id(32);
+            let (reader, length) =
+                multipart_body(&file_path, &ranges, size, &content_type, &boundary).await?;
+
+            let value = format!("multipart/byteranges; boundary={boundary}");
+            resp_headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(&value).map_err(api_error)?,
+            );
+            resp_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+
+            let body = Body::from_stream(ReaderStream::new(reader));
+            Ok((StatusCode::PARTIAL_CONTENT, resp_headers, body).into_response())
+        }
+    }
+}
+
+/// Evaluate `If-None-Match`, then `If-Modified-Since` when the former is absent.
+fn not_modified(headers: &HeaderMap, etag: Option<&str>, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        let if_none_match = if_none_match.to_str().unwrap_or_default();
+        return match etag {
+            Some(etag) => etag_list_matches(if_none_match, etag),
+            None => false,
+        };
+    }
+
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+
+    matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+}
+
+/// A `Range` only applies when `If-Range`, if any, still matches the file.
+fn range_applies(headers: &HeaderMap, etag: Option<&str>, modified: Option<SystemTime>) -> bool {
+    let if_range = match headers.get(header::IF_RANGE) {
+        Some(value) => value.to_str().unwrap_or_default(),
+        None => return true,
+    };
+
+    if if_range.starts_with('"') {
+        return etag == Some(if_range);
+    }
+
+    match (httpdate::parse_http_date(if_range), modified) {
+        (Ok(date), Some(modified)) => modified == date,
+        _ => false,
+    }
+}
+
+/// Weak comparison of `etag` with a comma separated list of entity tags, or `*`.
+fn etag_list_matches(list: &str, etag: &str) -> bool {
+    list.split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Parse a `bytes=` range header into inclusive ranges, sorted and merged. Returns `None` when
+/// no range is satisfiable, and an empty list when the header should be ignored.
+fn parse_ranges(value: &str, size: u64) -> Option<Vec<RangeInclusive<u64>>> {
+    let specs = match value.trim().strip_prefix("bytes=") {
+        Some(specs) => specs,
+        None => return Some(vec![]),
+    };
+
+    let mut ranges = vec![];
+    for spec in specs.split(',').map(str::trim) {
+        let (start, end) = match spec.split_once('-') {
+            Some(bounds) => bounds,
+            None => return Some(vec![]),
+        };
+
+        let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => start..=end.min(size.saturating_sub(1)),
+            (Ok(start), Err(_)) if end.is_empty() => start..=size.saturating_sub(1),
+            (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+                size.saturating_sub(suffix)..=size.saturating_sub(1)
+            }
+            _ => return Some(vec![]),
+        };
+
+        if *range.start() < size {
+            ranges.push(range);
+        }
+    }
+
+    if ranges.is_empty() {
+        return None;
+    }
+
+    if ranges.len() > MAX_RANGES {
+        return Some(vec![]);
+    }
+
+    ranges.sort_by_key(|range| *range.start());
+    let mut merged: Vec<RangeInclusive<u64>> = vec![];
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if *range.start() <= last.end() + 1 => {
+                *last = *last.start()..=*last.end().max(range.end());
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    Some(merged)
+}
+
+/// Build a `multipart/byteranges` body streaming each range from disk. Returns the body reader
+/// and its length.
+async fn multipart_body(
+    file_path: &std::path::Path,
+    ranges: &[RangeInclusive<u64>],
+    size: u64,
+    content_type: &str,
+    boundary: &str,
+) -> Result<(Box<dyn AsyncRead + Send + Unpin>, u64), ResponseError> {
+    let mut reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(Cursor::new(vec![]));
+    let mut length = 0;
+
+    for range in ranges {
+        let part_header = format!(
+            "\r\n--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: bytes {}-{}/{size}\r\n\r\n",
+            range.start(),
+            range.end()
+        );
+
+        let part_length = range.end() - range.start() + 1;
+        let mut file = tokio::fs::File::open(file_path).await?;
+        file.seek(SeekFrom::Start(*range.start())).await?;
+
+        length += part_header.len() as u64 + part_length;
+        reader = Box::new(
+            reader
+                .chain(Cursor::new(part_header.into_bytes()))
+                .chain(file.take(part_length)),
+        );
+    }
+
+    let closing = format!("\r\n--{boundary}--\r\n");
+    length += closing.len() as u64;
+    reader = Box::new(reader.chain(Cursor::new(closing.into_bytes())));
+
+    Ok((reader, length))
+}
+
+/// Build a `Content-Disposition` value for a file named `name`, unless overridden.
+pub(super) fn content_disposition(
+    name: &str,
+    overrides: &ResponseOverrides,
+) -> Result<String, ResponseError> {
+    let disposition = match overrides.disposition.as_deref() {
+        None | Some("inline") => "inline",
+        Some("attachment") => "attachment",
+        Some(_) => {
+            return Err(api_error("disposition must be inline or attachment")
+                .with_status_code(StatusCode::BAD_REQUEST));
+        }
+    };
+
+    let filename = overrides.filename.as_deref().unwrap_or(name);
+
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+
+    Ok(format!(
+        "{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{}",
+        encode_rfc5987(filename)
+    ))
+}
+
+/// Percent-encode a value for use in an RFC 5987 extended header parameter.
+fn encode_rfc5987(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+/// HTTP dates have a one second resolution.
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
@@ -0,0 +1,100 @@
+use crate::routers::listing::{authorize, resolve_prefix};
+use crate::routers::resp::ResponseError;
+use crate::state::AppState;
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::{Stream, stream};
+use serde::{Deserialize, Serialize};
+use shared::assets::{AssetType, relative_path};
+use shared::events::ObjectEvent;
+use shared::{buckets, client};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+#[derive(Deserialize)]
+pub(super) struct EventsQuery {
+    prefix: Option<String>,
+    /// pid of the bucket to watch.
+    bucket: Option<String>,
+    /// Signed listing token. Client authorization is required when it's not provided.
+    token: Option<String>,
+}
+
+/// Data of an event sent to subscribers. Paths are relative to the client or bucket namespace.
+#[derive(Serialize)]
+struct EventData<'a> {
+    path: &'a str,
+    from: Option<&'a str>,
+    /// pid of the bucket holding the object, if any.
+    bucket: Option<&'a str>,
+    asset_type: AssetType,
+    time: i64,
+}
+
+/// Stream `created`, `updated`, `deleted` and `moved` events of the client's assets under a
+/// prefix, as Server-Sent Events. Subscribers falling too far behind get a `lagged` event
+/// carrying the number of events they missed.
+#[axum::debug_handler]
+pub(super) async fn watch_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ResponseError> {
+    let mut prefix = query.prefix.unwrap_or_default();
+    let mut bucket = query.bucket;
+    let client = authorize(
+        &state,
+        &headers,
+        query.token.as_deref(),
+        &mut prefix,
+        &mut bucket,
+    )
+    .await?;
+    if let Some(pid) = &bucket {
+        client.bucket(&state, pid).await?;
+    }
+
+    let prefix = resolve_prefix(&state, &client, &prefix, bucket.as_deref()).await?;
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+
+    // subscribe before responding, so no event after the response is missed.
+    let receiver = state.events().subscribe();
+    let events = stream::unfold(receiver, move |mut receiver| {
+        let prefix = prefix.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if event.owner_id == owner_id && event.matches(&prefix) => {
+                        return Some((Ok(sse_event(&event)), receiver));
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        let event = Event::default().event("lagged").data(missed.to_string());
+                        return Some((Ok(event), receiver));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn sse_event(event: &ObjectEvent) -> Event {
+    let data = EventData {
+        path: relative_path(&event.path),
+        from: event.from.as_deref().map(relative_path),
+        bucket: buckets::pid_of(&event.path),
+        asset_type: event.asset_type,
+        time: event.time,
+    };
+
+    let event = Event::default().event(event.kind.name());
+    match event.json_data(&data) {
+        Ok(event) => event,
+        // serializing plain strings and numbers doesn't fail.
+        Err(_) => Event::default().event("error"),
+    }
+}
@@ -0,0 +1,137 @@
+use crate::bundle::{self, BundleEntry, BundleFormat};
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ResponseError, api_error};
+use crate::state::AppState;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use globset::{GlobBuilder, GlobMatcher};
+use serde::Deserialize;
+use shared::acl::{self, Visibility};
+use shared::assets::{self, Asset, AssetType};
+use shared::client;
+use tokio_util::io::ReaderStream;
+
+#[derive(Deserialize)]
+pub(super) struct FolderDownloadOptions {
+    format: Option<BundleFormat>,
+    /// Only bundle files whose path within the folder matches this glob.
+    include: Option<String>,
+    /// Leave out files whose path within the folder matches this glob.
+    exclude: Option<String>,
+}
+
+/// Download a folder as a zip or tar.gz archive, streamed as it's written. Clients get every
+/// file they own under the folder, with `path` resolved within their namespace. Without
+/// authentication, `path` is a storage path and only public files are bundled.
+#[axum::debug_handler]
+pub(super) async fn download_folder(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(options): Query<FolderDownloadOptions>,
+    headers: HeaderMap,
+) -> Result<Response, ResponseError> {
+    let include = options.include.as_deref().map(compile_glob).transpose()?;
+    let exclude = options.exclude.as_deref().map(compile_glob).transpose()?;
+    let not_found = || api_error("folder not found").with_status_code(StatusCode::NOT_FOUND);
+
+    let client = match headers.contains_key(&state.config().client_header_key) {
+        true => Some(ClientExtractor::from_headers(&state, &headers).await?),
+        false => None,
+    };
+
+    let folder = match &client {
+        Some(client) => client.asset(&state, &path).await?,
+        None => {
+            assets::check_path(&path)
+                .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+            assets::get(state.db(), &path)
+                .await?
+                .ok_or_else(not_found)?
+        }
+    };
+
+    if folder.asset_type() != AssetType::Folder {
+        return Err(not_found());
+    }
+
+    let owner_id = match &client {
+        Some(client) => Some(client::owner_id(state.db(), client.id()).await?),
+        None => None,
+    };
+
+    let root_dir = state.config().root_dir()?;
+    let cold_volume = state.config().cold_volume()?;
+    let prefix = format!("{}/", folder.path());
+    let mut entries = vec![];
+    for asset in assets::descendants(state.db(), folder.path()).await? {
+        let name = asset.path().strip_prefix(&prefix).unwrap_or(asset.path());
+        let selected = asset.asset_type() == AssetType::File
+            && include.as_ref().is_none_or(|glob| glob.is_match(name))
+            && !exclude.as_ref().is_some_and(|glob| glob.is_match(name));
+
+        if selected && readable(&state, &asset, owner_id).await? {
+            entries.push(BundleEntry {
+                name: name.to_string(),
+                path: asset.stored_path(&root_dir, cold_volume.as_deref())?,
+            });
+        }
+    }
+
+    // don't reveal private folders to anonymous readers.
+    if client.is_none() && entries.is_empty() {
+        return Err(api_error("authentication required to access this folder")
+            .with_status_code(StatusCode::UNAUTHORIZED));
+    }
+
+    let format = options.format.unwrap_or_default();
+    let name = folder.path().rsplit('/').next().unwrap_or_default();
+    let filename: String = name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+
+    let disposition = format!("attachment; filename=\"{filename}.{}\"", format.extension());
+    let mut resp_headers = HeaderMap::new();
+    resp_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    resp_headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).map_err(api_error)?,
+    );
+
+    let reader = bundle::stream(format, entries);
+    let body = Body::from_stream(ReaderStream::new(reader));
+    Ok((StatusCode::OK, resp_headers, body).into_response())
+}
+
+/// Whether a file may be bundled: clients read the files of their owner, anybody else reads
+/// public files.
+async fn readable(
+    state: &AppState,
+    asset: &Asset,
+    owner_id: Option<i32>,
+) -> Result<bool, ResponseError> {
+    let readable = match owner_id {
+        Some(owner_id) => asset.owner_id() == owner_id,
+        None => acl::effective(state.db(), asset).await? == Visibility::PublicRead,
+    };
+
+    Ok(readable)
+}
+
+fn compile_glob(glob: &str) -> Result<GlobMatcher, ResponseError> {
+    let glob = GlobBuilder::new(glob.trim_start_matches('/'))
+        .literal_separator(true)
+        .build()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    Ok(glob.compile_matcher())
+}
@@ -0,0 +1,124 @@
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, api_error, api_response};
+use crate::state::AppState;
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde::Deserialize;
+use shared::audit::{self, AuditEntry};
+use shared::client;
+use shared::lifecycle::{self, LifecycleAction, LifecycleRule, LifecycleRuleArgs};
+use validator::Validate;
+
+#[derive(Deserialize, Validate)]
+pub(super) struct CreateRuleOptions {
+    /// pid of the bucket the rule is limited to.
+    bucket: Option<String>,
+    prefix: Option<String>,
+    glob: Option<String>,
+    /// Limit the rule to objects tagged `key=value` (or just `key`).
+    tag: Option<String>,
+    /// Age (in days) after which the rule applies to an object.
+    #[validate(range(min = 1, max = lifecycle::MAX_AGE))]
+    age: u32,
+    action: LifecycleAction,
+}
+
+#[derive(Deserialize)]
+pub(super) struct AuditQuery {
+    limit: Option<i64>,
+}
+
+/// Create a lifecycle rule for the client or one of its buckets.
+#[axum::debug_handler]
+pub(super) async fn create_rule(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<CreateRuleOptions>,
+) -> ApiResponse<String> {
+    options
+        .validate()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    if let LifecycleAction::Transition = options.action
+        && state.config().cold_volume.is_none()
+    {
+        return Err(
+            api_error("transition rules require a cold_volume to be configured.")
+                .with_status_code(StatusCode::BAD_REQUEST),
+        );
+    }
+
+    let bucket_id = match &options.bucket {
+        Some(pid) => Some(client.bucket(&state, pid).await?.id()),
+        None => None,
+    };
+
+    let tag = options
+        .tag
+        .map(|tag| tag.parse())
+        .transpose()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    let args = LifecycleRuleArgs {
+        bucket_id,
+        prefix: options.prefix,
+        glob: options.glob,
+        tag,
+        age: options.age,
+        action: options.action,
+    };
+
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    let pid = lifecycle::create(state.db(), owner_id, args)
+        .await
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    api_response(pid)
+}
+
+/// List the client's lifecycle rules.
+#[axum::debug_handler]
+pub(super) async fn list_rules(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+) -> ApiResponse<Vec<LifecycleRule>> {
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    let rules = lifecycle::list(state.db(), owner_id).await?;
+
+    api_response(rules)
+}
+
+#[axum::debug_handler]
+pub(super) async fn delete_rule(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+) -> ApiResponse<()> {
+    let not_found = || api_error("rule not found").with_status_code(StatusCode::NOT_FOUND);
+    let rule = lifecycle::get(state.db(), &pid)
+        .await
+        .map_err(|_| not_found())?;
+
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    if rule.owner_id() != owner_id {
+        return Err(not_found());
+    }
+
+    lifecycle::delete(state.db(), &rule).await?;
+    api_response(())
+}
+
+/// List the client's audit trail, newest first.
+#[axum::debug_handler]
+pub(super) async fn list_audit(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Query(query): Query<AuditQuery>,
+) -> ApiResponse<Vec<AuditEntry>> {
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let entries = audit::list(state.db(), owner_id, limit).await?;
+
+    api_response(entries)
+}
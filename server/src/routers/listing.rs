@@ -0,0 +1,176 @@
+use crate::routers::assets::parse_tag_filters;
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, ResponseError, api_error, api_response};
+use crate::state::AppState;
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use serde::Deserialize;
+use shared::listing::{self, ListOptions, ListResult};
+use shared::server::{ListInfo, seconds_from_now};
+use shared::{buckets, client};
+use validator::Validate;
+
+#[derive(Deserialize)]
+pub(super) struct ListQuery {
+    prefix: Option<String>,
+    delimiter: Option<String>,
+    continuation_token: Option<String>,
+    start_after: Option<String>,
+    max_keys: Option<usize>,
+    /// pid of the bucket to list.
+    bucket: Option<String>,
+    /// Comma separated tag filters, e.g. `project=alpha,status`.
+    tags: Option<String>,
+    /// Signed listing token. Client authorization is required when it's not provided.
+    token: Option<String>,
+}
+
+#[derive(Deserialize, Validate)]
+pub(super) struct ListTokenOptions {
+    #[serde(default)]
+    prefix: String,
+    bucket: Option<String>,
+    #[validate(range(min = 30))]
+    expires: i64,
+}
+
+/// List assets under a prefix, S3 ListObjectsV2 style.
+#[axum::debug_handler]
+pub(super) async fn list_assets(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ListQuery>,
+) -> ApiResponse<ListResult> {
+    let db = state.db();
+    let mut prefix = query.prefix.unwrap_or_default();
+    let mut bucket = query.bucket;
+
+    let client = authorize(
+        &state,
+        &headers,
+        query.token.as_deref(),
+        &mut prefix,
+        &mut bucket,
+    )
+    .await?;
+
+    let client_id = client.id();
+    let bucket_id = match &bucket {
+        Some(pid) => Some(client.bucket(&state, pid).await?.id()),
+        None => None,
+    };
+
+    let prefix = resolve_prefix(&state, &client, &prefix, bucket.as_deref()).await?;
+    // keys are listed within their namespace, and sent back as such.
+    let start_after = match &query.start_after {
+        Some(key) => Some(resolve_prefix(&state, &client, key, bucket.as_deref()).await?),
+        None => None,
+    };
+
+    let tags = match &query.tags {
+        Some(tags) => parse_tag_filters(tags)?,
+        None => vec![],
+    };
+
+    let options = ListOptions {
+        prefix,
+        delimiter: query.delimiter,
+        continuation_token: query.continuation_token,
+        start_after,
+        max_keys: query.max_keys,
+        bucket_id,
+        tags,
+    };
+
+    let owner_id = client::owner_id(db, client_id).await?;
+    let result = listing::list(db, owner_id, options)
+        .await
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    api_response(result)
+}
+
+/// Authorize access to a client's assets with a signed listing `token`, or the client header
+/// when there's none. A token narrows `prefix` and `bucket` down to the ones it was issued for.
+pub(super) async fn authorize(
+    state: &AppState,
+    headers: &HeaderMap,
+    token: Option<&str>,
+    prefix: &mut String,
+    bucket: &mut Option<String>,
+) -> Result<ClientExtractor, ResponseError> {
+    let Some(token) = token else {
+        return ClientExtractor::from_headers(state, headers).await;
+    };
+
+    let db = state.db();
+    let info = ListInfo::verify(token, db, state.hasher())
+        .await
+        .map_err(|_| api_error("invalid list token").with_status_code(StatusCode::UNAUTHORIZED))?;
+
+    // tokens only reach the bucket, or the client's namespace, they were issued for. Their
+    // prefix is a folder, so `docs` doesn't reach `docs2`.
+    let storage_path = |path: &str| match &info.bucket {
+        Some(pid) => buckets::object_path(pid, path),
+        None => client::object_path(&info.client_id, path),
+    };
+
+    let scope = format!("{}/", storage_path(&info.prefix));
+    if !storage_path(prefix).starts_with(&scope) {
+        *prefix = scope;
+    }
+
+    *bucket = info.bucket;
+
+    let client_id = client::id_by_pid(db, &info.client_id).await?;
+    Ok(ClientExtractor::new(client_id))
+}
+
+/// Create a signed token allowing anyone holding it to list the client's assets under a prefix.
+#[axum::debug_handler]
+pub(super) async fn create_list_token(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(options): Json<ListTokenOptions>,
+) -> ApiResponse<String> {
+    options
+        .validate()
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    if let Some(bucket) = &options.bucket {
+        client.bucket(&state, bucket).await?;
+    }
+
+    let (pid, key) = client::get_claims_data(state.db(), &client.id()).await?;
+    let info = ListInfo {
+        client_id: pid,
+        prefix: options.prefix,
+        bucket: options.bucket,
+        exp: seconds_from_now(options.expires)?,
+    };
+
+    let token = info.sign(&key, state.hasher())?;
+    api_response(token)
+}
+
+/// Resolve a listing prefix within the bucket being listed, or the client's namespace. An empty
+/// prefix without a bucket lists everything the client owns.
+pub(super) async fn resolve_prefix(
+    state: &AppState,
+    client: &ClientExtractor,
+    prefix: &str,
+    bucket: Option<&str>,
+) -> Result<String, ResponseError> {
+    let resolved = match bucket {
+        Some(pid) => buckets::object_path(pid, prefix),
+        None if prefix.trim_matches('/').is_empty() => return Ok(String::new()),
+        None => client.resolve(state, prefix).await?,
+    };
+
+    // a trailing separator keeps `docs/` from matching `docs2`.
+    match prefix.ends_with('/') || prefix.trim_matches('/').is_empty() {
+        true => Ok(format!("{resolved}/")),
+        false => Ok(resolved),
+    }
+}
@@ -0,0 +1,240 @@
+use crate::routers::download::{consume_grant, content_disposition, serve, serve_granted};
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ResponseError, api_error};
+use crate::state::AppState;
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use futures_util::stream;
+use serde::Deserialize;
+use shared::hasher::errors::PayloadVerificationError;
+use shared::server::{DownloadInfo, ResponseOverrides, UploadInfo};
+use shared::{assets, buckets, client, root_dir};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::sync::watch;
+
+/// How often a live download looks for new bytes when it isn't told about new chunks.
+const LIVE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Size of the buffer staged bytes are read with.
+const LIVE_READ_SIZE: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+pub(super) struct LiveQuery {
+    /// Download token granting access to the file, for readers without a client token.
+    token: Option<String>,
+}
+
+/// Download a file while it's being uploaded through a resumable session. The response streams
+/// the bytes already staged, then new chunks as they arrive, until the upload completes or no
+/// chunk arrives before the session's chunk tokens expire. Files which aren't being uploaded are
+/// served like regular downloads.
+#[axum::debug_handler]
+pub(super) async fn live_download(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(query): Query<LiveQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ResponseError> {
+    assets::check_path(&path)
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    let (client, path, grant) = match (
+        headers.contains_key(&state.config().client_header_key),
+        query.token,
+    ) {
+        (true, _) => {
+            let client = ClientExtractor::from_headers(&state, &headers).await?;
+            let path = client.resolve(&state, &path).await?;
+            (client, path, None)
+        }
+        (false, Some(token)) => {
+            let info = DownloadInfo::verify(&token, state.db(), state.hasher())
+                .await
+                .map_err(|err| {
+                    let resp = match err {
+                        PayloadVerificationError::Error(err) => api_error(err),
+                        PayloadVerificationError::Expired => api_error("download link expired"),
+                    };
+
+                    resp.with_status_code(StatusCode::UNAUTHORIZED)
+                })?;
+
+            let client =
+                ClientExtractor::new(client::id_by_pid(state.db(), &info.client_id).await?);
+            let path = client.resolve(&state, &path).await?;
+            if !info.allows(&path) {
+                return Err(api_error("token does not grant access to this asset")
+                    .with_status_code(StatusCode::FORBIDDEN));
+            }
+
+            (client, path, Some(info))
+        }
+        (false, None) => {
+            return Err(api_error("authentication required to access this asset")
+                .with_status_code(StatusCode::UNAUTHORIZED));
+        }
+    };
+
+    if let Some(pid) = buckets::pid_of(&path) {
+        client.bucket(&state, pid).await?;
+    }
+
+    // download tokens are honored like signed downloads do, limits and overrides included.
+    match live_session(&state, &path).await? {
+        Some((session_id, info)) => {
+            let overrides = match &grant {
+                Some(grant) => {
+                    consume_grant(&state, grant).await?;
+                    grant.overrides.clone()
+                }
+                None => ResponseOverrides::default(),
+            };
+
+            stream_upload(&state, &path, &session_id, &info, &overrides).await
+        }
+        None => {
+            let asset = assets::get(state.db(), &path)
+                .await?
+                .ok_or(api_error("asset not found").with_status_code(StatusCode::NOT_FOUND))?;
+
+            match &grant {
+                Some(grant) => serve_granted(&state, grant, &asset, &headers).await,
+                None => serve(&state, &asset, &headers, &ResponseOverrides::default()).await,
+            }
+        }
+    }
+}
+
+/// Find a resumable upload in progress to `path`, which staged its first chunk.
+async fn live_session(
+    state: &AppState,
+    path: &str,
+) -> Result<Option<(String, UploadInfo)>, ResponseError> {
+    // resumable uploads are tracked by the message broker.
+    let Ok(broker) = state.broker() else {
+        return Ok(None);
+    };
+
+    for session_id in broker.uploads_to(path).await? {
+        if let Ok(info) = broker.get_upload_info(&session_id).await
+            && staging_path(&session_id)?.is_file()
+        {
+            return Ok(Some((session_id, info)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Reader of a staging file which waits for more bytes until the upload completes.
+struct LiveReader {
+    file: File,
+    read: u64,
+    target: u64,
+    progress: watch::Receiver<u64>,
+    /// Whether the session completed or failed, so no more bytes will be staged.
+    finished: bool,
+    last_read: Instant,
+    idle_timeout: Duration,
+}
+
+impl LiveReader {
+    async fn next(&mut self) -> Option<std::io::Result<Bytes>> {
+        let mut buf = vec![0; LIVE_READ_SIZE];
+        loop {
+            if self.read >= self.target {
+                return None;
+            }
+
+            let limit = (self.target - self.read).min(LIVE_READ_SIZE as u64) as usize;
+            match self.file.read(&mut buf[..limit]).await {
+                Ok(0) => {}
+                Ok(read) => {
+                    self.read += read as u64;
+                    self.last_read = Instant::now();
+                    buf.truncate(read);
+                    return Some(Ok(Bytes::from(buf)));
+                }
+                Err(err) => {
+                    self.target = self.read;
+                    return Some(Err(err));
+                }
+            }
+
+            // every staged byte was read: the upload failed, or stalled.
+            if self.finished || self.last_read.elapsed() >= self.idle_timeout {
+                return None;
+            }
+
+            // the sender is dropped once the session completes or fails.
+            let changed = tokio::time::timeout(LIVE_POLL_INTERVAL, self.progress.changed()).await;
+            if let Ok(Err(_)) = changed {
+                self.finished = true;
+            }
+        }
+    }
+}
+
+fn staging_path(session_id: &str) -> anyhow::Result<PathBuf> {
+    Ok(root_dir()?.join("tmp").join(session_id))
+}
+
+async fn stream_upload(
+    state: &AppState,
+    path: &str,
+    session_id: &str,
+    info: &UploadInfo,
+    overrides: &ResponseOverrides,
+) -> Result<Response, ResponseError> {
+    let config = info
+        .config
+        .as_ref()
+        .ok_or(api_error("missing configuration"))?;
+
+    let target = config.target_filesize.unwrap_or_default();
+    let progress = state.live_uploads().subscribe(session_id);
+    let file = File::open(staging_path(session_id)?)
+        .await
+        .map_err(|_| api_error("upload not found").with_status_code(StatusCode::NOT_FOUND))?;
+
+    let reader = LiveReader {
+        file,
+        read: 0,
+        target,
+        progress,
+        finished: false,
+        last_read: Instant::now(),
+        idle_timeout: Duration::from_secs(info.chunk_session_expiration.max(1) as u64),
+    };
+
+    let body = stream::unfold(reader, |mut reader| async move {
+        reader.next().await.map(|chunk| (chunk, reader))
+    });
+
+    let content_type = overrides
+        .content_type
+        .clone()
+        .or_else(|| assets::guess_content_type(path))
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&content_type).map_err(api_error)?,
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&content_disposition(name, overrides)?).map_err(api_error)?,
+    );
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(target));
+    // partial content must never be cached, whatever the token asks for.
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+    Ok((StatusCode::OK, headers, Body::from_stream(body)).into_response())
+}
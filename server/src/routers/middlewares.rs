@@ -1,31 +1,33 @@
 use crate::routers::resp::{ResponseError, api_error};
 use crate::state::AppState;
-use axum::extract::{FromRef, FromRequestParts, Path};
-use axum::http::StatusCode;
+use axum::extract::{FromRef, FromRequestParts, Path, Query};
 use axum::http::request::Parts;
-use shared::client::verify_client;
-use shared::server::UploadInfo;
+use axum::http::{HeaderMap, StatusCode};
+use serde::Deserialize;
+use shared::assets::{self, Asset};
+use shared::buckets::{self, Bucket};
+use shared::client::{self, verify_client};
 use shared::hasher::errors::PayloadVerificationError;
+use shared::server::{DownloadInfo, UploadInfo};
 
 pub struct ClientExtractor(i32);
 impl ClientExtractor {
+    /// Act on behalf of a client whose access was granted some other way, e.g. a signed token.
+    pub fn new(id: i32) -> Self {
+        Self(id)
+    }
+
     pub fn id(&self) -> i32 {
         self.0
     }
-}
-
-impl<S> FromRequestParts<S> for ClientExtractor
-where
-    S: Send + Sync + Clone + 'static,
-    AppState: FromRef<S>,
-{
-    type Rejection = ResponseError;
-
-    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let state = AppState::from_ref(state);
 
+    /// Verify the client token sent in the configured `client_header_key` header.
+    pub async fn from_headers(
+        state: &AppState,
+        headers: &HeaderMap,
+    ) -> Result<Self, ResponseError> {
         let header_key = state.config().client_header_key.clone();
-        let header = parts.headers.get(&header_key).ok_or(
+        let header = headers.get(&header_key).ok_or(
             api_error("missing client header key").with_status_code(StatusCode::UNAUTHORIZED),
         )?;
 
@@ -39,6 +41,56 @@ where
 
         Ok(Self(client_id))
     }
+
+    /// Get a bucket owned by this client.
+    pub async fn bucket(&self, state: &AppState, pid: &str) -> Result<Bucket, ResponseError> {
+        let bucket = buckets::get(pid, state.db())
+            .await
+            .map_err(|_| api_error("bucket not found").with_status_code(StatusCode::NOT_FOUND))?;
+
+        let owner_id = client::owner_id(state.db(), self.id()).await?;
+        if bucket.owner_id() != owner_id {
+            return Err(api_error("bucket not found").with_status_code(StatusCode::NOT_FOUND));
+        }
+
+        Ok(bucket)
+    }
+
+    /// Resolve a path to its location within the client's namespace. Bucket paths are kept as is.
+    pub async fn resolve(&self, state: &AppState, path: &str) -> Result<String, ResponseError> {
+        let (pid, _) = client::get_claims_data(state.db(), &self.id()).await?;
+        Ok(client::object_path(&pid, path))
+    }
+
+    /// Get an indexed asset owned by this client. `path` is resolved within the client's
+    /// namespace.
+    pub async fn asset(&self, state: &AppState, path: &str) -> Result<Asset, ResponseError> {
+        let not_found = || api_error("asset not found").with_status_code(StatusCode::NOT_FOUND);
+        let path = self.resolve(state, path).await?;
+        let asset = assets::get(state.db(), &path)
+            .await?
+            .ok_or_else(not_found)?;
+
+        let owner_id = client::owner_id(state.db(), self.id()).await?;
+        if asset.owner_id() != owner_id {
+            return Err(not_found());
+        }
+
+        Ok(asset)
+    }
+}
+
+impl<S> FromRequestParts<S> for ClientExtractor
+where
+    S: Send + Sync + Clone + 'static,
+    AppState: FromRef<S>,
+{
+    type Rejection = ResponseError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_ref(state);
+        Self::from_headers(&state, &parts.headers).await
+    }
 }
 
 pub struct UploadMiddleware(pub UploadInfo);
@@ -53,7 +105,7 @@ where
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let Path(payload) = Path::<String>::from_request_parts(parts, state)
             .await
-            .map_err(|e| api_error(e))?;
+            .map_err(api_error)?;
 
         let state = AppState::from_ref(state);
         match UploadInfo::verify(&payload, state.db(), state.hasher()).await {
@@ -69,3 +121,38 @@ where
         }
     }
 }
+
+#[derive(Deserialize)]
+struct SignedQuery {
+    token: String,
+}
+
+/// Verifies a signed download token passed as the `token` query parameter.
+pub struct DownloadMiddleware(pub DownloadInfo);
+
+impl<S> FromRequestParts<S> for DownloadMiddleware
+where
+    S: Send + Sync,
+    AppState: FromRef<S>,
+{
+    type Rejection = ResponseError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<SignedQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| api_error(err).with_status_code(StatusCode::UNAUTHORIZED))?;
+
+        let state = AppState::from_ref(state);
+        match DownloadInfo::verify(&query.token, state.db(), state.hasher()).await {
+            Ok(info) => Ok(Self(info)),
+            Err(err) => {
+                let resp = match err {
+                    PayloadVerificationError::Error(err) => api_error(err),
+                    PayloadVerificationError::Expired => api_error("download link expired"),
+                };
+
+                Err(resp.with_status_code(StatusCode::UNAUTHORIZED))
+            }
+        }
+    }
+}
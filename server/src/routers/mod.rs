@@ -1,12 +1,39 @@
+mod assets;
+mod buckets;
+mod delete;
+mod download;
+mod events;
+mod folder;
+mod lifecycle;
+mod listing;
+mod live;
 mod middlewares;
 mod resp;
+mod search;
+mod transfer;
+mod trash;
 mod upload;
+mod versions;
 
+use self::assets::*;
+use self::buckets::*;
+use self::delete::*;
+use self::download::*;
+use self::events::*;
+use self::folder::*;
+use self::lifecycle::*;
+use self::listing::*;
+use self::live::*;
+use self::search::*;
+use self::transfer::*;
+use self::trash::*;
 use self::upload::*;
+use self::versions::*;
+use crate::compression;
 use crate::state::AppState;
 use axum::Router;
 use axum::extract::DefaultBodyLimit;
-use axum::routing::post;
+use axum::routing::{delete, get, post, put};
 
 const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024; // 2MB max upload
 
@@ -16,3 +43,84 @@ pub(crate) fn upload_routes() -> Router<AppState> {
         .route("/session/play/{payload}", post(play_session))
         .layer(DefaultBodyLimit::max(DEFAULT_BODY_LIMIT))
 }
+
+pub(crate) fn asset_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_asset))
+        .route("/metadata", put(update_metadata))
+        .route("/visibility", put(update_visibility))
+        .route("/retention", put(update_retention))
+        .route("/legal-hold", put(update_legal_hold))
+        .route("/storage-class", put(update_storage_class))
+        .route("/tags", get(get_tags).put(put_tags).delete(delete_tags))
+        .route("/tagged", get(list_tagged))
+        .route("/copy", post(copy_asset))
+        .route("/move", post(move_asset))
+        .route("/{asset_type}/{*asset_path}", delete(delete_asset))
+        .route("/versions", get(list_versions))
+        .route("/versions/{pid}", get(download_version))
+        .route("/versions/{pid}/restore", post(restore_version))
+}
+
+pub(crate) fn bucket_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_buckets).post(create_bucket))
+        .route("/{pid}", get(get_bucket).delete(delete_bucket))
+        .route("/{pid}/visibility", put(update_bucket_visibility))
+        .route("/{pid}/retention", put(update_bucket_retention))
+        .route("/{pid}/versioning", put(update_bucket_versioning))
+        .route("/{pid}/precompress", put(update_bucket_precompress))
+}
+
+pub(crate) fn download_routes() -> Router<AppState> {
+    Router::new()
+        .route("/token", post(create_download_token))
+        .route("/transform", post(create_transform_token))
+        .route("/{*path}", get(download_asset))
+        .layer(compression::layer())
+}
+
+pub(crate) fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/{*path}", get(signed_download))
+        .layer(compression::layer())
+}
+
+pub(crate) fn folder_routes() -> Router<AppState> {
+    Router::new().route("/{*path}", get(download_folder))
+}
+
+pub(crate) fn live_routes() -> Router<AppState> {
+    Router::new().route("/{*path}", get(live_download))
+}
+
+pub(crate) fn list_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_assets))
+        .route("/token", post(create_list_token))
+}
+
+pub(crate) fn search_routes() -> Router<AppState> {
+    Router::new().route("/", get(search_assets))
+}
+
+pub(crate) fn events_routes() -> Router<AppState> {
+    Router::new().route("/", get(watch_events))
+}
+
+pub(crate) fn trash_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_trash))
+        .route("/{pid}", delete(purge_trash))
+        .route("/{pid}/restore", post(restore_trash))
+}
+
+pub(crate) fn lifecycle_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_rules).post(create_rule))
+        .route("/{pid}", delete(delete_rule))
+}
+
+pub(crate) fn audit_routes() -> Router<AppState> {
+    Router::new().route("/", get(list_audit))
+}
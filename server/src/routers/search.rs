@@ -0,0 +1,50 @@
+use crate::routers::listing::resolve_prefix;
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, api_error, api_response};
+use crate::state::AppState;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use serde::Deserialize;
+use shared::client;
+use shared::search::{self, SearchOptions, SearchResult};
+
+#[derive(Deserialize)]
+pub(super) struct SearchQuery {
+    /// Words to look for in paths, file names, metadata and tags.
+    q: String,
+    prefix: Option<String>,
+    /// pid of the bucket to search.
+    bucket: Option<String>,
+    continuation_token: Option<String>,
+    max_results: Option<usize>,
+}
+
+/// Search the client's assets by path, file name, metadata and tags, most relevant first.
+#[axum::debug_handler]
+pub(super) async fn search_assets(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Query(query): Query<SearchQuery>,
+) -> ApiResponse<SearchResult> {
+    let bucket_id = match &query.bucket {
+        Some(pid) => Some(client.bucket(&state, pid).await?.id()),
+        None => None,
+    };
+
+    let prefix = query.prefix.unwrap_or_default();
+    let prefix = resolve_prefix(&state, &client, &prefix, query.bucket.as_deref()).await?;
+    let options = SearchOptions {
+        query: query.q,
+        prefix,
+        bucket_id,
+        continuation_token: query.continuation_token,
+        max_results: query.max_results,
+    };
+
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    let result = search::search(state.db(), owner_id, options)
+        .await
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    api_response(result)
+}
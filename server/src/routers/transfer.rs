@@ -0,0 +1,291 @@
+use crate::routers::assets::{check_unlocked, is_cold, restore_cold};
+use crate::routers::buckets::check_content;
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, ResponseError, api_error, api_response};
+use crate::state::AppState;
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use serde::Deserialize;
+use shared::assets::{self, Asset, AssetType};
+use shared::buckets::Bucket;
+use shared::events::{ObjectEvent, ObjectEventKind};
+use shared::{buckets, copy_path, lock, move_path, quota, versions};
+use std::path::Path;
+
+#[derive(Deserialize)]
+pub(super) struct TransferOptions {
+    source: String,
+    destination: String,
+    /// pid of the bucket the destination belongs to. The destination is then relative to the
+    /// bucket.
+    bucket: Option<String>,
+    /// Create destination parent folders if they don't exist, else error will be returned.
+    create_parents: Option<bool>,
+    /// overwrite destination if it already exists.
+    overwrite: Option<bool>,
+    /// Move, or overwrite, files under governance retention.
+    bypass_governance: Option<bool>,
+}
+
+/// Copy a file or folder to a new path.
+#[axum::debug_handler]
+pub(super) async fn copy_asset(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(mut options): Json<TransferOptions>,
+) -> ApiResponse<Asset> {
+    let (source, bucket) = prepare(&state, &client, &mut options).await?;
+    let bucket_id = bucket.as_ref().map(Bucket::id);
+
+    let db = state.db();
+    let files = files(&state, &source).await?;
+    let size = added_size(&state, &files, &options).await?;
+    if let Some(bucket) = &bucket {
+        check_files(&state, bucket, &files, size).await?;
+    }
+
+    if !quota::allows(db, client.id(), size as u64).await? {
+        return Err(
+            api_error("storage quota exceeded").with_status_code(StatusCode::INSUFFICIENT_STORAGE)
+        );
+    }
+
+    let root_dir = state.config().root_dir()?;
+    let existed = root_dir.join(&options.destination).exists();
+    clear_destination(&state, &root_dir, &options, bucket_id).await?;
+    copy_path(
+        &root_dir.join(source.path()),
+        &root_dir.join(&options.destination),
+    )
+    .await?;
+
+    let owner_id = source.owner_id();
+    let destination = &options.destination;
+    assets::index_parents(db, destination, owner_id, bucket_id).await?;
+    assets::index_tree(db, &root_dir, destination, owner_id, bucket_id).await?;
+    assets::copy_attributes(db, source.path(), destination).await?;
+    if let Some(bucket) = &bucket {
+        for file in files_at(&state, destination).await? {
+            lock::apply(db, file.path(), Some(bucket), None, false).await?;
+        }
+    }
+
+    let asset = destination_asset(&state, destination).await?;
+    let kind = match existed {
+        true => ObjectEventKind::Updated,
+        false => ObjectEventKind::Created,
+    };
+
+    state
+        .events()
+        .publish(ObjectEvent::new(kind, &asset)?)
+        .await;
+    api_response(asset)
+}
+
+/// Move (or rename) a file or folder to a new path.
+#[axum::debug_handler]
+pub(super) async fn move_asset(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Json(mut options): Json<TransferOptions>,
+) -> ApiResponse<Asset> {
+    let (source, bucket) = prepare(&state, &client, &mut options).await?;
+    let bucket_id = bucket.as_ref().map(Bucket::id);
+    if let Some(bucket) = &bucket
+        && source.bucket_id() != bucket_id
+    {
+        let files = files(&state, &source).await?;
+        let size = added_size(&state, &files, &options).await?;
+        check_files(&state, bucket, &files, size).await?;
+    }
+
+    let db = state.db();
+    let root_dir = state.config().root_dir()?;
+    let bypass = options.bypass_governance.unwrap_or_default();
+    check_unlocked(&state, source.path(), bypass).await?;
+    clear_destination(&state, &root_dir, &options, bucket_id).await?;
+    move_path(
+        &root_dir.join(source.path()),
+        &root_dir.join(&options.destination),
+    )
+    .await?;
+
+    let destination = &options.destination;
+    assets::index_parents(db, destination, source.owner_id(), bucket_id).await?;
+    assets::relocate(db, source.path(), destination, bucket_id).await?;
+
+    let asset = destination_asset(&state, destination).await?;
+    let event = ObjectEvent::moved(&asset, source.path())?;
+    state.events().publish(event).await;
+    api_response(asset)
+}
+
+/// Check a transfer request and normalize its paths. Returns the source asset and the
+/// destination's bucket.
+async fn prepare(
+    state: &AppState,
+    client: &ClientExtractor,
+    options: &mut TransferOptions,
+) -> Result<(Asset, Option<Bucket>), ResponseError> {
+    let bad_request = |msg: &str| api_error(msg).with_status_code(StatusCode::BAD_REQUEST);
+    for path in [&mut options.source, &mut options.destination] {
+        assets::check_path(path).map_err(|err| bad_request(&err.to_string()))?;
+        *path = assets::normalize(path);
+    }
+
+    options.destination = match &options.bucket {
+        Some(pid) => buckets::object_path(pid, &options.destination),
+        None => client.resolve(state, &options.destination).await?,
+    };
+
+    let source = client.asset(state, &options.source).await?;
+    let destination = &options.destination;
+    if destination == source.path() || destination.starts_with(&format!("{}/", source.path())) {
+        return Err(bad_request("destination can't be the source or within it"));
+    }
+
+    if buckets::is_namespace(destination) {
+        return Err(bad_request("destination is reserved"));
+    }
+
+    let bucket = match buckets::pid_of(destination) {
+        Some(pid) => Some(client.bucket(state, pid).await?),
+        None => None,
+    };
+
+    let root_dir = state.config().root_dir()?;
+    let target = root_dir.join(destination);
+    if target.exists() || is_cold(state, destination).await? {
+        if !options.overwrite.unwrap_or_default() {
+            return Err(api_error("Asset already exists").with_status_code(StatusCode::CONFLICT));
+        }
+
+        // only the owner of an existing asset may overwrite it.
+        client.asset(state, destination).await?;
+        restore_cold(state, destination).await?;
+    }
+
+    // cold files are copied and moved from the storage root.
+    restore_cold(state, source.path()).await?;
+
+    let parent_dir = target.parent().unwrap_or(&root_dir);
+    if parent_dir != root_dir && !parent_dir.exists() && !options.create_parents.unwrap_or_default()
+    {
+        return Err(bad_request("Parent directory does not exist"));
+    }
+
+    Ok((source, bucket))
+}
+
+/// Files being transferred: the source itself, or the files within a source folder.
+async fn files(state: &AppState, source: &Asset) -> Result<Vec<Asset>, ResponseError> {
+    let files = match source.asset_type() {
+        AssetType::File => vec![source.clone()],
+        AssetType::Folder => assets::descendants(state.db(), source.path())
+            .await?
+            .into_iter()
+            .filter(|asset| asset.asset_type() == AssetType::File)
+            .collect(),
+    };
+
+    Ok(files)
+}
+
+/// Files indexed at or under `path`.
+async fn files_at(state: &AppState, path: &str) -> Result<Vec<Asset>, ResponseError> {
+    match assets::get(state.db(), path).await? {
+        Some(asset) => files(state, &asset).await,
+        None => Ok(vec![]),
+    }
+}
+
+/// Number of bytes a transfer of `files` adds at its destination, net of the files it
+/// overwrites.
+async fn added_size(
+    state: &AppState,
+    files: &[Asset],
+    options: &TransferOptions,
+) -> Result<i64, ResponseError> {
+    let size: i64 = files.iter().map(Asset::size).sum();
+    let replaced: i64 = files_at(state, &options.destination)
+        .await?
+        .iter()
+        .map(Asset::size)
+        .sum();
+
+    Ok((size - replaced).max(0))
+}
+
+/// Check that the destination bucket accepts every transferred file.
+async fn check_files(
+    state: &AppState,
+    bucket: &Bucket,
+    files: &[Asset],
+    size: i64,
+) -> Result<(), ResponseError> {
+    for file in files {
+        check_content(state, bucket, file.content_type(), 0).await?;
+    }
+
+    if !bucket.has_room(state.db(), size as u64).await? {
+        return Err(
+            api_error("bucket size exceeded").with_status_code(StatusCode::INSUFFICIENT_STORAGE)
+        );
+    }
+
+    Ok(())
+}
+
+/// Remove the asset a transfer overwrites, if any. Files in versioned buckets are archived
+/// instead, and those of overwritten folders marked deleted.
+async fn clear_destination(
+    state: &AppState,
+    root_dir: &Path,
+    options: &TransferOptions,
+    bucket_id: Option<i32>,
+) -> Result<(), ResponseError> {
+    let db = state.db();
+    let path = &options.destination;
+    let target = root_dir.join(path);
+    if target.exists() {
+        check_unlocked(state, path, options.bypass_governance.unwrap_or_default()).await?;
+    }
+
+    let versioning = match bucket_id {
+        Some(id) if buckets::get_by_id(id, db).await?.versioning() => Some(id),
+        _ => None,
+    };
+
+    if target.is_file() {
+        match versioning {
+            Some(id) => {
+                versions::archive(db, root_dir, id, path).await?;
+            }
+            None => tokio::fs::remove_file(&target).await?,
+        }
+    } else if target.is_dir() {
+        // files of an overwritten folder are kept as deleted versions, like deleted folders.
+        if let Some(id) = versioning {
+            for file in files_at(state, path).await? {
+                versions::mark_deleted(db, root_dir, id, file.path()).await?;
+            }
+        }
+
+        tokio::fs::remove_dir_all(&target).await?;
+    } else {
+        return Ok(());
+    }
+
+    assets::remove(db, root_dir, path).await?;
+    Ok(())
+}
+
+async fn destination_asset(state: &AppState, path: &str) -> Result<Asset, ResponseError> {
+    let asset = assets::get(state.db(), path)
+        .await?
+        .ok_or(api_error("unable to index destination"))?;
+
+    Ok(asset)
+}
@@ -0,0 +1,72 @@
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, ResponseError, api_error, api_response};
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use shared::client;
+use shared::events::{ObjectEvent, ObjectEventKind};
+use shared::trash::{self, TrashItem};
+
+/// List assets in the client's trash.
+#[axum::debug_handler]
+pub(super) async fn list_trash(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+) -> ApiResponse<Vec<TrashItem>> {
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    let items = trash::list(state.db(), owner_id).await?;
+
+    api_response(items)
+}
+
+/// Move a trashed asset back to its original path.
+#[axum::debug_handler]
+pub(super) async fn restore_trash(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+) -> ApiResponse<()> {
+    let item = client_item(&state, &client, &pid).await?;
+    let root_dir = state.config().root_dir()?;
+    if root_dir.join(item.original_path()).exists() {
+        return Err(api_error("an asset already exists at the original path")
+            .with_status_code(StatusCode::CONFLICT));
+    }
+
+    let asset = trash::restore(state.db(), &root_dir, &item).await?;
+    let event = ObjectEvent::new(ObjectEventKind::Created, &asset)?;
+    state.events().publish(event).await;
+
+    api_response(())
+}
+
+/// Permanently delete a trashed asset.
+#[axum::debug_handler]
+pub(super) async fn purge_trash(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+) -> ApiResponse<()> {
+    let item = client_item(&state, &client, &pid).await?;
+    let root_dir = state.config().root_dir()?;
+    trash::purge(state.db(), &root_dir, &item).await?;
+
+    api_response(())
+}
+
+/// Get a trash item owned by the client.
+async fn client_item(
+    state: &AppState,
+    client: &ClientExtractor,
+    pid: &str,
+) -> Result<TrashItem, ResponseError> {
+    let not_found = || api_error("trash item not found").with_status_code(StatusCode::NOT_FOUND);
+    let item = trash::get(state.db(), pid).await.map_err(|_| not_found())?;
+
+    let owner_id = client::owner_id(state.db(), client.id()).await?;
+    if item.owner_id() != owner_id {
+        return Err(not_found());
+    }
+
+    Ok(item)
+}
@@ -1,4 +1,7 @@
+use crate::compression;
 use crate::routers::DEFAULT_BODY_LIMIT;
+use crate::routers::assets::{check_unlocked, is_cold, metadata_from_headers, restore_cold};
+use crate::routers::buckets::check_content;
 use crate::routers::middlewares::{ClientExtractor, UploadMiddleware};
 use crate::routers::resp::{ApiResponse, api_error, api_response};
 use crate::state::AppState;
@@ -6,9 +9,10 @@ use anyhow::anyhow;
 use axum::Json;
 use axum::body::Bytes;
 use axum::extract::State;
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
+use shared::events::{ObjectEvent, ObjectEventKind};
 use shared::server::*;
-use shared::{client, generate_nano_id, root_dir};
+use shared::{acl, assets, buckets, client, generate_nano_id, lock, quota, root_dir, versions};
 use std::path::{Path, PathBuf};
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncWriteExt;
@@ -20,12 +24,47 @@ use validator::Validate;
 pub(super) async fn create_session(
     State(state): State<AppState>,
     client: ClientExtractor,
-    Json(config): Json<UploadUrlConfig>,
+    headers: HeaderMap,
+    Json(mut config): Json<UploadUrlConfig>,
 ) -> ApiResponse<String> {
+    let mut metadata = metadata_from_headers(&headers)?;
+    if let Some(body_metadata) = config.metadata.take() {
+        metadata.extend(body_metadata);
+    }
+
+    if !metadata.is_empty() {
+        config.metadata = Some(metadata);
+    }
+
     config
         .validate()
         .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
 
+    assets::check_path(&config.path)
+        .map_err(|err| api_error(err).with_status_code(StatusCode::BAD_REQUEST))?;
+
+    match &config.bucket {
+        Some(pid) => {
+            let bucket = client.bucket(&state, pid).await?;
+            if let AssetType::File = config.asset_type {
+                let content_type = assets::guess_content_type(&config.path);
+                let size = config.target_filesize.unwrap_or_default();
+                check_content(&state, &bucket, content_type.as_deref(), size).await?;
+            }
+
+            // objects live under the bucket's namespace.
+            config.path = buckets::object_path(pid, &config.path);
+        }
+        None if buckets::pid_of(&config.path).is_some() || buckets::is_namespace(&config.path) => {
+            return Err(api_error(format!(
+                "\"{}\" is reserved. Set bucket to upload to a bucket.",
+                buckets::BUCKETS_DIR
+            ))
+            .with_status_code(StatusCode::BAD_REQUEST));
+        }
+        None => config.path = client.resolve(&state, &config.path).await?,
+    }
+
     let resumable = config.resumable.unwrap_or_default();
     if resumable && state.config().message_broker.is_none() {
         return Err(
@@ -47,6 +86,11 @@ pub(super) async fn create_session(
             .with_status_code(StatusCode::PAYLOAD_TOO_LARGE));
         }
 
+        if !quota::allows(state.db(), client.id(), size).await? {
+            return Err(api_error("storage quota exceeded")
+                .with_status_code(StatusCode::INSUFFICIENT_STORAGE));
+        }
+
         // SessionID is tightly coupled with MessageBroker. No need for a session if broker is not provided.
         if resumable && state.config().message_broker.is_some() {
             session_id = Some(generate_nano_id(32));
@@ -88,13 +132,27 @@ pub(super) async fn play_session(
     let config = config.ok_or(api_error("missing configuration"))?;
     let root_dir = state.config().root_dir()?;
     let target_path = root_dir.join(&config.path);
+    let base_dir = match &config.bucket {
+        Some(pid) => buckets::bucket_dir(&root_dir, pid),
+        None => client::namespace_dir(&root_dir, &info.client_id),
+    };
 
-    let parent_dir = target_path.parent().unwrap_or(&root_dir);
-    if target_path.exists() && !config.overwrite.unwrap_or_default() {
-        return Err(api_error("Asset already exists"));
+    if !base_dir.exists() {
+        tokio::fs::create_dir_all(&base_dir).await?;
+    }
+
+    let parent_dir = target_path.parent().unwrap_or(&base_dir);
+    if target_path.exists() || is_cold(&state, &config.path).await? {
+        if !config.overwrite.unwrap_or_default() {
+            return Err(api_error("Asset already exists"));
+        }
+
+        let bypass = config.bypass_governance.unwrap_or_default();
+        check_unlocked(&state, &config.path, bypass).await?;
+        restore_cold(&state, &config.path).await?;
     }
 
-    if parent_dir != root_dir && !parent_dir.exists() && !config.create_parents.unwrap_or_default()
+    if parent_dir != base_dir && !parent_dir.exists() && !config.create_parents.unwrap_or_default()
     {
         return Err(api_error("Parent directory does not exist"));
     }
@@ -109,6 +167,17 @@ pub(super) async fn play_session(
                 tokio::fs::create_dir(target_path).await?;
             }
 
+            let (owner_id, bucket_id) = asset_owner(&state, &info.client_id, &config).await?;
+            assets::index_parents(state.db(), &config.path, owner_id, bucket_id).await?;
+            assets::index_folder(state.db(), &config.path, owner_id, bucket_id).await?;
+            if let Some(metadata) = &config.metadata {
+                assets::set_metadata(state.db(), &config.path, metadata).await?;
+            }
+
+            if config.visibility.is_some() {
+                acl::set(state.db(), &config.path, config.visibility).await?;
+            }
+
             api_response(None)
         }
     }
@@ -124,6 +193,7 @@ async fn handle_session(
         Ok(token) => api_response(token),
         Err(err) => {
             if let Some(id) = session_id {
+                state.live_uploads().finish(&id);
                 let tmp_path = root_dir()?.join("tmp").join(id);
                 if let Err(err) = tokio::fs::remove_file(tmp_path).await {
                     tracing::error!("unable to clean up file after failure: {err}");
@@ -164,8 +234,11 @@ async fn get_next_session(
     let resumable = config.resumable.unwrap_or_default();
     let mut next_token = None;
 
-    let (tmp_path, completed) =
-        upload_file(session_id.clone(), &tmp_dir, &body, target_filesize).await?;
+    let (tmp_path, staged) = upload_file(session_id.clone(), &tmp_dir, &body).await?;
+    let completed = staged >= target_filesize;
+    if let Some(id) = &session_id {
+        state.live_uploads().notify(id, staged);
+    }
 
     println!("handling resumable state...");
     if !completed && resumable {
@@ -186,8 +259,62 @@ async fn get_next_session(
             tokio::fs::create_dir_all(&parent_dir).await?;
         }
 
+        let bucket = match &config.bucket {
+            Some(pid) => Some(buckets::get(pid, state.db()).await?),
+            None => None,
+        };
+
+        let existed = target_path.is_file();
+        if existed
+            && let Some(bucket) = &bucket
+            && bucket.versioning()
+        {
+            versions::archive(state.db(), &root_dir, bucket.id(), &config.path).await?;
+        }
+
         tokio::fs::rename(tmp_path, target_path).await?;
+
+        let db = state.db();
+        let (owner_id, bucket_id) = asset_owner(state, &info.client_id, &config).await?;
+        assets::index_parents(db, &config.path, owner_id, bucket_id).await?;
+        let asset = assets::index_file(db, &root_dir, &config.path, owner_id, bucket_id).await?;
+        let kind = match existed {
+            true => ObjectEventKind::Updated,
+            false => ObjectEventKind::Created,
+        };
+
+        state
+            .events()
+            .publish(ObjectEvent::new(kind, &asset)?)
+            .await;
+        if bucket.as_ref().is_some_and(|bucket| bucket.precompress()) {
+            tokio::spawn(async move {
+                if let Err(err) = compression::precompress(&root_dir, &asset).await {
+                    tracing::error!("unable to precompress {}: {err}", asset.path());
+                }
+            });
+        }
+
+        if let Some(metadata) = &config.metadata {
+            assets::set_metadata(db, &config.path, metadata).await?;
+        }
+
+        if config.visibility.is_some() {
+            acl::set(db, &config.path, config.visibility).await?;
+        }
+
+        let legal_hold = config.legal_hold.unwrap_or_default();
+        lock::apply(
+            db,
+            &config.path,
+            bucket.as_ref(),
+            config.retention,
+            legal_hold,
+        )
+        .await?;
+
         if let Some(id) = session_id {
+            state.live_uploads().finish(&id);
             let broker = state.broker()?;
             broker.remove_upload_info(&id).await?;
         }
@@ -196,12 +323,30 @@ async fn get_next_session(
     Ok(next_token)
 }
 
+/// Resolve the `asset_owner` id and bucket id an upload should be indexed under.
+async fn asset_owner(
+    state: &AppState,
+    client_pid: &str,
+    config: &UploadUrlConfig,
+) -> anyhow::Result<(i32, Option<i32>)> {
+    let db = state.db();
+    let client_id = client::id_by_pid(db, client_pid).await?;
+    let owner_id = client::owner_id(db, client_id).await?;
+
+    let bucket_id = match &config.bucket {
+        Some(pid) => Some(buckets::get(pid, db).await?.id()),
+        None => None,
+    };
+
+    Ok((owner_id, bucket_id))
+}
+
+/// Append a chunk to the upload's staging file. Returns the file's path and size.
 async fn upload_file(
     session_id: Option<String>,
     tmp_dir: &Path,
     data: &Bytes,
-    target_filesize: u64,
-) -> anyhow::Result<(PathBuf, bool)> {
+) -> anyhow::Result<(PathBuf, u64)> {
     let tmp_name = session_id.unwrap_or(generate_nano_id(32));
     let tmp_path = tmp_dir.join(&tmp_name);
 
@@ -219,6 +364,5 @@ async fn upload_file(
     tmp_file.flush().await?;
 
     let tmp_size = tmp_file.metadata().await?.len();
-    let completed = tmp_size >= target_filesize;
-    Ok((tmp_path, completed))
+    Ok((tmp_path, tmp_size))
 }
@@ -0,0 +1,107 @@
+use crate::routers::assets::{check_unlocked, restore_cold};
+use crate::routers::middlewares::ClientExtractor;
+use crate::routers::resp::{ApiResponse, ResponseError, api_error, api_response};
+use crate::state::AppState;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use shared::buckets;
+use shared::versions::{self, AssetVersion};
+use tokio_util::io::ReaderStream;
+
+#[derive(Deserialize)]
+pub(super) struct VersionsQuery {
+    bucket: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RestoreVersionOptions {
+    /// Replace a file under governance retention.
+    bypass_governance: Option<bool>,
+}
+
+/// List prior versions (and delete markers) of an object, newest first.
+#[axum::debug_handler]
+pub(super) async fn list_versions(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Query(query): Query<VersionsQuery>,
+) -> ApiResponse<Vec<AssetVersion>> {
+    let bucket = client.bucket(&state, &query.bucket).await?;
+    let path = buckets::object_path(bucket.pid(), &query.path);
+    let versions = versions::list(state.db(), bucket.id(), &path).await?;
+
+    api_response(versions)
+}
+
+/// Download the content of a given version.
+#[axum::debug_handler]
+pub(super) async fn download_version(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+) -> Result<Response, ResponseError> {
+    let version = client_version(&state, &client, &pid).await?;
+    if version.is_delete_marker() {
+        return Err(api_error("version is a delete marker").with_status_code(StatusCode::NOT_FOUND));
+    }
+
+    let root_dir = state.config().root_dir()?;
+    let file = tokio::fs::File::open(version.data_path(&root_dir)).await?;
+    let body = Body::from_stream(ReaderStream::new(file));
+
+    let headers = [
+        (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+        (header::CONTENT_LENGTH, version.size().to_string()),
+    ];
+
+    Ok((headers, body).into_response())
+}
+
+/// Make a prior version the current content of its object. Fails with `423 Locked` when the
+/// current content is retained or held.
+#[axum::debug_handler]
+pub(super) async fn restore_version(
+    State(state): State<AppState>,
+    client: ClientExtractor,
+    Path(pid): Path<String>,
+    Query(options): Query<RestoreVersionOptions>,
+) -> ApiResponse<()> {
+    let version = client_version(&state, &client, &pid).await?;
+    if version.is_delete_marker() {
+        return Err(api_error("a delete marker cannot be restored")
+            .with_status_code(StatusCode::BAD_REQUEST));
+    }
+
+    let bypass = options.bypass_governance.unwrap_or_default();
+    check_unlocked(&state, version.path(), bypass).await?;
+    restore_cold(&state, version.path()).await?;
+
+    let root_dir = state.config().root_dir()?;
+    versions::restore(state.db(), &root_dir, &version).await?;
+
+    api_response(())
+}
+
+/// Get a version whose bucket is owned by the client.
+async fn client_version(
+    state: &AppState,
+    client: &ClientExtractor,
+    pid: &str,
+) -> Result<AssetVersion, ResponseError> {
+    let not_found = || api_error("version not found").with_status_code(StatusCode::NOT_FOUND);
+    let version = versions::get(state.db(), pid)
+        .await
+        .map_err(|_| not_found())?;
+
+    let bucket = buckets::get_by_id(version.bucket_id(), state.db()).await?;
+    client
+        .bucket(state, bucket.pid())
+        .await
+        .map_err(|_| not_found())?;
+
+    Ok(version)
+}
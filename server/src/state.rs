@@ -1,3 +1,5 @@
+use crate::events::EventBus;
+use crate::live::LiveUploads;
 use shared::broker::MessageBroker;
 use shared::config::AppConfig;
 use shared::db::{Database, DbPool};
@@ -10,6 +12,8 @@ pub struct AppState {
     config: AppConfig,
     db: Database,
     broker: Option<MessageBroker>,
+    live_uploads: LiveUploads,
+    events: EventBus,
 }
 
 impl AppState {
@@ -23,11 +27,14 @@ impl AppState {
             broker = Some(MessageBroker::new(url).await?);
         }
 
+        let events = EventBus::new(broker.clone());
         Ok(Self {
             secrets,
             config,
             db,
             broker,
+            live_uploads: LiveUploads::default(),
+            events,
         })
     }
 
@@ -54,6 +61,14 @@ impl AppState {
         }
     }
 
+    pub(crate) fn live_uploads(&self) -> &LiveUploads {
+        &self.live_uploads
+    }
+
+    pub(crate) fn events(&self) -> &EventBus {
+        &self.events
+    }
+
     pub fn hasher(&self) -> &Hasher {
         &self.config().hasher
     }
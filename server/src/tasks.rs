@@ -0,0 +1,170 @@
+use crate::state::AppState;
+use shared::{grants, lifecycle, mb_to_bytes, scrub, search, trash};
+use std::time::Duration;
+
+/// How often expired trash items are looked for.
+const TRASH_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often lifecycle rules are applied.
+const LIFECYCLE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often expired download grants are removed.
+const GRANT_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often a batch of objects is scrubbed while a pass is underway.
+const SCRUB_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of objects checked per scrub batch.
+const SCRUB_BATCH: usize = 100;
+
+/// Minimum time (in seconds) between the end of a scrub pass and the start of the next one.
+const SCRUB_PASS_INTERVAL: i64 = 7 * 24 * 60 * 60;
+
+/// Start background jobs that run for the lifetime of the server.
+pub(crate) fn spawn(state: AppState) {
+    tokio::spawn(sweep_trash(state.clone()));
+    tokio::spawn(apply_lifecycle_rules(state.clone()));
+    tokio::spawn(sweep_grants(state.clone()));
+    tokio::spawn(scrub_objects(state.clone()));
+    tokio::spawn(backfill_search(state.clone()));
+    tokio::spawn(state.events().clone().relay());
+}
+
+/// Index assets the search index is missing, e.g. those indexed before search was available.
+async fn backfill_search(state: AppState) {
+    match search::backfill(state.db()).await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("search: indexed {count} asset(s)"),
+        Err(err) => tracing::error!("search backfill failed: {err}"),
+    }
+}
+
+/// Permanently remove assets that have outlived the trash retention period.
+async fn sweep_trash(state: AppState) {
+    let retention = state
+        .config()
+        .trash_retention
+        .unwrap_or(trash::DEFAULT_RETENTION_DAYS);
+
+    let mut interval = tokio::time::interval(TRASH_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let root_dir = match state.config().root_dir() {
+            Ok(dir) => dir,
+            Err(err) => {
+                tracing::error!("trash sweep: unable to get root dir: {err}");
+                continue;
+            }
+        };
+
+        match trash::sweep(state.db(), &root_dir, retention).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("trash sweep: purged {count} expired item(s)"),
+            Err(err) => tracing::error!("trash sweep failed: {err}"),
+        }
+    }
+}
+
+/// Remove download grants whose token has expired.
+async fn sweep_grants(state: AppState) {
+    let mut interval = tokio::time::interval(GRANT_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = grants::sweep(state.db()).await {
+            tracing::error!("grant sweep failed: {err}");
+        }
+    }
+}
+
+/// Re-read stored objects to detect (and repair) bit rot. Passes resume where they stopped
+/// across restarts.
+async fn scrub_objects(state: AppState) {
+    let mut interval = tokio::time::interval(SCRUB_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = run_scrub(&state).await {
+            tracing::error!("scrub: {err}");
+        }
+    }
+}
+
+async fn run_scrub(state: &AppState) -> anyhow::Result<()> {
+    let progress = scrub::progress(state.db()).await?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+
+    let due = match (progress.started_at, progress.finished_at) {
+        (Some(_), _) | (None, None) => true,
+        (None, Some(finished_at)) => now - finished_at >= SCRUB_PASS_INTERVAL,
+    };
+
+    if !due {
+        return Ok(());
+    }
+
+    let config = state.config();
+    let rate = config
+        .scrub_rate
+        .map(|rate| mb_to_bytes(rate) as u64)
+        .unwrap_or(scrub::DEFAULT_RATE);
+
+    let root_dir = config.root_dir()?;
+    let replicas = config.replicas()?;
+    let report = scrub::scrub(state.db(), &root_dir, &replicas, rate, SCRUB_BATCH).await?;
+    for finding in report.findings {
+        match finding.error {
+            Some(err) => tracing::error!("scrub: unable to check {}: {err}", finding.path),
+            None => tracing::warn!("scrub: {} is {:?}", finding.path, finding.status),
+        }
+    }
+
+    if report.completed {
+        tracing::info!("scrub: pass completed");
+    }
+
+    Ok(())
+}
+
+/// Apply every lifecycle rule to the objects it matches.
+async fn apply_lifecycle_rules(state: AppState) {
+    let mut interval = tokio::time::interval(LIFECYCLE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = run_lifecycle_rules(&state).await {
+            tracing::error!("lifecycle: {err}");
+        }
+    }
+}
+
+async fn run_lifecycle_rules(state: &AppState) -> anyhow::Result<()> {
+    let root_dir = state.config().root_dir()?;
+    let cold_volume = state.config().cold_volume()?;
+
+    for rule in lifecycle::all(state.db()).await? {
+        match lifecycle::apply(state.db(), &root_dir, cold_volume.as_deref(), &rule).await {
+            Ok(outcomes) => {
+                for outcome in outcomes {
+                    match &outcome.error {
+                        Some(err) => tracing::error!(
+                            "lifecycle: rule {} failed to apply {:?} to {}: {err}",
+                            outcome.rule,
+                            outcome.action,
+                            outcome.path
+                        ),
+                        None => tracing::info!(
+                            "lifecycle: rule {} applied {:?} to {}",
+                            outcome.rule,
+                            outcome.action,
+                            outcome.path
+                        ),
+                    }
+                }
+            }
+            Err(err) => tracing::error!("lifecycle: rule {} failed: {err}", rule.pid()),
+        }
+    }
+
+    Ok(())
+}
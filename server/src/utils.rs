@@ -13,7 +13,7 @@ pub fn create_jwt(secrets: &AppSecrets, claims: &Claims) -> anyhow::Result<Strin
     .map_err(|e| anyhow!(e))
 }
 
-pub(crate) fn decode_jwt(secrets: &AppSecrets, token: &str) -> anyhow::Result<Claims> {
+pub fn decode_jwt(secrets: &AppSecrets, token: &str) -> anyhow::Result<Claims> {
     let mut validation = Validation::default();
     validation.algorithms = vec![Algorithm::HS512];
 
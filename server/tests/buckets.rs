@@ -0,0 +1,133 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload_config};
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+use server::state::AppState;
+use shared::client::create_client;
+
+#[tokio::test]
+async fn test_bucket_management() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Bucket Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    let body = json!({ "size": 16, "accepts": ["text/*"] });
+    let resp = server
+        .post("/bucket", &body)
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    let pid: String = resp.json();
+
+    let buckets: Vec<Value> = server
+        .get("/bucket")
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(buckets.len(), 1);
+    assert_eq!(buckets[0]["pid"], pid.as_str());
+
+    // Objects live under the bucket namespace
+    let mut config = upload_config();
+    config.path = "notes/todo.txt".to_string();
+    config.bucket = Some(pid.clone());
+    config.create_parents = Some(true);
+    config.target_filesize = Some(5);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::from_static(b"hello"),
+        )
+        .await
+        .assert_status_ok();
+
+    let object = format!("buckets/{pid}/notes/todo.txt");
+    server
+        .get(&format!("/download/{object}"))
+        .add_header(&header_key, client.token())
+        .await
+        .assert_text("hello");
+
+    let info: Value = server
+        .get(&format!("/bucket/{pid}"))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(info["usage"], 5);
+    assert_eq!(info["versioning"], false);
+
+    // Versioning
+    server
+        .put(
+            &format!("/bucket/{pid}/versioning"),
+            &json!({ "versioning": true }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let info: Value = server
+        .get(&format!("/bucket/{pid}"))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(info["versioning"], true);
+
+    // Accepts
+    config.path = "photo.png".to_string();
+    server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    // Size
+    config.path = "large.txt".to_string();
+    config.target_filesize = Some(12);
+    server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::INSUFFICIENT_STORAGE);
+
+    // Other clients can't see the bucket
+    let other = create_client(state.db(), state.secrets(), "Other Bucket Client", None).await?;
+    server
+        .get(&format!("/bucket/{pid}"))
+        .add_header(&header_key, other.token())
+        .await
+        .assert_status_not_found();
+
+    // Delete
+    let url = format!("/bucket/{pid}");
+    server
+        .delete(&url)
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::CONFLICT);
+
+    server
+        .delete(&format!("{url}?force=true"))
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let root_dir = state.config().root_dir()?;
+    assert!(!root_dir.join(&object).exists());
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use axum::body::Bytes;
 use axum_test::{TestRequest, TestServer, TestServerConfig, Transport};
 use serde::Serialize;
@@ -22,6 +24,14 @@ impl TestServerWrapper {
         Ok(s)
     }
 
+    /// Address the server listens on, e.g. `http://127.0.0.1:8080/`.
+    pub fn address(&self) -> String {
+        self.server
+            .server_address()
+            .map(|url| url.to_string())
+            .unwrap_or_default()
+    }
+
     pub fn post<B: Serialize>(&self, url: &str, body: &B) -> TestRequest {
         self.server
             .post(url)
@@ -29,6 +39,21 @@ impl TestServerWrapper {
             .content_type("application/json")
     }
 
+    pub fn put<B: Serialize>(&self, url: &str, body: &B) -> TestRequest {
+        self.server
+            .put(url)
+            .json(body)
+            .content_type("application/json")
+    }
+
+    pub fn get(&self, url: &str) -> TestRequest {
+        self.server.get(url)
+    }
+
+    pub fn delete(&self, url: &str) -> TestRequest {
+        self.server.delete(url)
+    }
+
     pub fn post_bytes(&self, url: &str, body: Bytes) -> TestRequest {
         self.server.post(url).bytes(body)
     }
@@ -38,6 +63,35 @@ impl TestServerWrapper {
     }
 }
 
+/// Upload `data` to `path` for the client identified by `client_token`.
+pub async fn upload(
+    server: &TestServerWrapper,
+    header_key: &str,
+    client_token: &str,
+    path: &str,
+    data: &[u8],
+) {
+    let mut config = upload_config();
+    config.path = path.to_string();
+    config.create_parents = Some(true);
+    config.overwrite = Some(true);
+    config.target_filesize = Some(data.len() as u64);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(header_key, client_token)
+        .await
+        .json();
+
+    server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::copy_from_slice(data),
+        )
+        .await
+        .assert_status_ok();
+}
+
 pub fn upload_config() -> UploadUrlConfig {
     UploadUrlConfig::test()
 }
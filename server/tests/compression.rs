@@ -0,0 +1,166 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload, upload_config};
+use axum::body::Bytes;
+use axum::http::{StatusCode, header};
+use flate2::read::GzDecoder;
+use serde_json::json;
+use server::state::AppState;
+use shared::client::create_client;
+use shared::{assets, buckets};
+use std::io::Read;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_response_compression() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Compression Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    let text = "all work and no play makes jack a dull boy\n".repeat(100);
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "jack.txt",
+        text.as_bytes(),
+    )
+    .await;
+
+    // Compressed on the fly
+    let resp = server
+        .get("/download/jack.txt")
+        .add_header(&header_key, client.token())
+        .add_header(header::ACCEPT_ENCODING, "gzip")
+        .await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.header(header::CONTENT_ENCODING), "gzip");
+    assert_eq!(resp.header(header::VARY), "accept-encoding");
+
+    let mut decoded = String::new();
+    GzDecoder::new(&resp.as_bytes()[..]).read_to_string(&mut decoded)?;
+    assert_eq!(decoded, text);
+
+    // Unless the client doesn't ask, or wants a range
+    let resp = server
+        .get("/download/jack.txt")
+        .add_header(&header_key, client.token())
+        .await;
+
+    assert!(resp.maybe_header(header::CONTENT_ENCODING).is_none());
+    resp.assert_text(&text);
+
+    let resp = server
+        .get("/download/jack.txt")
+        .add_header(&header_key, client.token())
+        .add_header(header::ACCEPT_ENCODING, "gzip")
+        .add_header(header::RANGE, "bytes=0-2")
+        .await;
+
+    resp.assert_status(StatusCode::PARTIAL_CONTENT);
+    assert!(resp.maybe_header(header::CONTENT_ENCODING).is_none());
+    resp.assert_text("all");
+
+    // Already compressed content is sent as is
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "jack.gz",
+        text.as_bytes(),
+    )
+    .await;
+    let resp = server
+        .get("/download/jack.gz")
+        .add_header(&header_key, client.token())
+        .add_header(header::ACCEPT_ENCODING, "gzip")
+        .await;
+
+    assert!(resp.maybe_header(header::CONTENT_ENCODING).is_none());
+
+    // Buckets may precompress uploads
+    let pid: String = server
+        .post("/bucket", &json!({ "precompress": true }))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let mut config = upload_config();
+    config.path = "jack.txt".to_string();
+    config.bucket = Some(pid.clone());
+    config.target_filesize = Some(text.len() as u64);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::from(text.clone()),
+        )
+        .await
+        .assert_status_ok();
+
+    // copies are written in the background.
+    let url = format!("/download/buckets/{pid}/jack.txt");
+    let mut precompressed = None;
+    for _ in 0..50 {
+        let resp = server
+            .get(&url)
+            .add_header(&header_key, client.token())
+            .add_header(header::ACCEPT_ENCODING, "gzip, br")
+            .await;
+
+        if resp.header(header::ETAG).to_str()?.ends_with("-br\"") {
+            precompressed = Some(resp);
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let resp = precompressed.expect("upload is precompressed");
+    assert_eq!(resp.header(header::CONTENT_ENCODING), "br");
+    assert_eq!(resp.header(header::VARY), "accept-encoding");
+
+    let mut decoded = String::new();
+    brotli::Decompressor::new(&resp.as_bytes()[..], 4096).read_to_string(&mut decoded)?;
+    assert_eq!(decoded, text);
+
+    let resp = server
+        .get(&url)
+        .add_header(&header_key, client.token())
+        .add_header(header::ACCEPT_ENCODING, "br;q=0.5, gzip")
+        .await;
+
+    assert_eq!(resp.header(header::CONTENT_ENCODING), "gzip");
+    assert!(resp.header(header::ETAG).to_str()?.ends_with("-gz\""));
+
+    let mut decoded = String::new();
+    GzDecoder::new(&resp.as_bytes()[..]).read_to_string(&mut decoded)?;
+    assert_eq!(decoded, text);
+
+    // Copies are removed along with the bucket
+    let root_dir = state.config().root_dir()?;
+    let path = buckets::object_path(&pid, "jack.txt");
+    let asset = assets::get(state.db(), &path)
+        .await?
+        .expect("upload is indexed");
+    let variants = asset.variants_dir(&root_dir);
+    assert!(variants.is_dir());
+
+    server
+        .delete(&format!("/bucket/{pid}?force=true"))
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    assert!(!variants.exists());
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload, upload_config};
+use axum::body::Bytes;
+use serde_json::{Value, json};
+use server::state::AppState;
+use shared::buckets;
+use shared::client::{self, create_client};
+
+#[tokio::test]
+async fn test_delete_asset() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Delete Client", None).await?;
+    let namespace = client::namespace_dir(&state.config().root_dir()?, client.id());
+
+    let server = TestServerWrapper::new().await?;
+    let folder = format!("test-assets/delete-{}", client.id());
+    let first = format!("{folder}/first.txt");
+    let second = format!("{folder}/second.txt");
+    upload(&server, &header_key, client.token(), &first, b"first").await;
+    upload(&server, &header_key, client.token(), &second, b"second").await;
+
+    // Wrong asset type
+    let resp = server
+        .delete(&format!("/asset/Folder/{first}"))
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_not_found();
+
+    // Another client can't delete the asset
+    let other = create_client(state.db(), state.secrets(), "Other Client", None).await?;
+    let resp = server
+        .delete(&format!("/asset/File/{first}"))
+        .add_header(&header_key, other.token())
+        .await;
+
+    resp.assert_status_not_found();
+
+    // Delete a file
+    let resp = server
+        .delete(&format!("/asset/File/{first}?permanent=true"))
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    assert!(!namespace.join(&first).exists());
+
+    // Non-empty folder requires recursive
+    let resp = server
+        .delete(&format!("/asset/Folder/{folder}"))
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_conflict();
+
+    let resp = server
+        .delete(&format!("/asset/Folder/{folder}?recursive=true"))
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    assert!(!namespace.join(&folder).exists());
+
+    // Already deleted
+    let resp = server
+        .delete(&format!("/asset/File/{second}"))
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_not_found();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_bucket_object() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Restore Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    let bucket: String = server
+        .post("/bucket", &json!({ "visibility": "PublicRead" }))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let mut config = upload_config();
+    config.path = "report.txt".to_string();
+    config.bucket = Some(bucket.clone());
+    config.target_filesize = Some(4);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::from_static(b"data"),
+        )
+        .await
+        .assert_status_ok();
+
+    let path = buckets::object_path(&bucket, "report.txt");
+    server
+        .put(
+            "/asset/metadata",
+            &json!({ "path": path, "metadata": { "author": "finance" } }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    server
+        .put(
+            "/asset/tags",
+            &json!({ "path": path, "tags": { "status": "final" } }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    server
+        .delete(&format!("/asset/File/{path}"))
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let items: Value = server
+        .get("/trash")
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let pid = items[0]["pid"].as_str().unwrap();
+    server
+        .post(&format!("/trash/{pid}/restore"), &json!({}))
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    // Back in the bucket, with its bucket's visibility and its own attributes
+    server
+        .get(&format!("/download/{path}"))
+        .await
+        .assert_text("data");
+
+    let asset: Value = server
+        .get(&format!("/asset?path={path}"))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(asset["metadata"]["author"], "finance");
+
+    let tags: Value = server
+        .get(&format!("/asset/tags?path={path}"))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(tags["status"], "final");
+
+    let bucket = buckets::get(&bucket, state.db()).await?;
+    assert_eq!(buckets::usage(state.db(), bucket.id()).await?, 4);
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload};
+use axum::http::StatusCode;
+use axum::http::header;
+use server::state::AppState;
+use shared::client::{self, create_client};
+
+#[tokio::test]
+async fn test_download_asset() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Download Client", None).await?;
+
+    let server = TestServerWrapper::new().await?;
+    let path = format!("test-assets/download-{}/digits.txt", client.id());
+    upload(&server, &header_key, client.token(), &path, b"0123456789").await;
+
+    let url = format!("/download/{path}");
+
+    // Unauthorized
+    let stored = client::object_path(client.id(), &path);
+    server
+        .get(&format!("/download/{stored}"))
+        .await
+        .assert_status_unauthorized();
+
+    // Full content
+    let resp = server
+        .get(&url)
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    resp.assert_text("0123456789");
+    let etag = resp.header(header::ETAG);
+
+    // Not modified
+    let resp = server
+        .get(&url)
+        .add_header(&header_key, client.token())
+        .add_header(header::IF_NONE_MATCH, etag)
+        .await;
+
+    resp.assert_status(StatusCode::NOT_MODIFIED);
+
+    // Single range
+    let resp = server
+        .get(&url)
+        .add_header(&header_key, client.token())
+        .add_header(header::RANGE, "bytes=2-4")
+        .await;
+
+    resp.assert_status(StatusCode::PARTIAL_CONTENT);
+    resp.assert_text("234");
+    resp.assert_header(header::CONTENT_RANGE, "bytes 2-4/10");
+
+    // Multiple ranges
+    let resp = server
+        .get(&url)
+        .add_header(&header_key, client.token())
+        .add_header(header::RANGE, "bytes=0-1,-2")
+        .await;
+
+    resp.assert_status(StatusCode::PARTIAL_CONTENT);
+    let body = resp.text();
+    assert!(body.contains("Content-Range: bytes 0-1/10\r\n\r\n01"));
+    assert!(body.contains("Content-Range: bytes 8-9/10\r\n\r\n89"));
+
+    // Unsatisfiable range
+    let resp = server
+        .get(&url)
+        .add_header(&header_key, client.token())
+        .add_header(header::RANGE, "bytes=20-")
+        .await;
+
+    resp.assert_status(StatusCode::RANGE_NOT_SATISFIABLE);
+
+    // Content-Disposition override
+    let resp = server
+        .get(&format!(
+            "{url}?disposition=attachment&filename=numbers.txt"
+        ))
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_header(
+        header::CONTENT_DISPOSITION,
+        "attachment; filename=\"numbers.txt\"; filename*=UTF-8''numbers.txt",
+    );
+
+    Ok(())
+}
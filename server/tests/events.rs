@@ -0,0 +1,228 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload};
+use serde_json::{Value, json};
+use server::state::AppState;
+use shared::client::create_client;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Server-Sent Events read off a raw connection, since the stream never ends.
+struct EventStream {
+    stream: TcpStream,
+    buffer: String,
+}
+
+impl EventStream {
+    /// Request `url` and read the response head. Returns the response status too.
+    async fn open(
+        server: &TestServerWrapper,
+        url: &str,
+        header: Option<(&str, &str)>,
+    ) -> anyhow::Result<(u16, EventStream)> {
+        let address = server.address();
+        let host = address.trim_start_matches("http://").trim_end_matches('/');
+        let mut stream = TcpStream::connect(host).await?;
+
+        let mut request = format!("GET {url} HTTP/1.1\r\nHost: {host}\r\n");
+        if let Some((key, value)) = header {
+            request.push_str(&format!("{key}: {value}\r\n"));
+        }
+
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut events = EventStream {
+            stream,
+            buffer: String::new(),
+        };
+
+        while !events.buffer.contains("\r\n\r\n") {
+            events.read().await?;
+        }
+
+        let status = events
+            .buffer
+            .split(' ')
+            .nth(1)
+            .unwrap_or_default()
+            .parse()?;
+        let body = events.buffer.find("\r\n\r\n").unwrap() + 4;
+        events.buffer.drain(..body);
+
+        Ok((status, events))
+    }
+
+    async fn read(&mut self) -> anyhow::Result<()> {
+        let mut chunk = [0; 4096];
+        let read =
+            tokio::time::timeout(Duration::from_secs(10), self.stream.read(&mut chunk)).await??;
+
+        anyhow::ensure!(read > 0, "event stream closed");
+        self.buffer
+            .push_str(&String::from_utf8_lossy(&chunk[..read]));
+        Ok(())
+    }
+
+    /// Wait for the next event. Returns its name and data.
+    async fn next(&mut self) -> anyhow::Result<(String, Value)> {
+        loop {
+            if let Some(start) = self.buffer.find("event: ")
+                && let Some(len) = self.buffer[start..].find("\n\n")
+            {
+                let block: String = self.buffer.drain(..start + len).skip(start).collect();
+                let mut name = String::new();
+                let mut data = Value::Null;
+                for line in block.lines() {
+                    if let Some(value) = line.strip_prefix("event: ") {
+                        name = value.to_string();
+                    } else if let Some(value) = line.strip_prefix("data: ") {
+                        data = serde_json::from_str(value)?;
+                    }
+                }
+
+                return Ok((name, data));
+            }
+
+            self.read().await?;
+        }
+    }
+}
+
+fn path_of(data: &Value, field: &str) -> String {
+    let path = data[field].as_str().unwrap_or_default();
+    path.split_once('/')
+        .map(|(_, rest)| rest.to_string())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_object_events() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Events Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    let (status, _) = EventStream::open(&server, "/events?prefix=watched", None).await?;
+    assert_eq!(status, 401);
+
+    let auth = Some((header_key.as_str(), client.token()));
+    let (status, mut events) = EventStream::open(&server, "/events?prefix=watched/", auth).await?;
+    assert_eq!(status, 200);
+
+    // Uploads
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "watched/a.txt",
+        b"one",
+    )
+    .await;
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "watched/a.txt",
+        b"two",
+    )
+    .await;
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "other/b.txt",
+        b"three",
+    )
+    .await;
+
+    let (name, data) = events.next().await?;
+    assert_eq!(name, "created");
+    assert_eq!(data["path"], "watched/a.txt");
+    assert_eq!(data["bucket"], Value::Null);
+    assert_eq!(data["asset_type"], "File");
+
+    let (name, _) = events.next().await?;
+    assert_eq!(name, "updated");
+
+    // Moves, within and into the prefix
+    for (source, destination) in [
+        ("watched/a.txt", "watched/c.txt"),
+        ("other/b.txt", "watched/b.txt"),
+    ] {
+        server
+            .post(
+                "/asset/move",
+                &json!({ "source": source, "destination": destination }),
+            )
+            .add_header(&header_key, client.token())
+            .await
+            .assert_status_ok();
+    }
+
+    let (name, data) = events.next().await?;
+    assert_eq!(name, "moved");
+    assert_eq!(path_of(&data, "path"), "c.txt");
+    assert_eq!(path_of(&data, "from"), "a.txt");
+
+    let (name, data) = events.next().await?;
+    assert_eq!(name, "moved");
+    assert_eq!(path_of(&data, "path"), "b.txt");
+    assert_eq!(path_of(&data, "from"), "b.txt");
+
+    // Deletes
+    server
+        .delete("/asset/File/watched/c.txt")
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let (name, data) = events.next().await?;
+    assert_eq!(name, "deleted");
+    assert_eq!(path_of(&data, "path"), "c.txt");
+
+    // Signed listing tokens, limited to their prefix
+    let token: String = server
+        .post("/list/token", &json!({ "prefix": "other", "expires": 60 }))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let url = format!("/events?prefix=watched&token={token}");
+    let (status, mut signed) = EventStream::open(&server, &url, None).await?;
+    assert_eq!(status, 200);
+
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "watched/d.txt",
+        b"four",
+    )
+    .await;
+    upload(&server, &header_key, client.token(), "other/e.txt", b"five").await;
+
+    let (name, data) = signed.next().await?;
+    assert_eq!(name, "created");
+    assert_eq!(path_of(&data, "path"), "e.txt");
+
+    // Other clients' changes aren't sent
+    let other = create_client(state.db(), state.secrets(), "Other Events Client", None).await?;
+    upload(&server, &header_key, other.token(), "watched/f.txt", b"six").await;
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "watched/g.txt",
+        b"seven",
+    )
+    .await;
+
+    let (_, data) = events.next().await?;
+    assert_eq!(path_of(&data, "path"), "d.txt");
+    let (_, data) = events.next().await?;
+    assert_eq!(path_of(&data, "path"), "g.txt");
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload};
+use axum::http::{StatusCode, header};
+use flate2::read::GzDecoder;
+use serde_json::json;
+use server::state::AppState;
+use shared::client::{self, create_client};
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+fn zip_entries(bytes: &[u8]) -> anyhow::Result<Vec<(String, String)>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    let mut entries = vec![];
+    for idx in 0..archive.len() {
+        let mut file = archive.by_index(idx)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        entries.push((file.name().to_string(), content));
+    }
+
+    Ok(entries)
+}
+
+#[tokio::test]
+async fn test_folder_download() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Folder Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    for (path, content) in [
+        ("album/cover.txt", "cover"),
+        ("album/tracks/one.txt", "one"),
+        ("album/tracks/one.log", "log"),
+    ] {
+        upload(
+            &server,
+            &header_key,
+            client.token(),
+            path,
+            content.as_bytes(),
+        )
+        .await;
+    }
+
+    // Zip
+    let resp = server
+        .get("/folder/album")
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.header(header::CONTENT_TYPE), "application/zip");
+    let entries = zip_entries(resp.as_bytes())?;
+    assert_eq!(entries.len(), 3);
+    assert!(entries.contains(&("tracks/one.txt".to_string(), "one".to_string())));
+
+    // Globs
+    let resp = server
+        .get("/folder/album?include=**/*.txt&exclude=cover.txt")
+        .add_header(&header_key, client.token())
+        .await;
+
+    let entries = zip_entries(resp.as_bytes())?;
+    assert_eq!(
+        entries,
+        vec![("tracks/one.txt".to_string(), "one".to_string())]
+    );
+
+    // Tar.gz
+    let resp = server
+        .get("/folder/album/tracks?format=TarGz")
+        .add_header(&header_key, client.token())
+        .await;
+
+    let mut archive = tar::Archive::new(GzDecoder::new(&resp.as_bytes()[..]));
+    let mut names = vec![];
+    for entry in archive.entries()? {
+        names.push(entry?.path()?.to_string_lossy().to_string());
+    }
+
+    names.sort();
+    assert_eq!(names, vec!["one.log", "one.txt"]);
+
+    // Anonymous readers only get public files
+    let album = client::object_path(client.id(), "album");
+    let url = format!("/folder/{album}");
+    server
+        .get(&url)
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    server
+        .put(
+            "/asset/visibility",
+            &json!({ "path": "album/cover.txt", "visibility": "PublicRead" }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let entries = zip_entries(server.get(&url).await.as_bytes())?;
+    assert_eq!(
+        entries,
+        vec![("cover.txt".to_string(), "cover".to_string())]
+    );
+
+    // Files aren't folders
+    server
+        .get("/folder/album/cover.txt")
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_not_found();
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload};
+use axum::http::{StatusCode, header};
+use image::{ImageFormat, RgbImage};
+use serde_json::json;
+use server::state::AppState;
+use shared::assets;
+use shared::client::{self, create_client};
+use shared::server::{ImageFit, ImageTransform, TransformInfo, seconds_from_now};
+use std::io::Cursor;
+
+fn png(width: u32, height: u32) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Cursor::new(vec![]);
+    RgbImage::new(width, height).write_to(&mut bytes, ImageFormat::Png)?;
+    Ok(bytes.into_inner())
+}
+
+#[tokio::test]
+async fn test_image_transform() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Image Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "photo.png",
+        &png(40, 20)?,
+    )
+    .await;
+    let dimensions = |bytes: &[u8]| -> anyhow::Result<(u32, u32)> {
+        let image = image::load_from_memory(bytes)?;
+        Ok((image.width(), image.height()))
+    };
+
+    // Owner
+    let resp = server
+        .get("/download/photo.png?w=20&dpr=2&rotate=90")
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    assert_eq!(resp.header(header::CONTENT_TYPE), "image/png");
+    assert_eq!(dimensions(resp.as_bytes())?, (40, 80));
+
+    let resp = server
+        .get("/download/photo.png?w=10&h=10&fit=Cover&crop=0,0,30,20")
+        .add_header(&header_key, client.token())
+        .await;
+
+    assert_eq!(dimensions(resp.as_bytes())?, (10, 10));
+
+    server
+        .get("/download/photo.png?rotate=45")
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    // Third parties need a signed transformation
+    server
+        .put(
+            "/asset/visibility",
+            &json!({ "path": "photo.png", "visibility": "PublicRead" }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let stored = client::object_path(client.id(), "photo.png");
+    let url = format!("/download/{stored}?w=10&fit=Fill");
+    server.get(&url).await.assert_status(StatusCode::FORBIDDEN);
+
+    let sig: String = server
+        .post(
+            "/download/transform",
+            &json!({ "path": "photo.png", "expires": 60, "transform": { "w": 10, "fit": "Fill" } }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let resp = server.get(&format!("{url}&sig={sig}")).await;
+    resp.assert_status_ok();
+    assert_eq!(dimensions(resp.as_bytes())?, (10, 5));
+
+    server
+        .get(&format!("/download/{stored}?w=11&fit=Fill&sig={sig}"))
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    // Other clients can't sign transformations of the image
+    let other = create_client(state.db(), state.secrets(), "Other Image Client", None).await?;
+    let info = TransformInfo {
+        client_id: other.id().to_string(),
+        path: stored.clone(),
+        transform: ImageTransform {
+            w: Some(10),
+            fit: Some(ImageFit::Fill),
+            ..Default::default()
+        },
+        exp: seconds_from_now(60)?,
+    };
+
+    let key = client::get_key(state.db(), other.id()).await?;
+    let sig = info.sign(&key, state.hasher())?;
+    server
+        .get(&format!("{url}&sig={sig}"))
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    // Non-images
+    upload(&server, &header_key, client.token(), "notes.txt", b"notes").await;
+    server
+        .get("/download/notes.txt?w=10")
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_image_conversion() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Conversion Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "logo.png",
+        &png(8, 8)?,
+    )
+    .await;
+    let convert = async |query: &str, accept: &str| {
+        server
+            .get(&format!("/download/logo.png?{query}"))
+            .add_header(&header_key, client.token())
+            .add_header(header::ACCEPT, accept)
+            .await
+    };
+
+    let resp = convert("format=WebP", "*/*").await;
+    assert_eq!(resp.header(header::CONTENT_TYPE), "image/webp");
+    let disposition = resp.header(header::CONTENT_DISPOSITION);
+    assert!(disposition.to_str()?.contains("logo.webp"));
+
+    // Negotiated
+    let resp = convert("format=Auto", "image/avif,image/webp,*/*").await;
+    assert_eq!(resp.header(header::CONTENT_TYPE), "image/avif");
+    assert_eq!(resp.header(header::VARY), "accept");
+
+    let resp = convert("format=Auto", "image/avif;q=0,image/webp").await;
+    assert_eq!(resp.header(header::CONTENT_TYPE), "image/webp");
+
+    let resp = convert("format=Auto", "image/*").await;
+    assert_eq!(resp.header(header::CONTENT_TYPE), "image/png");
+
+    // Overwriting the source drops its conversions
+    let stored = client::object_path(client.id(), "logo.png");
+    let asset = assets::get(state.db(), &stored)
+        .await?
+        .expect("asset indexed");
+    let variants = asset.variants_dir(&state.config().root_dir()?);
+    assert!(variants.is_dir());
+
+    upload(
+        &server,
+        &header_key,
+        client.token(),
+        "logo.png",
+        &png(4, 4)?,
+    )
+    .await;
+    assert!(!variants.exists());
+
+    let resp = convert("format=Jpeg", "*/*").await;
+    let image = image::load_from_memory(resp.as_bytes())?;
+    assert_eq!((image.width(), image.height()), (4, 4));
+
+    Ok(())
+}
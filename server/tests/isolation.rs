@@ -0,0 +1,95 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload, upload_config};
+use serde_json::{Value, json};
+use server::state::AppState;
+use shared::client::{self, create_client};
+
+#[tokio::test]
+async fn test_client_namespaces() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let alice = create_client(state.db(), state.secrets(), "Alice Client", None).await?;
+    let bob = create_client(state.db(), state.secrets(), "Bob Client", None).await?;
+    let root_dir = state.config().root_dir()?;
+
+    let server = TestServerWrapper::new().await?;
+    let path = "shared/report.txt";
+    upload(&server, &header_key, alice.token(), path, b"alice").await;
+    upload(&server, &header_key, bob.token(), path, b"bob").await;
+
+    // Same path, separate objects
+    let alice_file = client::namespace_dir(&root_dir, alice.id()).join(path);
+    let bob_file = client::namespace_dir(&root_dir, bob.id()).join(path);
+    assert_eq!(tokio::fs::read(&alice_file).await?, b"alice");
+    assert_eq!(tokio::fs::read(&bob_file).await?, b"bob");
+
+    for (token, content) in [(alice.token(), "alice"), (bob.token(), "bob")] {
+        server
+            .get(&format!("/download/{path}"))
+            .add_header(&header_key, token)
+            .await
+            .assert_text(content);
+    }
+
+    // Bob can't reach Alice's object through its storage path
+    let alice_path = client::object_path(alice.id(), path);
+    server
+        .get(&format!("/download/{alice_path}"))
+        .add_header(&header_key, bob.token())
+        .await
+        .assert_status_not_found();
+
+    server
+        .delete(&format!("/asset/File/{alice_path}?permanent=true"))
+        .add_header(&header_key, bob.token())
+        .await
+        .assert_status_not_found();
+
+    // Writing to Alice's storage path lands in Bob's namespace
+    upload(&server, &header_key, bob.token(), &alice_path, b"intruder").await;
+    assert_eq!(tokio::fs::read(&alice_file).await?, b"alice");
+
+    // Paths can't escape the namespace
+    let mut config = upload_config();
+    config.path = format!("../{}/{path}", alice.id());
+    config.target_filesize = Some(1);
+    server
+        .post("/upload/session", &config)
+        .add_header(&header_key, bob.token())
+        .await
+        .assert_status_bad_request();
+
+    // Alice's buckets are off limits
+    let bucket: String = server
+        .post("/bucket", &json!({}))
+        .add_header(&header_key, alice.token())
+        .await
+        .json();
+
+    config.path = "notes.txt".to_string();
+    config.bucket = Some(bucket);
+    server
+        .post("/upload/session", &config)
+        .add_header(&header_key, bob.token())
+        .await
+        .assert_status_not_found();
+
+    // Listings only include the client's objects
+    let list: Value = server
+        .get("/list?prefix=shared/")
+        .add_header(&header_key, bob.token())
+        .await
+        .json();
+
+    let keys: Vec<&str> = list["contents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|entry| entry["key"].as_str())
+        .collect();
+
+    assert_eq!(keys, vec![path]);
+
+    Ok(())
+}
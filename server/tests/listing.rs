@@ -0,0 +1,137 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload, upload_config};
+use axum::body::Bytes;
+use serde_json::{Value, json};
+use server::state::AppState;
+use shared::client::create_client;
+
+/// Names of the files in a listing.
+fn names(list: &Value) -> Vec<String> {
+    let mut names: Vec<String> = list["contents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["asset_type"] == "File")
+        .filter_map(|entry| entry["key"].as_str())
+        .map(|key| key.rsplit('/').next().unwrap().to_string())
+        .collect();
+
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn test_list_token_scope() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Listing Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    upload(&server, &header_key, client.token(), "docs/a.txt", b"a").await;
+    upload(&server, &header_key, client.token(), "docs2/b.txt", b"b").await;
+
+    let bucket: String = server
+        .post("/bucket", &json!({}))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let mut config = upload_config();
+    config.path = "c.txt".to_string();
+    config.bucket = Some(bucket.clone());
+    config.target_filesize = Some(1);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::from_static(b"c"),
+        )
+        .await
+        .assert_status_ok();
+
+    let list_token = |prefix: &str| {
+        server
+            .post("/list/token", &json!({ "prefix": prefix, "expires": 60 }))
+            .add_header(&header_key, client.token())
+    };
+
+    // Token prefixes are folders
+    let token: String = list_token("docs").await.json();
+    for prefix in ["docs", "docs2", "doc"] {
+        let list: Value = server
+            .get(&format!("/list?prefix={prefix}&token={token}"))
+            .await
+            .json();
+
+        assert_eq!(names(&list), vec!["a.txt"]);
+    }
+
+    // Tokens for the client's namespace don't reach its buckets
+    let token: String = list_token("").await.json();
+    let list: Value = server
+        .get(&format!("/list?bucket={bucket}&token={token}"))
+        .await
+        .json();
+
+    assert_eq!(names(&list), vec!["a.txt", "b.txt"]);
+
+    let list: Value = server
+        .get(&format!("/list?prefix=buckets/{bucket}/&token={token}"))
+        .await
+        .json();
+
+    assert_eq!(names(&list), vec!["a.txt", "b.txt"]);
+
+    // Keys are relative to their namespace, so they can be sent back as is
+    let list: Value = server
+        .get(&format!("/list?bucket={bucket}"))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(list["contents"][0]["key"], "c.txt");
+    assert_eq!(list["contents"][0]["bucket"], bucket.as_str());
+
+    let list: Value = server
+        .get("/list?prefix=docs&delimiter=/")
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(list["prefix"], "docs");
+    assert_eq!(list["common_prefixes"], json!(["docs/", "docs2/"]));
+
+    let list: Value = server
+        .get("/list?prefix=docs&start_after=docs/a.txt")
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let keys: Vec<&str> = list["contents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|entry| entry["key"].as_str())
+        .collect();
+
+    assert_eq!(keys, vec!["docs2/", "docs2/b.txt"]);
+
+    // Tag filters are limited
+    let filters: Vec<String> = (0..300).map(|idx| format!("key{idx}")).collect();
+    for url in ["/list", "/asset/tagged"] {
+        server
+            .get(&format!("{url}?tags={}", filters.join(",")))
+            .add_header(&header_key, client.token())
+            .await
+            .assert_status_bad_request();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload, upload_config};
+use axum::body::Bytes;
+use axum::http::{StatusCode, header};
+use serde_json::json;
+use server::state::AppState;
+use shared::client::create_client;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_live_download() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Live Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    // Files which aren't being uploaded are served as is
+    upload(&server, &header_key, client.token(), "done.log", b"done").await;
+    server
+        .get("/live/done.log")
+        .add_header(&header_key, client.token())
+        .await
+        .assert_text("done");
+
+    server
+        .get("/live/done.log")
+        .await
+        .assert_status_unauthorized();
+
+    let token: String = server
+        .post(
+            "/download/token",
+            &json!({ "path": "done.log", "expires": 60 }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .get(&format!("/live/done.log?token={token}"))
+        .await
+        .assert_text("done");
+
+    // One-time links stay one-time, overrides included
+    let body = json!({
+        "path": "done.log",
+        "expires": 60,
+        "max_downloads": 1,
+        "disposition": "attachment",
+    });
+
+    let token: String = server
+        .post("/download/token", &body)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let url = format!("/live/done.log?token={token}");
+    let resp = server.get(&url).await;
+    resp.assert_text("done");
+    assert!(
+        resp.header(header::CONTENT_DISPOSITION)
+            .to_str()?
+            .starts_with("attachment")
+    );
+
+    server.get(&url).await.assert_status(StatusCode::GONE);
+
+    server
+        .get("/live/missing.log")
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_not_found();
+
+    // Streaming uploads in progress needs resumable uploads, hence a message broker.
+    if state.config().message_broker.is_none() {
+        return Ok(());
+    }
+
+    let mut config = upload_config();
+    config.path = "live.log".to_string();
+    config.resumable = Some(true);
+    config.overwrite = Some(true);
+    config.target_filesize = Some(10);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let next: Option<String> = server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::from_static(b"first"),
+        )
+        .await
+        .json();
+
+    let next = next.expect("upload is resumable");
+    let reader = server
+        .get("/live/live.log")
+        .add_header(&header_key, client.token());
+
+    let writer = async {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        server
+            .post_bytes(
+                &format!("/upload/session/play/{next}"),
+                Bytes::from_static(b"-last"),
+            )
+            .await
+            .assert_status_ok();
+    };
+
+    let (resp, _) = tokio::join!(reader.into_future(), writer);
+    resp.assert_text("first-last");
+
+    Ok(())
+}
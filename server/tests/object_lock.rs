@@ -0,0 +1,246 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload, upload_config};
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+use server::state::AppState;
+use shared::buckets;
+use shared::client::create_client;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[tokio::test]
+async fn test_object_lock() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Lock Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    // Bucket default retention
+    let bucket: String = server
+        .post(
+            "/bucket",
+            &json!({ "retention": { "mode": "Governance", "days": 1 } }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let mut config = upload_config();
+    config.path = "report.txt".to_string();
+    config.bucket = Some(bucket.clone());
+    config.target_filesize = Some(6);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::from_static(b"report"),
+        )
+        .await
+        .assert_status_ok();
+
+    let report = buckets::object_path(&bucket, "report.txt");
+    let url = format!("/asset/File/{report}?permanent=true");
+    server
+        .delete(&url)
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::LOCKED);
+
+    server
+        .delete(&format!("{url}&bypass_governance=true"))
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    // Legal hold
+    upload(&server, &header_key, client.token(), "held.txt", b"held").await;
+    server
+        .put(
+            "/asset/legal-hold",
+            &json!({ "path": "held.txt", "hold": true }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let mut config = upload_config();
+    config.path = "held.txt".to_string();
+    config.overwrite = Some(true);
+    config.target_filesize = Some(3);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::from_static(b"new"),
+        )
+        .await
+        .assert_status(StatusCode::LOCKED);
+
+    server
+        .post(
+            "/asset/move",
+            &json!({ "source": "held.txt", "destination": "moved.txt" }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::LOCKED);
+
+    server
+        .put(
+            "/asset/legal-hold",
+            &json!({ "path": "held.txt", "hold": false }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    server
+        .post(
+            "/asset/move",
+            &json!({ "source": "held.txt", "destination": "moved.txt" }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    // Compliance retention can't be bypassed or shortened
+    let retain_until = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + 60 * 60;
+    let asset: Value = server
+        .put(
+            "/asset/retention",
+            &json!({ "path": "moved.txt", "mode": "Compliance", "retain_until": retain_until }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(asset["lock"]["retain_until"], retain_until);
+
+    server
+        .delete("/asset/File/moved.txt?bypass_governance=true")
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::LOCKED);
+
+    server
+        .put(
+            "/asset/retention",
+            &json!({
+                "path": "moved.txt",
+                "mode": "Governance",
+                "retain_until": retain_until,
+                "bypass_governance": true
+            }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::CONFLICT);
+
+    // Buckets holding locked objects can't be deleted
+    let bucket: String = server
+        .post(
+            "/bucket",
+            &json!({ "retention": { "mode": "Compliance", "days": 1 } }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let mut config = upload_config();
+    config.path = "ledger.txt".to_string();
+    config.bucket = Some(bucket.clone());
+    config.target_filesize = Some(6);
+
+    let token: String = server
+        .post("/upload/session", &config)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .post_bytes(
+            &format!("/upload/session/play/{token}"),
+            Bytes::from_static(b"ledger"),
+        )
+        .await
+        .assert_status_ok();
+
+    let url = format!("/bucket/{bucket}?force=true");
+    for url in [url.clone(), format!("{url}&bypass_governance=true")] {
+        server
+            .delete(&url)
+            .add_header(&header_key, client.token())
+            .await
+            .assert_status(StatusCode::CONFLICT);
+    }
+
+    let ledger = buckets::object_path(&bucket, "ledger.txt");
+    assert!(state.config().root_dir()?.join(ledger).is_file());
+
+    // Locked objects can't be replaced by a prior version
+    let bucket: String = server
+        .post("/bucket", &json!({ "versioning": true }))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    for content in [b"first", b"final"] {
+        let mut config = upload_config();
+        config.path = "draft.txt".to_string();
+        config.bucket = Some(bucket.clone());
+        config.overwrite = Some(true);
+        config.target_filesize = Some(5);
+
+        let token: String = server
+            .post("/upload/session", &config)
+            .add_header(&header_key, client.token())
+            .await
+            .json();
+
+        server
+            .post_bytes(
+                &format!("/upload/session/play/{token}"),
+                Bytes::copy_from_slice(content),
+            )
+            .await
+            .assert_status_ok();
+    }
+
+    let draft = buckets::object_path(&bucket, "draft.txt");
+    server
+        .put("/asset/legal-hold", &json!({ "path": draft, "hold": true }))
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let versions: Value = server
+        .get(&format!("/asset/versions?bucket={bucket}&path=draft.txt"))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let pid = versions[0]["pid"].as_str().unwrap();
+    server
+        .post(&format!("/asset/versions/{pid}/restore"), &json!({}))
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status(StatusCode::LOCKED);
+
+    let content = tokio::fs::read_to_string(state.config().root_dir()?.join(&draft)).await?;
+    assert_eq!(content, "final");
+
+    Ok(())
+}
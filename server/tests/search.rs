@@ -0,0 +1,145 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload};
+use serde_json::{Value, json};
+use server::state::AppState;
+use shared::client::create_client;
+
+#[tokio::test]
+async fn test_search_assets() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Search Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    for path in [
+        "reports/annual_report-2024.pdf",
+        "reports/draft.txt",
+        "photos/holiday.jpg",
+    ] {
+        upload(&server, &header_key, client.token(), path, b"content").await;
+    }
+
+    let search = |query: &str| {
+        server
+            .get(&format!("/search?{query}"))
+            .add_header(&header_key, client.token())
+    };
+
+    let keys = |result: &Value| -> Vec<String> {
+        let mut keys: Vec<String> = result["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                let key = entry["key"].as_str().unwrap().trim_end_matches('/');
+                key.rsplit('/').next().unwrap().to_string()
+            })
+            .collect();
+
+        keys.sort();
+        keys
+    };
+
+    // File names and folders, by words and prefixes of words
+    let result: Value = search("q=annual+2024").await.json();
+    assert_eq!(keys(&result), vec!["annual_report-2024.pdf"]);
+
+    let result: Value = search("q=repo").await.json();
+    assert_eq!(
+        keys(&result),
+        vec!["annual_report-2024.pdf", "draft.txt", "reports"]
+    );
+
+    // Metadata and tags
+    server
+        .put(
+            "/asset/metadata",
+            &json!({ "path": "photos/holiday.jpg", "metadata": { "location": "Lisbon" } }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    server
+        .put(
+            "/asset/tags",
+            &json!({ "path": "reports/draft.txt", "tags": { "status": "pending" } }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let result: Value = search("q=lisbon").await.json();
+    assert_eq!(keys(&result), vec!["holiday.jpg"]);
+
+    let result: Value = search("q=pending").await.json();
+    assert_eq!(keys(&result), vec!["draft.txt"]);
+
+    // Prefix and pages
+    let result: Value = search("q=repo&prefix=photos").await.json();
+    assert!(keys(&result).is_empty());
+
+    let first: Value = search("q=repo&max_results=2").await.json();
+    assert_eq!(first["is_truncated"], true);
+    let token = first["next_continuation_token"].as_str().unwrap();
+    let second: Value = search(&format!("q=repo&max_results=2&continuation_token={token}"))
+        .await
+        .json();
+
+    assert_eq!(second["is_truncated"], false);
+    let mut all = [keys(&first), keys(&second)].concat();
+    all.sort();
+    assert_eq!(all, vec!["annual_report-2024.pdf", "draft.txt", "reports"]);
+
+    // Moves and deletes
+    server
+        .post(
+            "/asset/move",
+            &json!({ "source": "reports/draft.txt", "destination": "photos/notes.txt" }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let result: Value = search("q=notes").await.json();
+    assert_eq!(keys(&result), vec!["notes.txt"]);
+    let result: Value = search("q=draft").await.json();
+    assert!(keys(&result).is_empty());
+
+    server
+        .delete("/asset/File/photos/notes.txt")
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    let result: Value = search("q=notes").await.json();
+    assert!(keys(&result).is_empty());
+
+    // Other clients' assets aren't found
+    let other = create_client(state.db(), state.secrets(), "Other Search Client", None).await?;
+    let result: Value = server
+        .get("/search?q=annual")
+        .add_header(&header_key, other.token())
+        .await
+        .json();
+
+    assert!(keys(&result).is_empty());
+
+    // Two-letter words and stopwords, which MySQL's full-text index leaves out
+    let path = "photos/q3-of-the-year.csv";
+    upload(&server, &header_key, client.token(), path, b"content").await;
+
+    let result: Value = search("q=q3").await.json();
+    assert_eq!(keys(&result), vec!["q3-of-the-year.csv"]);
+
+    let result: Value = search("q=of+the").await.json();
+    assert_eq!(keys(&result), vec!["q3-of-the-year.csv"]);
+
+    let result: Value = search("q=q3+year").await.json();
+    assert_eq!(keys(&result), vec!["q3-of-the-year.csv"]);
+
+    search("q=--").await.assert_status_bad_request();
+
+    Ok(())
+}
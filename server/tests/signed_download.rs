@@ -0,0 +1,131 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload};
+use axum::http::StatusCode;
+use axum::http::header;
+use serde_json::json;
+use server::state::AppState;
+use shared::client::create_client;
+
+#[tokio::test]
+async fn test_signed_download() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Signed Client", None).await?;
+
+    let server = TestServerWrapper::new().await?;
+    let folder = format!("test-assets/signed-{}", client.id());
+    let path = format!("{folder}/report.txt");
+    let other = format!("{folder}/other.txt");
+    upload(&server, &header_key, client.token(), &path, b"quarterly").await;
+    upload(&server, &header_key, client.token(), &other, b"other").await;
+
+    // Minting requires the client
+    let body = json!({ "path": path, "expires": 60 });
+    server
+        .post("/download/token", &body)
+        .await
+        .assert_status_unauthorized();
+
+    let body = json!({
+        "path": path,
+        "expires": 60,
+        "max_downloads": 1,
+        "disposition": "attachment",
+        "filename": "q3.txt",
+    });
+
+    let resp = server
+        .post("/download/token", &body)
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    let token: String = resp.json();
+
+    // Revalidation doesn't count as a download
+    let etag = server
+        .get(&format!("/download/{path}"))
+        .add_header(&header_key, client.token())
+        .await
+        .header(header::ETAG);
+
+    server
+        .get(&format!("/signed/{path}?token={token}"))
+        .add_header(header::IF_NONE_MATCH, etag)
+        .await
+        .assert_status(StatusCode::NOT_MODIFIED);
+
+    // Scoped to a single file
+    server
+        .get(&format!("/signed/{other}?token={token}"))
+        .await
+        .assert_status_forbidden();
+
+    // Anonymous download with overrides
+    let url = format!("/signed/{path}?token={token}");
+    let resp = server.get(&url).await;
+    resp.assert_status_ok();
+    resp.assert_text("quarterly");
+    resp.assert_header(
+        header::CONTENT_DISPOSITION,
+        "attachment; filename=\"q3.txt\"; filename*=UTF-8''q3.txt",
+    );
+
+    // Download limit
+    server.get(&url).await.assert_status(StatusCode::GONE);
+
+    // Prefix scope
+    let body = json!({ "prefix": format!("{folder}/"), "expires": 60 });
+    let token: String = server
+        .post("/download/token", &body)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let resp = server.get(&format!("/signed/{other}?token={token}")).await;
+    resp.assert_status_ok();
+    resp.assert_text("other");
+
+    // Prefixes are folders
+    let secret = format!("{folder}-secret/key.txt");
+    upload(&server, &header_key, client.token(), &secret, b"secret").await;
+    let body = json!({ "prefix": folder, "expires": 60 });
+    let token: String = server
+        .post("/download/token", &body)
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    server
+        .get(&format!("/signed/{other}?token={token}"))
+        .await
+        .assert_status_ok();
+
+    server
+        .get(&format!("/signed/{secret}?token={token}"))
+        .await
+        .assert_status_forbidden();
+
+    // Download tokens aren't listing tokens
+    server
+        .get(&format!("/list?token={token}"))
+        .await
+        .assert_status_unauthorized();
+
+    // Tampered token
+    server
+        .get(&format!("/signed/{other}?token={token}x"))
+        .await
+        .assert_status_unauthorized();
+
+    // Missing path and prefix
+    let body = json!({ "expires": 60 });
+    server
+        .post("/download/token", &body)
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_bad_request();
+
+    Ok(())
+}
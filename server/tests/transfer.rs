@@ -0,0 +1,148 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload, upload_config};
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+use server::state::AppState;
+use shared::buckets;
+use shared::client::{self, create_client};
+
+#[tokio::test]
+async fn test_copy_and_move_asset() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Transfer Client", None).await?;
+    let namespace = client::namespace_dir(&state.config().root_dir()?, client.id());
+
+    let server = TestServerWrapper::new().await?;
+    let folder = format!("test-assets/transfer-{}", client.id());
+    let source = format!("{folder}/a.txt");
+    upload(&server, &header_key, client.token(), &source, b"transfer").await;
+
+    // Copy
+    let copy = format!("{folder}/b.txt");
+    let body = json!({ "source": source, "destination": copy });
+    let resp = server
+        .post("/asset/copy", &body)
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    assert!(namespace.join(&source).is_file());
+    assert!(namespace.join(&copy).is_file());
+
+    // Destination exists and overwrite isn't set
+    let resp = server
+        .post("/asset/copy", &body)
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status(StatusCode::CONFLICT);
+
+    // Missing parent
+    let moved = format!("{folder}/nested/c.txt");
+    let mut body = json!({ "source": copy, "destination": moved });
+    let resp = server
+        .post("/asset/move", &body)
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_bad_request();
+
+    // Move
+    body["create_parents"] = json!(true);
+    let resp = server
+        .post("/asset/move", &body)
+        .add_header(&header_key, client.token())
+        .await;
+
+    resp.assert_status_ok();
+    assert!(!namespace.join(&copy).exists());
+    assert_eq!(tokio::fs::read(namespace.join(&moved)).await?, b"transfer");
+
+    // Copy into a missing parent
+    let copied = format!("{folder}/deeper/nested/d.txt");
+    let body = json!({ "source": moved, "destination": copied, "create_parents": true });
+    server
+        .post("/asset/copy", &body)
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    assert_eq!(tokio::fs::read(namespace.join(&copied)).await?, b"transfer");
+
+    // Another client can't move the asset
+    let other = create_client(state.db(), state.secrets(), "Other Client", None).await?;
+    let body = json!({ "source": moved, "destination": format!("{folder}/stolen.txt") });
+    let resp = server
+        .post("/asset/move", &body)
+        .add_header(&header_key, other.token())
+        .await;
+
+    resp.assert_status_not_found();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_overwrite_in_versioned_bucket() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Overwrite Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    let bucket: String = server
+        .post("/bucket", &json!({ "size": 16, "versioning": true }))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    for path in ["docs/a.txt", "c.txt"] {
+        let mut config = upload_config();
+        config.path = path.to_string();
+        config.bucket = Some(bucket.clone());
+        config.create_parents = Some(true);
+        config.target_filesize = Some(8);
+
+        let token: String = server
+            .post("/upload/session", &config)
+            .add_header(&header_key, client.token())
+            .await
+            .json();
+
+        server
+            .post_bytes(
+                &format!("/upload/session/play/{token}"),
+                Bytes::from_static(b"versions"),
+            )
+            .await
+            .assert_status_ok();
+    }
+
+    // The bucket is full, but the copy replaces as much as it adds
+    let body = json!({
+        "source": buckets::object_path(&bucket, "c.txt"),
+        "destination": "docs",
+        "bucket": bucket,
+        "overwrite": true,
+    });
+
+    server
+        .post("/asset/copy", &body)
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    // Files of the overwritten folder are kept as versions
+    let versions: Value = server
+        .get(&format!("/asset/versions?bucket={bucket}&path=docs/a.txt"))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    assert_eq!(versions.as_array().map(Vec::len), Some(2));
+    assert_eq!(versions[0]["delete_marker"], true);
+
+    Ok(())
+}
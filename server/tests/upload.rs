@@ -20,7 +20,7 @@ async fn test_create_upload_session() -> anyhow::Result<()> {
     let filepath = root_dir()?.join("test-assets/demo.jpg");
     let metadata = tokio::fs::metadata(&filepath).await?;
 
-    let mut upload_config = (&upload_config()).clone();
+    let mut upload_config = upload_config();
     upload_config.target_filesize = Some(metadata.len());
 
     let url = "/upload/session";
@@ -167,7 +167,7 @@ async fn test_play_upload_resumable_session() -> anyhow::Result<()> {
     while let Some(Ok(next_chunk)) = stream.next().await
         && let Some(token) = &next_token
     {
-        let request = server.post_bytes(&get_upload_url(&token), next_chunk);
+        let request = server.post_bytes(&get_upload_url(token), next_chunk);
 
         let resp = request.await;
         resp.assert_status_ok();
@@ -0,0 +1,107 @@
+mod common;
+
+use crate::common::{TestServerWrapper, upload, upload_config};
+use axum::body::Bytes;
+use axum::http::header;
+use serde_json::json;
+use server::state::AppState;
+use shared::acl::Visibility;
+use shared::buckets;
+use shared::client::{self, create_client};
+
+#[tokio::test]
+async fn test_public_and_private_objects() -> anyhow::Result<()> {
+    let state = AppState::new().await?;
+    let header_key = state.config().client_header_key.clone();
+    let client = create_client(state.db(), state.secrets(), "Visibility Client", None).await?;
+    let server = TestServerWrapper::new().await?;
+
+    let bucket: String = server
+        .post("/bucket", &json!({ "visibility": "PublicRead" }))
+        .add_header(&header_key, client.token())
+        .await
+        .json();
+
+    let upload_to_bucket = async |path: &str, visibility: Option<Visibility>| {
+        let mut config = upload_config();
+        config.path = path.to_string();
+        config.bucket = Some(bucket.clone());
+        config.target_filesize = Some(4);
+        config.visibility = visibility;
+
+        let token: String = server
+            .post("/upload/session", &config)
+            .add_header(&header_key, client.token())
+            .await
+            .json();
+
+        server
+            .post_bytes(
+                &format!("/upload/session/play/{token}"),
+                Bytes::from_static(b"data"),
+            )
+            .await
+            .assert_status_ok();
+    };
+
+    upload_to_bucket("public.txt", None).await;
+    upload_to_bucket("private.txt", Some(Visibility::Private)).await;
+
+    // Bucket default
+    let public = buckets::object_path(&bucket, "public.txt");
+    server
+        .get(&format!("/download/{public}"))
+        .await
+        .assert_text("data");
+
+    // Anonymous readers can't change how public files are interpreted or cached
+    let resp = server
+        .get(&format!(
+            "/download/{public}?content_type=text/html&cache_control=max-age=31536000&disposition=attachment"
+        ))
+        .await;
+
+    resp.assert_header(header::CONTENT_TYPE, "text/plain");
+    resp.assert_header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    assert!(!resp.headers().contains_key(header::CACHE_CONTROL));
+    assert!(
+        resp.header(header::CONTENT_DISPOSITION)
+            .to_str()?
+            .starts_with("attachment;")
+    );
+
+    // Object override
+    let private = buckets::object_path(&bucket, "private.txt");
+    let url = format!("/download/{private}");
+    server.get(&url).await.assert_status_unauthorized();
+    server
+        .get(&url)
+        .add_header(&header_key, client.token())
+        .await
+        .assert_text("data");
+
+    // Objects outside buckets are private until made public
+    upload(&server, &header_key, client.token(), "notes.txt", b"notes").await;
+    let notes = client::object_path(client.id(), "notes.txt");
+    let url = format!("/download/{notes}");
+    server.get(&url).await.assert_status_unauthorized();
+
+    server
+        .put(
+            "/asset/visibility",
+            &json!({ "path": "notes.txt", "visibility": "PublicRead" }),
+        )
+        .add_header(&header_key, client.token())
+        .await
+        .assert_status_ok();
+
+    server.get(&url).await.assert_text("notes");
+
+    // Unknown objects
+    server
+        .get(&format!("/download/{notes}.missing"))
+        .await
+        .assert_status_not_found();
+
+    Ok(())
+}
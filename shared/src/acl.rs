@@ -0,0 +1,171 @@
+use crate::assets::{self, Asset};
+use crate::buckets;
+use crate::db::Database;
+use crate::sql_safe;
+use crate::tags::{self, TagFilter};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+/// Who may read an object.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+pub enum Visibility {
+    /// Only the owner, or holders of a signed URL.
+    #[default]
+    Private,
+    /// Anyone, without authentication.
+    PublicRead,
+}
+
+impl From<i16> for Visibility {
+    fn from(value: i16) -> Self {
+        use Visibility::*;
+
+        match value {
+            0 => Private,
+            1 => PublicRead,
+            _ => Default::default(),
+        }
+    }
+}
+
+impl From<Visibility> for i16 {
+    fn from(value: Visibility) -> Self {
+        use Visibility::*;
+
+        match value {
+            Private => 0,
+            PublicRead => 1,
+        }
+    }
+}
+
+/// Visibility set on an object, overriding its bucket's default. Stored as a nullable
+/// `SMALLINT`.
+#[derive(Serialize, Clone, Copy, Default)]
+#[serde(transparent)]
+pub struct VisibilityOverride(Option<Visibility>);
+
+impl VisibilityOverride {
+    pub fn get(&self) -> Option<Visibility> {
+        self.0
+    }
+}
+
+impl From<Option<i16>> for VisibilityOverride {
+    fn from(value: Option<i16>) -> Self {
+        Self(value.map(Visibility::from))
+    }
+}
+
+/// Set the visibility of an indexed asset. `None` makes it inherit its bucket's default.
+pub async fn set(
+    db: &Database,
+    path: &str,
+    visibility: Option<Visibility>,
+) -> anyhow::Result<Asset> {
+    let path = assets::normalize(path);
+    let query = sql_safe!(
+        "UPDATE assets SET visibility = {} WHERE path = {}",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    sqlx::query(query)
+        .bind(visibility.map(i16::from))
+        .bind(&path)
+        .execute(&**db)
+        .await?;
+
+    assets::get(db, &path)
+        .await?
+        .ok_or(anyhow!("asset {path} is not indexed"))
+}
+
+/// Visibility an asset is served with. The asset's own visibility wins. Otherwise, assets in a
+/// bucket are public when they match the bucket's `public_tag`, else they get the bucket's
+/// default. Assets outside of buckets are private.
+pub async fn effective(db: &Database, asset: &Asset) -> anyhow::Result<Visibility> {
+    if let Some(visibility) = asset.visibility() {
+        return Ok(visibility);
+    }
+
+    let bucket = match asset.bucket_id() {
+        Some(id) => buckets::get_by_id(id, db).await?,
+        None => return Ok(Visibility::Private),
+    };
+
+    if let Some(filter) = bucket.public_tag() {
+        let filter: TagFilter = filter.parse()?;
+        if filter.matches(&tags::get(db, asset.id()).await?) {
+            return Ok(Visibility::PublicRead);
+        }
+    }
+
+    Ok(bucket.visibility())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::acl::{self, Visibility};
+    use crate::assets::{self, AssetRecord, AssetType};
+    use crate::buckets::{self, AssetOwnerName, CreateBucketData};
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::secrets::AppSecrets;
+    use crate::tags::{self, TagSet};
+    use std::env;
+
+    #[tokio::test]
+    async fn test_effective_visibility() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Visibility Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let data = CreateBucketData {
+            size: None,
+            accepts: None,
+            owner_type: AssetOwnerName::Client,
+            owner_id: client_id,
+            versioning: false,
+            visibility: Visibility::Private,
+            public_tag: Some("share=public".parse()?),
+            retention: None,
+            precompress: false,
+        };
+
+        let pid = buckets::create(data, &db).await?;
+        let bucket = buckets::get(&pid, &db).await?;
+
+        let index = |name: &str| AssetRecord {
+            path: buckets::object_path(&pid, name),
+            asset_type: AssetType::File,
+            size: 1,
+            content_type: None,
+            checksum: None,
+            owner_id,
+            bucket_id: Some(bucket.id()),
+        };
+
+        let private = assets::upsert(&db, index("private.txt")).await?;
+        let tagged = assets::upsert(&db, index("tagged.txt")).await?;
+        let tag_set: TagSet = [("share".to_string(), "public".to_string())].into();
+        tags::put(&db, tagged.id(), &tag_set).await?;
+
+        assert_eq!(acl::effective(&db, &private).await?, Visibility::Private);
+        assert_eq!(acl::effective(&db, &tagged).await?, Visibility::PublicRead);
+
+        let private = acl::set(&db, private.path(), Some(Visibility::PublicRead)).await?;
+        assert_eq!(acl::effective(&db, &private).await?, Visibility::PublicRead);
+
+        buckets::set_visibility(&pid, Visibility::PublicRead, None, &db).await?;
+        let tagged = acl::set(&db, tagged.path(), Some(Visibility::Private)).await?;
+        assert_eq!(acl::effective(&db, &tagged).await?, Visibility::Private);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,580 @@
+use crate::assets::{AssetType, StorageClass, check_path};
+use crate::db::{Database, DbEngine};
+use crate::trash::TRASH_DIR;
+use crate::utils::unix_timestamp;
+use crate::versions::VERSIONS_DIR;
+use crate::{generate_nano_id, move_path, search, sql_safe};
+use anyhow::anyhow;
+use argon2::Argon2;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::aead::common::Generate;
+use chacha20poly1305::{Key, KeyInit, XChaCha20Poly1305, XNonce};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use sqlx::any::{AnyRow, AnyTypeInfoKind};
+use sqlx::{Column, Row, ValueRef};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+/// Version of the archive layout written by [export].
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Exported tables, ordered so that rows are restored after the rows they reference.
+const TABLES: [&str; 11] = [
+    "asset_owner",
+    "clients",
+    "users",
+    "buckets",
+    "assets",
+    "asset_tags",
+    "asset_versions",
+    "trash",
+    "lifecycle_rules",
+    "audit_log",
+    "download_grants",
+];
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const SECRETS_ENTRY: &str = "secrets.enc";
+const TABLES_DIR: &str = "tables";
+const OBJECTS_DIR: &str = "objects";
+
+/// Describes the content of an archive.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub created_at: i64,
+    /// Number of rows exported per table.
+    pub tables: BTreeMap<String, usize>,
+    /// Hex encoded blake3 digest of every object, keyed by path relative to the storage root.
+    pub objects: BTreeMap<String, String>,
+}
+
+/// Export the database rows, stored objects and secrets of an instance to a gzipped tar archive.
+/// The secrets file is encrypted with `passphrase`.
+pub async fn export(
+    db: &Database,
+    root_dir: &Path,
+    secrets_file: &Path,
+    passphrase: &str,
+    output: &Path,
+) -> anyhow::Result<Manifest> {
+    let mut tables = BTreeMap::new();
+    for table in TABLES {
+        tables.insert(table.to_string(), dump_table(db, table).await?);
+    }
+
+    let mut objects = BTreeMap::new();
+    for path in object_paths(db, root_dir).await? {
+        let digest = crate::assets::checksum(&root_dir.join(&path)).await?;
+        objects.insert(path, digest);
+    }
+
+    let secrets = tokio::fs::read(secrets_file).await?;
+    let secrets = encrypt(&secrets, passphrase)?;
+
+    let manifest = Manifest {
+        version: ARCHIVE_VERSION,
+        created_at: unix_timestamp()?,
+        tables: tables
+            .iter()
+            .map(|(name, rows)| (name.clone(), rows.len()))
+            .collect(),
+        objects,
+    };
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    let root_dir = root_dir.to_path_buf();
+    let output = output.to_path_buf();
+    let paths: Vec<String> = manifest.objects.keys().cloned().collect();
+
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let file = File::create(&output)?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+        for (name, rows) in &tables {
+            let data = serde_json::to_vec(rows)?;
+            append_data(&mut builder, &format!("{TABLES_DIR}/{name}.json"), &data)?;
+        }
+
+        append_data(&mut builder, SECRETS_ENTRY, &secrets)?;
+        for path in paths {
+            let name = format!("{OBJECTS_DIR}/{path}");
+            builder.append_path_with_name(root_dir.join(&path), name)?;
+        }
+
+        append_data(&mut builder, MANIFEST_ENTRY, &manifest_json)?;
+        builder.into_inner()?.finish()?;
+        Ok(())
+    })
+    .await??;
+
+    Ok(manifest)
+}
+
+/// Restore an archive created by [export] into an empty instance. Everything is verified before
+/// the instance is changed, and nothing is restored when any step fails.
+pub async fn import(
+    db: &Database,
+    root_dir: &Path,
+    secrets_file: &Path,
+    passphrase: &str,
+    archive: &Path,
+) -> anyhow::Result<Manifest> {
+    for table in ["clients", "users", "buckets", "assets"] {
+        let query = sql_safe!("SELECT COUNT(*) FROM {table}");
+        let count: i64 = sqlx::query_scalar(query).fetch_one(&**db).await?;
+        if count > 0 {
+            return Err(anyhow!(
+                "import requires an empty instance, but {table} has rows"
+            ));
+        }
+    }
+
+    let staging = root_dir
+        .join("tmp")
+        .join(format!("import-{}", generate_nano_id(16)));
+    tokio::fs::create_dir_all(&staging).await?;
+
+    let restored = restore(db, root_dir, secrets_file, passphrase, archive, &staging).await;
+    // staged files left behind are harmless, don't hide the import's outcome.
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+
+    restored
+}
+
+async fn restore(
+    db: &Database,
+    root_dir: &Path,
+    secrets_file: &Path,
+    passphrase: &str,
+    archive: &Path,
+    staging: &Path,
+) -> anyhow::Result<Manifest> {
+    let source = archive.to_path_buf();
+    let target = staging.to_path_buf();
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let file = File::open(source)?;
+        tar::Archive::new(GzDecoder::new(file)).unpack(target)?;
+        Ok(())
+    })
+    .await??;
+
+    let manifest = tokio::fs::read(staging.join(MANIFEST_ENTRY))
+        .await
+        .map_err(|_| anyhow!("archive has no manifest"))?;
+    let manifest: Manifest = serde_json::from_slice(&manifest)?;
+    if manifest.version != ARCHIVE_VERSION {
+        return Err(anyhow!("unsupported archive version {}", manifest.version));
+    }
+
+    let secrets = tokio::fs::read(staging.join(SECRETS_ENTRY)).await?;
+    let secrets = decrypt(&secrets, passphrase)?;
+
+    let mut tables = BTreeMap::new();
+    for table in TABLES {
+        let data = tokio::fs::read(staging.join(TABLES_DIR).join(format!("{table}.json"))).await?;
+        let rows: Vec<Map<String, Value>> = serde_json::from_slice(&data)?;
+        if manifest.tables.get(table) != Some(&rows.len()) {
+            return Err(anyhow!("table {table} doesn't match the manifest"));
+        }
+
+        tables.insert(table, rows);
+    }
+
+    verify_objects(&manifest, &tables, root_dir, &staging.join(OBJECTS_DIR)).await?;
+
+    let mut tx = db.begin().await?;
+    for table in TABLES {
+        for row in &tables[table] {
+            insert_row(db, &mut tx, table, row).await?;
+        }
+    }
+
+    if let DbEngine::Postgres = db.engine() {
+        for table in TABLES {
+            let query = sql_safe!(
+                "SELECT setval(pg_get_serial_sequence('{table}', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM {table}"
+            );
+            sqlx::query(query).execute(&mut *tx).await?;
+        }
+    }
+
+    let mut moved = vec![];
+    let mut restored = Ok(());
+    for path in manifest.objects.keys() {
+        let target = root_dir.join(path);
+        restored = move_path(&staging.join(OBJECTS_DIR).join(path), &target).await;
+        if restored.is_err() {
+            break;
+        }
+
+        moved.push(target);
+    }
+
+    // the secrets file is replaced before the rows are committed, and put back if they aren't,
+    // so that restored rows never end up without the secrets they were written with.
+    let previous = tokio::fs::read(secrets_file).await.ok();
+    if restored.is_ok() {
+        restored = write_staged(secrets_file, &secrets).await;
+    }
+
+    if restored.is_ok() {
+        restored = tx.commit().await.map_err(anyhow::Error::from);
+        if restored.is_err() {
+            let _ = match &previous {
+                Some(previous) => write_staged(secrets_file, previous).await,
+                None => tokio::fs::remove_file(secrets_file)
+                    .await
+                    .map_err(Into::into),
+            };
+        }
+    }
+
+    if let Err(err) = restored {
+        for path in moved {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+
+        return Err(err);
+    }
+
+    // search documents aren't exported, as they're derived from the restored rows.
+    search::backfill(db).await?;
+
+    Ok(manifest)
+}
+
+/// Write `contents` to `path` through a staged file, so it's never left partially written.
+async fn write_staged(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let staged = path.with_extension(format!("{}.tmp", generate_nano_id(8)));
+    tokio::fs::write(&staged, contents).await?;
+    tokio::fs::rename(&staged, path).await?;
+    Ok(())
+}
+
+/// Check that every object stays within the storage root and matches its digest, that every
+/// indexed file is part of the archive and that restoring objects won't overwrite anything.
+async fn verify_objects(
+    manifest: &Manifest,
+    tables: &BTreeMap<&str, Vec<Map<String, Value>>>,
+    root_dir: &Path,
+    objects_dir: &Path,
+) -> anyhow::Result<()> {
+    for (path, digest) in &manifest.objects {
+        if Path::new(path).is_absolute() || check_path(path).is_err() {
+            return Err(anyhow!("object path {path} is outside of the storage root"));
+        }
+
+        let staged = objects_dir.join(path);
+        if !staged.is_file() {
+            return Err(anyhow!("object {path} is missing from the archive"));
+        }
+
+        if crate::assets::checksum(&staged).await? != *digest {
+            return Err(anyhow!("object {path} is corrupted"));
+        }
+
+        if root_dir.join(path).exists() {
+            return Err(anyhow!("object {path} already exists"));
+        }
+    }
+
+    let file_type = Value::from(i16::from(AssetType::File));
+    for asset in tables["assets"]
+        .iter()
+        .filter(|a| a["asset_type"] == file_type)
+    {
+        let path = asset["path"].as_str().unwrap_or_default();
+        let digest = manifest
+            .objects
+            .get(path)
+            .ok_or(anyhow!("object {path} is missing from the archive"))?;
+
+        if let Some(checksum) = asset.get("checksum").and_then(Value::as_str)
+            && checksum != digest
+        {
+            return Err(anyhow!("object {path} doesn't match its recorded checksum"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Storage paths of indexed files, along with stored versions and trashed assets. Files in the
+/// cold volume aren't included: their rows still point at the same cold volume once imported.
+async fn object_paths(db: &Database, root_dir: &Path) -> anyhow::Result<Vec<String>> {
+    let query = sql_safe!(
+        "SELECT path FROM assets WHERE asset_type = {} AND storage_class = {} ORDER BY path",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    let mut paths: Vec<String> = sqlx::query_scalar(query)
+        .bind(i16::from(AssetType::File))
+        .bind(i16::from(StorageClass::Standard))
+        .fetch_all(&**db)
+        .await?;
+
+    if let Some(path) = paths.iter().find(|path| !root_dir.join(path).is_file()) {
+        return Err(anyhow!("indexed file {path} is missing from storage"));
+    }
+
+    for dir in [VERSIONS_DIR, TRASH_DIR] {
+        let mut files = vec![];
+        collect_files(root_dir, &root_dir.join(dir), &mut files).await?;
+        paths.extend(files);
+    }
+
+    Ok(paths)
+}
+
+async fn collect_files(root_dir: &Path, dir: &Path, files: &mut Vec<String>) -> anyhow::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let mut rd = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = rd.next_entry().await? {
+        let path = entry.path();
+        if path.is_dir() {
+            Box::pin(collect_files(root_dir, &path, files)).await?;
+        } else if let Ok(relative) = path.strip_prefix(root_dir) {
+            files.push(relative.to_string_lossy().to_string());
+        }
+    }
+
+    Ok(())
+}
+
+async fn dump_table(db: &Database, table: &str) -> anyhow::Result<Vec<Map<String, Value>>> {
+    let query = sql_safe!("SELECT * FROM {table} ORDER BY id");
+    let rows: Vec<AnyRow> = sqlx::query(query).fetch_all(&**db).await?;
+
+    rows.iter().map(row_to_json).collect()
+}
+
+fn row_to_json(row: &AnyRow) -> anyhow::Result<Map<String, Value>> {
+    let mut map = Map::new();
+    for (idx, column) in row.columns().iter().enumerate() {
+        let raw = row.try_get_raw(idx)?;
+        let value = if raw.is_null() {
+            Value::Null
+        } else {
+            match raw.type_info().kind() {
+                AnyTypeInfoKind::Bool => Value::from(row.try_get::<bool, _>(idx)?),
+                AnyTypeInfoKind::SmallInt | AnyTypeInfoKind::Integer | AnyTypeInfoKind::BigInt => {
+                    Value::from(row.try_get::<i64, _>(idx)?)
+                }
+                AnyTypeInfoKind::Real => Value::from(row.try_get::<f32, _>(idx)? as f64),
+                AnyTypeInfoKind::Double => Value::from(row.try_get::<f64, _>(idx)?),
+                AnyTypeInfoKind::Text => Value::from(row.try_get::<String, _>(idx)?),
+                kind => return Err(anyhow!("unsupported column type {kind:?}")),
+            }
+        };
+
+        map.insert(column.name().to_string(), value);
+    }
+
+    Ok(map)
+}
+
+async fn insert_row(
+    db: &Database,
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    table: &str,
+    row: &Map<String, Value>,
+) -> anyhow::Result<()> {
+    // NULL columns are left out, so their types don't have to be known.
+    let values: Vec<_> = row.iter().filter(|(_, value)| !value.is_null()).collect();
+    let columns: Vec<_> = values.iter().map(|(name, _)| quote(db, name)).collect();
+    let placeholders: Vec<_> = (1..=values.len())
+        .map(|idx| db.placeholder(idx as u8))
+        .collect();
+
+    let columns = columns.join(", ");
+    let placeholders = placeholders.join(", ");
+    let query = sql_safe!("INSERT INTO {table} ({columns}) VALUES ({placeholders})");
+
+    let mut query = sqlx::query(query);
+    for (name, value) in values {
+        query = match value {
+            Value::Bool(value) => query.bind(*value),
+            Value::Number(number) => bind_number(query, number),
+            Value::String(value) => query.bind(value.clone()),
+            _ => return Err(anyhow!("unsupported value for {table}.{name}")),
+        };
+    }
+
+    query.execute(&mut **tx).await?;
+    Ok(())
+}
+
+fn bind_number<'q>(
+    query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments>,
+    number: &Number,
+) -> sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments> {
+    match number.as_i64() {
+        Some(value) => query.bind(value),
+        None => query.bind(number.as_f64().unwrap_or_default()),
+    }
+}
+
+fn quote(db: &Database, name: &str) -> String {
+    match db.engine() {
+        DbEngine::Mysql => format!("`{name}`"),
+        _ => format!("\"{name}\""),
+    }
+}
+
+fn append_data<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    name: &str,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(unix_timestamp()? as u64);
+    header.set_cksum();
+
+    builder.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+/// Encrypt `data` with a key derived from `passphrase`. The salt and nonce are prepended to the
+/// ciphertext.
+fn encrypt(data: &[u8], passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    let salt = Key::generate();
+    let nonce = XNonce::generate();
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+
+    let mut output = Vec::from(salt.as_slice());
+    output.extend_from_slice(nonce.as_slice());
+    output.extend(cipher.encrypt(&nonce, data)?);
+
+    Ok(output)
+}
+
+fn decrypt(data: &[u8], passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    if data.len() < 32 + 24 {
+        return Err(anyhow!("encrypted secrets are truncated"));
+    }
+
+    let (salt, rest) = data.split_at(32);
+    let (nonce, ciphertext) = rest.split_at(24);
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+
+    cipher
+        .decrypt(&XNonce::try_from(nonce)?, ciphertext)
+        .map_err(|_| anyhow!("wrong passphrase or corrupted secrets"))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> anyhow::Result<Key> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| anyhow!("unable to derive key: {err}"))?;
+
+    Ok(Key::from(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive;
+    use crate::assets;
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::generate_nano_id;
+    use crate::secrets::AppSecrets;
+    use flate2::Compression;
+    use flate2::read::GzDecoder;
+    use flate2::write::GzEncoder;
+    use std::fs::File;
+
+    #[tokio::test]
+    async fn test_export_and_import() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("ppdrive-archive-{}", generate_nano_id(8)));
+        let (source_root, target_root) = (dir.join("source"), dir.join("target"));
+        tokio::fs::create_dir_all(&source_root).await?;
+        tokio::fs::create_dir_all(&target_root).await?;
+
+        let source = Database::new(&format!("sqlite:{}", dir.join("source.db").display())).await?;
+        let target = Database::new(&format!("sqlite:{}", dir.join("target.db").display())).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&source, &secrets, "Archive Test", None).await?;
+        let client_id = client::id_by_pid(&source, details.id()).await?;
+        let owner_id = client::owner_id(&source, client_id).await?;
+
+        let path = client::object_path(details.id(), "notes.txt");
+        tokio::fs::create_dir_all(client::namespace_dir(&source_root, details.id())).await?;
+        tokio::fs::write(source_root.join(&path), "notes").await?;
+        assets::index_file(&source, &source_root, &path, owner_id, None).await?;
+
+        let (source_secrets, target_secrets) = (dir.join("source.key"), dir.join("target.key"));
+        tokio::fs::write(&source_secrets, "secret").await?;
+
+        let output = dir.join("export.tar.gz");
+        let manifest =
+            archive::export(&source, &source_root, &source_secrets, "pass", &output).await?;
+        assert_eq!(manifest.tables["clients"], 1);
+
+        let wrong = archive::import(&target, &target_root, &target_secrets, "wrong", &output).await;
+        assert!(wrong.is_err());
+        assert!(!target_root.join(&path).exists());
+
+        archive::import(&target, &target_root, &target_secrets, "pass", &output).await?;
+        assert_eq!(
+            tokio::fs::read_to_string(target_root.join(&path)).await?,
+            "notes"
+        );
+        assert_eq!(tokio::fs::read_to_string(&target_secrets).await?, "secret");
+        assert!(assets::get(&target, &path).await?.is_some());
+        assert_eq!(client::id_by_pid(&target, details.id()).await?, client_id);
+
+        // The instance isn't empty anymore.
+        let again = archive::import(&target, &target_root, &target_secrets, "pass", &output).await;
+        assert!(again.is_err());
+
+        // Objects can't be restored outside of the storage root
+        let unpacked = dir.join("unpacked");
+        tar::Archive::new(GzDecoder::new(File::open(&output)?)).unpack(&unpacked)?;
+        let manifest_path = unpacked.join("manifest.json");
+        let mut manifest: archive::Manifest =
+            serde_json::from_slice(&tokio::fs::read(&manifest_path).await?)?;
+
+        let digest = assets::checksum(&unpacked.join("secrets.enc")).await?;
+        manifest
+            .objects
+            .insert("../secrets.enc".to_string(), digest);
+        tokio::fs::write(&manifest_path, serde_json::to_vec(&manifest)?).await?;
+
+        let crafted = dir.join("crafted.tar.gz");
+        let encoder = GzEncoder::new(File::create(&crafted)?, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        builder.append_dir_all(".", &unpacked)?;
+        builder.into_inner()?.finish()?;
+
+        let other = Database::new(&format!("sqlite:{}", dir.join("other.db").display())).await?;
+        let other_root = dir.join("other");
+        tokio::fs::create_dir_all(&other_root).await?;
+        let crafted = archive::import(
+            &other,
+            &other_root,
+            &dir.join("other.key"),
+            "pass",
+            &crafted,
+        )
+        .await;
+        assert!(crafted.is_err());
+        assert!(!dir.join("secrets.enc").exists());
+
+        tokio::fs::remove_dir_all(dir).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,930 @@
+use crate::acl::{self, Visibility, VisibilityOverride};
+use crate::db::{Database, DbEngine};
+use crate::lock::ObjectLock;
+use crate::utils::instance_as_string;
+use crate::{buckets, client, generate_nano_id, search, sql_safe, tags};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+pub enum AssetType {
+    #[default]
+    File,
+    Folder,
+}
+
+impl From<i16> for AssetType {
+    fn from(value: i16) -> Self {
+        use AssetType::*;
+
+        match value {
+            0 => File,
+            1 => Folder,
+            _ => Default::default(),
+        }
+    }
+}
+
+impl From<AssetType> for i16 {
+    fn from(value: AssetType) -> Self {
+        use AssetType::*;
+
+        match value {
+            File => 0,
+            Folder => 1,
+        }
+    }
+}
+
+/// Where a file's content is stored.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+pub enum StorageClass {
+    /// Under the storage root.
+    #[default]
+    Standard,
+    /// Under the configured `cold_volume`, at the same path relative to it as under the storage
+    /// root. See [crate::lifecycle::LifecycleAction::Transition].
+    Cold,
+}
+
+impl From<i16> for StorageClass {
+    fn from(value: i16) -> Self {
+        use StorageClass::*;
+
+        match value {
+            0 => Standard,
+            1 => Cold,
+            _ => Default::default(),
+        }
+    }
+}
+
+impl From<StorageClass> for i16 {
+    fn from(value: StorageClass) -> Self {
+        use StorageClass::*;
+
+        match value {
+            Standard => 0,
+            Cold => 1,
+        }
+    }
+}
+
+/// Prefix of request and response headers carrying user-defined metadata.
+pub const METADATA_HEADER_PREFIX: &str = "x-ppdrive-meta-";
+pub const MAX_METADATA_KEY_LEN: usize = 128;
+pub const MAX_METADATA_VALUE_LEN: usize = 1024;
+/// Maximum combined size (in bytes) of every key and value of an asset's metadata.
+pub const MAX_METADATA_SIZE: usize = 8 * 1024;
+
+/// Folder, under the storage root, files derived from assets (e.g. converted images) are cached
+/// in, one folder per asset.
+pub const VARIANTS_DIR: &str = ".cache/variants";
+
+/// User-defined key/value pairs attached to an asset. Keys are stored lowercase and without
+/// [METADATA_HEADER_PREFIX].
+#[derive(Serialize, Default, Clone, PartialEq, Debug)]
+#[serde(transparent)]
+pub struct Metadata(BTreeMap<String, String>);
+
+impl<'de> Deserialize<'de> for Metadata {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = BTreeMap::<String, String>::deserialize(deserializer)?;
+        let mut metadata = Metadata::new();
+        for (key, value) in entries {
+            metadata.insert(&key, value);
+        }
+
+        Ok(metadata)
+    }
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an entry. `key` may include [METADATA_HEADER_PREFIX].
+    pub fn insert(&mut self, key: &str, value: impl Into<String>) {
+        self.0.insert(normalize_metadata_key(key), value.into());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(&normalize_metadata_key(key)).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter()
+    }
+
+    /// Merge `other` into this metadata, overriding existing keys.
+    pub fn extend(&mut self, other: Metadata) {
+        self.0.extend(other.0);
+    }
+
+    /// Check metadata against size limits. Keys may only contain alphanumerics, `-` and `_`, and
+    /// values must be printable ASCII so both can be sent as HTTP headers.
+    pub fn check(&self) -> anyhow::Result<()> {
+        let mut total = 0;
+        for (key, value) in self.iter() {
+            if key.is_empty() || key.len() > MAX_METADATA_KEY_LEN {
+                return Err(anyhow!(
+                    "metadata keys must be between 1 and {MAX_METADATA_KEY_LEN} characters"
+                ));
+            }
+
+            if !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(anyhow!("invalid metadata key \"{key}\""));
+            }
+
+            if value.len() > MAX_METADATA_VALUE_LEN {
+                return Err(anyhow!(
+                    "metadata \"{key}\" exceeds {MAX_METADATA_VALUE_LEN} characters"
+                ));
+            }
+
+            if !value.chars().all(|c| c == ' ' || c.is_ascii_graphic()) {
+                return Err(anyhow!("metadata \"{key}\" must be printable ASCII"));
+            }
+
+            total += key.len() + value.len();
+        }
+
+        if total > MAX_METADATA_SIZE {
+            return Err(anyhow!("metadata exceeds {MAX_METADATA_SIZE} bytes"));
+        }
+
+        Ok(())
+    }
+}
+
+impl TryFrom<Option<String>> for Metadata {
+    type Error = serde_json::Error;
+
+    fn try_from(value: Option<String>) -> Result<Self, Self::Error> {
+        match value {
+            Some(value) => serde_json::from_str(&value),
+            None => Ok(Default::default()),
+        }
+    }
+}
+
+fn normalize_metadata_key(key: &str) -> String {
+    let key = key.to_lowercase();
+    key.strip_prefix(METADATA_HEADER_PREFIX)
+        .map(str::to_string)
+        .unwrap_or(key)
+}
+
+/// An indexed file or folder. `path` is relative to the storage root.
+#[derive(FromRow, Serialize, Clone)]
+pub struct Asset {
+    #[serde(skip)]
+    id: i32,
+    pid: String,
+    path: String,
+    #[sqlx(try_from = "i16")]
+    asset_type: AssetType,
+    size: i64,
+    content_type: Option<String>,
+    checksum: Option<String>,
+    #[serde(skip)]
+    owner_id: i32,
+    #[serde(skip)]
+    bucket_id: Option<i32>,
+    #[sqlx(try_from = "Option<String>")]
+    metadata: Metadata,
+    #[sqlx(try_from = "Option<i16>")]
+    visibility: VisibilityOverride,
+    #[sqlx(flatten)]
+    lock: ObjectLock,
+    #[sqlx(try_from = "i16")]
+    storage_class: StorageClass,
+    created_at: String,
+    updated_at: String,
+}
+
+impl Asset {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Location of the asset's content, which is under `cold_volume` for
+    /// [StorageClass::Cold] files.
+    pub fn stored_path(
+        &self,
+        root_dir: &Path,
+        cold_volume: Option<&Path>,
+    ) -> anyhow::Result<PathBuf> {
+        match self.storage_class {
+            StorageClass::Standard => Ok(root_dir.join(&self.path)),
+            StorageClass::Cold => {
+                let cold_volume = cold_volume.ok_or(anyhow!(
+                    "{} is in a cold_volume that isn't configured",
+                    self.path
+                ))?;
+                Ok(cold_volume.join(&self.path))
+            }
+        }
+    }
+
+    /// Folder the asset's derived files are cached in.
+    pub fn variants_dir(&self, root_dir: &Path) -> PathBuf {
+        root_dir.join(VARIANTS_DIR).join(&self.pid)
+    }
+
+    pub fn asset_type(&self) -> AssetType {
+        self.asset_type
+    }
+
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Hex encoded blake3 digest of the file's content.
+    pub fn checksum(&self) -> Option<&str> {
+        self.checksum.as_deref()
+    }
+
+    pub fn owner_id(&self) -> i32 {
+        self.owner_id
+    }
+
+    pub fn bucket_id(&self) -> Option<i32> {
+        self.bucket_id
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Retention and legal hold of the asset.
+    pub fn lock(&self) -> &ObjectLock {
+        &self.lock
+    }
+
+    pub fn storage_class(&self) -> StorageClass {
+        self.storage_class
+    }
+
+    /// Visibility set on the asset itself, if any. See [crate::acl::effective].
+    pub fn visibility(&self) -> Option<Visibility> {
+        self.visibility.get()
+    }
+
+    pub fn created_at(&self) -> &str {
+        &self.created_at
+    }
+
+    pub fn updated_at(&self) -> &str {
+        &self.updated_at
+    }
+}
+
+pub struct AssetRecord {
+    pub path: String,
+    pub asset_type: AssetType,
+    pub size: i64,
+    pub content_type: Option<String>,
+    pub checksum: Option<String>,
+    pub owner_id: i32,
+    pub bucket_id: Option<i32>,
+}
+
+/// Insert an asset into the index, or update it if the path is already indexed.
+pub async fn upsert(db: &Database, record: AssetRecord) -> anyhow::Result<Asset> {
+    let AssetRecord {
+        path,
+        asset_type,
+        size,
+        content_type,
+        checksum,
+        owner_id,
+        bucket_id,
+    } = record;
+
+    let path = normalize(&path);
+    let now = instance_as_string()?;
+
+    // a single statement, so concurrent writers of a path don't race to insert it.
+    let updated = [
+        "asset_type",
+        "size",
+        "content_type",
+        "checksum",
+        "owner_id",
+        "bucket_id",
+        "storage_class",
+        "updated_at",
+    ];
+    let on_conflict = match db.engine() {
+        DbEngine::Mysql => {
+            let updates: Vec<_> = updated
+                .iter()
+                .map(|column| format!("{column} = VALUES({column})"))
+                .collect();
+            format!("ON DUPLICATE KEY UPDATE {}", updates.join(", "))
+        }
+        _ => {
+            let updates: Vec<_> = updated
+                .iter()
+                .map(|column| format!("{column} = excluded.{column}"))
+                .collect();
+            format!("ON CONFLICT (path) DO UPDATE SET {}", updates.join(", "))
+        }
+    };
+
+    let mut placeholders = Vec::with_capacity(11);
+    for idx in 1..12 {
+        placeholders.push(db.placeholder(idx))
+    }
+
+    let placeholders = placeholders.join(",");
+    let query = sql_safe!(
+        "INSERT INTO assets (pid, path, asset_type, size, content_type, checksum, owner_id, bucket_id, storage_class, created_at, updated_at) VALUES ({placeholders}) {on_conflict}"
+    );
+
+    sqlx::query(query)
+        .bind(generate_nano_id(32))
+        .bind(&path)
+        .bind(i16::from(asset_type))
+        .bind(size)
+        .bind(content_type)
+        .bind(checksum)
+        .bind(owner_id)
+        .bind(bucket_id)
+        .bind(i16::from(StorageClass::Standard))
+        .bind(&now)
+        .bind(&now)
+        .execute(&**db)
+        .await?;
+
+    let asset = get(db, &path)
+        .await?
+        .ok_or(anyhow!("unable to index {path}"))?;
+    search::index(db, asset.id).await?;
+
+    Ok(asset)
+}
+
+/// Index a file stored at `root_dir/path`, computing its size, content type and checksum. Files
+/// derived from a previous content are dropped.
+pub async fn index_file(
+    db: &Database,
+    root_dir: &Path,
+    path: &str,
+    owner_id: i32,
+    bucket_id: Option<i32>,
+) -> anyhow::Result<Asset> {
+    let path = normalize(path);
+    let file = root_dir.join(&path);
+
+    let size = tokio::fs::metadata(&file).await?.len() as i64;
+    let checksum = checksum(&file).await?;
+    let content_type = guess_content_type(&path);
+
+    let record = AssetRecord {
+        path,
+        asset_type: AssetType::File,
+        size,
+        content_type,
+        checksum: Some(checksum),
+        owner_id,
+        bucket_id,
+    };
+
+    let previous = get(db, &record.path).await?;
+    let asset = upsert(db, record).await?;
+    if let Some(previous) = previous
+        && previous.checksum() != asset.checksum()
+    {
+        let variants = asset.variants_dir(root_dir);
+        if variants.is_dir() {
+            tokio::fs::remove_dir_all(variants).await?;
+        }
+    }
+
+    Ok(asset)
+}
+
+pub async fn index_folder(
+    db: &Database,
+    path: &str,
+    owner_id: i32,
+    bucket_id: Option<i32>,
+) -> anyhow::Result<Asset> {
+    let record = AssetRecord {
+        path: normalize(path),
+        asset_type: AssetType::Folder,
+        size: 0,
+        content_type: None,
+        checksum: None,
+        owner_id,
+        bucket_id,
+    };
+
+    upsert(db, record).await
+}
+
+/// Index the ancestors of `path` which are not indexed yet.
+pub async fn index_parents(
+    db: &Database,
+    path: &str,
+    owner_id: i32,
+    bucket_id: Option<i32>,
+) -> anyhow::Result<()> {
+    let path = normalize(path);
+    let mut parent = Path::new(&path).parent();
+
+    while let Some(dir) = parent
+        && !dir.as_os_str().is_empty()
+    {
+        let dir_path = dir.to_string_lossy();
+        if buckets::is_namespace(&dir_path) || client::is_namespace(&dir_path) {
+            break;
+        }
+
+        if get(db, &dir_path).await?.is_none() {
+            index_folder(db, &dir_path, owner_id, bucket_id).await?;
+        }
+
+        parent = dir.parent();
+    }
+
+    Ok(())
+}
+
+/// Index a file or folder and, for folders, everything inside it.
+pub async fn index_tree(
+    db: &Database,
+    root_dir: &Path,
+    path: &str,
+    owner_id: i32,
+    bucket_id: Option<i32>,
+) -> anyhow::Result<()> {
+    let path = normalize(path);
+    let target = root_dir.join(&path);
+
+    if target.is_file() {
+        index_file(db, root_dir, &path, owner_id, bucket_id).await?;
+        return Ok(());
+    }
+
+    index_folder(db, &path, owner_id, bucket_id).await?;
+    let mut rd = tokio::fs::read_dir(&target).await?;
+    while let Ok(Some(entry)) = rd.next_entry().await {
+        let child = format!("{path}/{}", entry.file_name().to_string_lossy());
+        Box::pin(index_tree(db, root_dir, &child, owner_id, bucket_id)).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn get(db: &Database, path: &str) -> anyhow::Result<Option<Asset>> {
+    let query = sql_safe!(
+        "SELECT * FROM assets WHERE path = {} LIMIT 1",
+        db.placeholder(1)
+    );
+
+    let asset = sqlx::query_as(query)
+        .bind(normalize(path))
+        .fetch_optional(&**db)
+        .await?;
+
+    Ok(asset)
+}
+
+/// Replace the metadata of an indexed asset.
+pub async fn set_metadata(db: &Database, path: &str, metadata: &Metadata) -> anyhow::Result<Asset> {
+    metadata.check()?;
+
+    let path = normalize(path);
+    let query = sql_safe!(
+        "UPDATE assets SET metadata = {}, updated_at = {} WHERE path = {}",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3)
+    );
+
+    sqlx::query(query)
+        .bind(serde_json::to_string(metadata)?)
+        .bind(instance_as_string()?)
+        .bind(&path)
+        .execute(&**db)
+        .await?;
+
+    let asset = get(db, &path)
+        .await?
+        .ok_or(anyhow!("asset {path} is not indexed"))?;
+    search::index(db, asset.id).await?;
+
+    Ok(asset)
+}
+
+/// Update the index after `from` was moved to `to`. Folders carry their descendants along.
+pub async fn relocate(
+    db: &Database,
+    from: &str,
+    to: &str,
+    bucket_id: Option<i32>,
+) -> anyhow::Result<()> {
+    let from = normalize(from);
+    let to = normalize(to);
+    let now = instance_as_string()?;
+
+    let mut moved = descendants(db, &from).await?;
+    if let Some(asset) = get(db, &from).await? {
+        moved.push(asset);
+    }
+
+    for asset in moved {
+        let path = format!("{to}{}", &asset.path[from.len()..]);
+        let query = sql_safe!(
+            "UPDATE assets SET path = {}, bucket_id = {}, updated_at = {} WHERE id = {}",
+            db.placeholder(1),
+            db.placeholder(2),
+            db.placeholder(3),
+            db.placeholder(4)
+        );
+
+        sqlx::query(query)
+            .bind(path)
+            .bind(bucket_id)
+            .bind(&now)
+            .bind(asset.id)
+            .execute(&**db)
+            .await?;
+
+        search::index(db, asset.id).await?;
+    }
+
+    Ok(())
+}
+
+/// Copy metadata and tags of `from`, and of its descendants if it's a folder, to their
+/// counterparts under `to`. The copies must already be indexed.
+pub async fn copy_attributes(db: &Database, from: &str, to: &str) -> anyhow::Result<()> {
+    let from = normalize(from);
+    let to = normalize(to);
+
+    let mut sources = descendants(db, &from).await?;
+    if let Some(asset) = get(db, &from).await? {
+        sources.push(asset);
+    }
+
+    for source in sources {
+        let path = format!("{to}{}", &source.path[from.len()..]);
+        let copy = match get(db, &path).await? {
+            Some(copy) => copy,
+            None => continue,
+        };
+
+        if !source.metadata.is_empty() {
+            set_metadata(db, &path, &source.metadata).await?;
+        }
+
+        let tags = tags::get(db, source.id).await?;
+        if !tags.is_empty() {
+            tags::put(db, copy.id, &tags).await?;
+        }
+
+        if let Some(visibility) = source.visibility() {
+            acl::set(db, &path, Some(visibility)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove an asset, and everything under it if it's a folder, from the index. Search documents
+/// and the files derived from them go along with them.
+pub async fn remove(db: &Database, root_dir: &Path, path: &str) -> anyhow::Result<()> {
+    let path = normalize(path);
+    let query = sql_safe!(
+        "SELECT pid FROM assets WHERE path = {} OR path LIKE {} ESCAPE '!'",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    let pids: Vec<String> = sqlx::query_scalar(query)
+        .bind(&path)
+        .bind(descendants_pattern(&path))
+        .fetch_all(&**db)
+        .await?;
+
+    let query = sql_safe!(
+        "DELETE FROM assets WHERE path = {} OR path LIKE {} ESCAPE '!'",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    sqlx::query(query)
+        .bind(&path)
+        .bind(descendants_pattern(&path))
+        .execute(&**db)
+        .await?;
+
+    remove_variants(root_dir, &pids).await
+}
+
+/// Remove the files derived from assets, such as transformed images and precompressed copies.
+/// Nothing can refer to them once their assets leave the index.
+pub async fn remove_variants(root_dir: &Path, pids: &[String]) -> anyhow::Result<()> {
+    for pid in pids {
+        let variants = root_dir.join(VARIANTS_DIR).join(pid);
+        if variants.is_dir() {
+            tokio::fs::remove_dir_all(variants).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Record where the content of the file at `path` is stored.
+pub async fn set_storage_class(
+    db: &Database,
+    path: &str,
+    storage_class: StorageClass,
+) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "UPDATE assets SET storage_class = {} WHERE path = {}",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    sqlx::query(query)
+        .bind(i16::from(storage_class))
+        .bind(normalize(path))
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+/// Files at or under `path` stored in the cold volume.
+pub async fn cold_files(db: &Database, path: &str) -> anyhow::Result<Vec<Asset>> {
+    let path = normalize(path);
+    let query = sql_safe!(
+        "SELECT * FROM assets WHERE storage_class = {} AND (path = {} OR path LIKE {} ESCAPE '!') ORDER BY path",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3)
+    );
+
+    let assets = sqlx::query_as(query)
+        .bind(i16::from(StorageClass::Cold))
+        .bind(&path)
+        .bind(descendants_pattern(&path))
+        .fetch_all(&**db)
+        .await?;
+
+    Ok(assets)
+}
+
+/// List assets under a folder (`prefix`), recursively.
+pub async fn descendants(db: &Database, prefix: &str) -> anyhow::Result<Vec<Asset>> {
+    let query = sql_safe!(
+        "SELECT * FROM assets WHERE path LIKE {} ESCAPE '!' ORDER BY path",
+        db.placeholder(1)
+    );
+
+    let assets = sqlx::query_as(query)
+        .bind(descendants_pattern(&normalize(prefix)))
+        .fetch_all(&**db)
+        .await?;
+
+    Ok(assets)
+}
+
+/// Total size (in bytes) of files indexed for an owner.
+pub async fn usage(db: &Database, owner_id: i32) -> anyhow::Result<i64> {
+    let query = sql_safe!(
+        "SELECT COALESCE(SUM(size), 0) FROM assets WHERE owner_id = {}",
+        db.placeholder(1)
+    );
+
+    let size = sqlx::query_scalar(query)
+        .bind(owner_id)
+        .fetch_one(&**db)
+        .await?;
+    Ok(size)
+}
+
+/// Compute the hex encoded blake3 digest of a file.
+pub async fn checksum(path: &Path) -> anyhow::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Make sure a client provided path stays within the storage root.
+pub fn check_path(path: &str) -> anyhow::Result<()> {
+    let invalid = Path::new(&normalize(path))
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)));
+
+    if path.trim_matches('/').is_empty() || invalid {
+        return Err(anyhow!("invalid path \"{path}\""));
+    }
+
+    Ok(())
+}
+
+/// Content type of a file, guessed from its extension.
+pub fn guess_content_type(path: &str) -> Option<String> {
+    mime_guess::from_path(path)
+        .first()
+        .map(|mime| mime.to_string())
+}
+
+/// Index paths never start or end with a separator.
+pub fn normalize(path: &str) -> String {
+    path.trim_matches('/').to_string()
+}
+
+/// Path of an asset within its client or bucket namespace, as clients refer to it.
+pub fn relative_path(path: &str) -> &str {
+    let path = path.trim_matches('/');
+    match path.split_once('/') {
+        Some((client::CLIENTS_DIR | buckets::BUCKETS_DIR, rest)) => {
+            rest.split_once('/').map_or("", |(_, rest)| rest)
+        }
+        _ => path,
+    }
+}
+
+/// Escape `LIKE` wildcards in `value`. Patterns must use `ESCAPE '!'`.
+pub(crate) fn like_escape(value: &str) -> String {
+    value
+        .replace('!', "!!")
+        .replace('%', "!%")
+        .replace('_', "!_")
+}
+
+/// `LIKE` pattern matching everything under `path`.
+fn descendants_pattern(path: &str) -> String {
+    let escaped = like_escape(path);
+    if escaped.is_empty() {
+        "%".to_string()
+    } else {
+        format!("{escaped}/%")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets::{self, AssetType, MAX_METADATA_VALUE_LEN, Metadata};
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::secrets::AppSecrets;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_index_and_remove() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Assets Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let root_dir = crate::root_dir()?.join("test-assets/index");
+        let folder = format!("{}/docs", details.id());
+        let path = format!("{folder}/notes.txt");
+        tokio::fs::create_dir_all(root_dir.join(&folder)).await?;
+        tokio::fs::write(root_dir.join(&path), "indexed").await?;
+
+        assets::index_parents(&db, &path, owner_id, None).await?;
+        let asset = assets::index_file(&db, &root_dir, &path, owner_id, None).await?;
+        assert_eq!(asset.size(), 7);
+        assert_eq!(asset.content_type(), Some("text/plain"));
+        assert!(asset.checksum().is_some());
+
+        let parent = assets::get(&db, &folder).await?.expect("parent is indexed");
+        assert_eq!(parent.asset_type(), AssetType::Folder);
+        assert_eq!(assets::usage(&db, owner_id).await?, 7);
+
+        // derived files go along with the asset
+        let variants = asset.variants_dir(&root_dir);
+        tokio::fs::create_dir_all(&variants).await?;
+        tokio::fs::write(variants.join("notes.txt.gz"), "compressed").await?;
+
+        assets::remove(&db, &root_dir, details.id()).await?;
+        assert!(!variants.exists());
+        assert!(assets::get(&db, &path).await?.is_none());
+        assert!(assets::descendants(&db, details.id()).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_upserts() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Upsert Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let record = move |path: String, size: i64| assets::AssetRecord {
+            path,
+            asset_type: AssetType::File,
+            size,
+            content_type: None,
+            checksum: None,
+            owner_id,
+            bucket_id: None,
+        };
+
+        let path = format!("{}/a.txt", details.id());
+        let mut writers = Vec::new();
+        for size in 1..=8 {
+            let (db, path) = (db.clone(), path.clone());
+            writers.push(tokio::spawn(async move {
+                assets::upsert(&db, record(path, size)).await
+            }));
+        }
+
+        for writer in writers {
+            writer.await??;
+        }
+
+        // paths differing only by case are different assets
+        assets::upsert(&db, record(format!("{}/A.txt", details.id()), 100)).await?;
+        let lower = assets::get(&db, &path).await?;
+        assert!(lower.is_some_and(|asset| asset.size() <= 8));
+        assert_eq!(assets::descendants(&db, details.id()).await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_metadata() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Metadata Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let path = format!("{}/reports", details.id());
+        assets::index_folder(&db, &path, owner_id, None).await?;
+
+        let mut metadata = Metadata::new();
+        metadata.insert("X-PPDRIVE-META-Author", "Jane Doe");
+        metadata.insert("original-filename", "report.pdf");
+
+        let asset = assets::set_metadata(&db, &path, &metadata).await?;
+        assert_eq!(asset.metadata().get("author"), Some("Jane Doe"));
+        assert_eq!(asset.metadata(), &metadata);
+
+        metadata.insert("summary", "x".repeat(MAX_METADATA_VALUE_LEN + 1));
+        assert!(assets::set_metadata(&db, &path, &metadata).await.is_err());
+
+        let mut metadata = Metadata::new();
+        metadata.insert("bad key", "value");
+        assert!(metadata.check().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use crate::db::Database;
+use crate::sql_safe;
+use crate::utils::instance_as_string;
+use serde::Serialize;
+use sqlx::FromRow;
+
+/// An entry in an owner's audit trail.
+#[derive(FromRow, Serialize)]
+pub struct AuditEntry {
+    #[serde(skip)]
+    id: i32,
+    #[serde(skip)]
+    owner_id: i32,
+    action: String,
+    path: String,
+    detail: Option<String>,
+    created_at: String,
+}
+
+impl AuditEntry {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn owner_id(&self) -> i32 {
+        self.owner_id
+    }
+
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+}
+
+/// Append an entry to an owner's audit trail.
+pub async fn record(
+    db: &Database,
+    owner_id: i32,
+    action: &str,
+    path: &str,
+    detail: Option<String>,
+) -> anyhow::Result<()> {
+    let now = instance_as_string()?;
+    let mut placeholders = Vec::with_capacity(5);
+    for idx in 1..6 {
+        placeholders.push(db.placeholder(idx))
+    }
+
+    let placeholders = placeholders.join(",");
+    let query = sql_safe!(
+        "INSERT INTO audit_log (owner_id, action, path, detail, created_at) VALUES ({placeholders})"
+    );
+
+    sqlx::query(query)
+        .bind(owner_id)
+        .bind(action)
+        .bind(path)
+        .bind(detail)
+        .bind(now)
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+/// List an owner's audit trail, newest first.
+pub async fn list(db: &Database, owner_id: i32, limit: i64) -> anyhow::Result<Vec<AuditEntry>> {
+    let query = sql_safe!(
+        "SELECT * FROM audit_log WHERE owner_id = {} ORDER BY id DESC LIMIT {}",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    let entries = sqlx::query_as(query)
+        .bind(owner_id)
+        .bind(limit)
+        .fetch_all(&**db)
+        .await?;
+
+    Ok(entries)
+}
@@ -1,11 +1,17 @@
+use crate::events::ObjectEvent;
+use crate::server::UploadInfo;
 use anyhow::anyhow;
+use redis::aio::PubSubStream;
 use redis::{AsyncCommands, Value};
-use crate::server::UploadInfo;
 
 type RedisConnection = redis::aio::MultiplexedConnection;
 
+/// Channel object change events are published on.
+const EVENTS_CHANNEL: &str = "ppdrive:events";
+
 #[derive(Clone)]
 pub struct MessageBroker {
+    client: redis::Client,
     conn: RedisConnection
 }
 
@@ -13,30 +19,177 @@ impl MessageBroker {
     pub async fn new(url: &str) -> anyhow::Result<MessageBroker> {
         let client = redis::Client::open(url)?;
         let conn = client.get_multiplexed_async_connection().await?;
-        
-        Ok(Self { conn })
+
+        Ok(Self { client, conn })
     }
-    
+
     fn conn(&self) -> RedisConnection {
         self.conn.clone()
     }
-    
+
     pub async fn get_upload_info(&self, session_id: &str) -> anyhow::Result<UploadInfo> {
-        let data = self.conn().get::<_, String>(session_id).await.map_err(|e| anyhow!("{e}"))?;
+        let data = self
+            .conn()
+            .get::<_, String>(session_id)
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
         let info = serde_json::from_str(&data)?;
-        
+
         Ok(info)
     }
-    
-    pub async fn upsert_upload_info(&self, session_id: &str, info: &UploadInfo) -> anyhow::Result<()> {
+
+    pub async fn upsert_upload_info(
+        &self,
+        session_id: &str,
+        info: &UploadInfo,
+    ) -> anyhow::Result<()> {
         let data = serde_json::to_string(info)?;
-        self.conn().set_ex::<_, String, Value>(session_id, data, info.exp as u64).await.map_err(|e| anyhow!("{e}"))?;
-        
+        // `exp` is a unix timestamp, not a time to live.
+        self.conn()
+            .set::<_, String, Value>(session_id, data)
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+        self.conn()
+            .expire_at::<_, Value>(session_id, info.exp)
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+
+        if let Some(config) = &info.config {
+            let key = upload_path_key(&config.path);
+            self.conn()
+                .sadd::<_, _, Value>(&key, session_id)
+                .await
+                .map_err(|e| anyhow!("{e}"))?;
+            self.conn()
+                .expire_at::<_, Value>(&key, info.exp)
+                .await
+                .map_err(|e| anyhow!("{e}"))?;
+        }
+
         Ok(())
     }
-    
+
+    /// Cancel in-flight uploads to `path` and, when `recursive`, to paths under it. Returns ids of
+    /// the cancelled sessions.
+    pub async fn cancel_uploads(&self, path: &str, recursive: bool) -> anyhow::Result<Vec<String>> {
+        let mut keys = vec![upload_path_key(path)];
+        if recursive {
+            let pattern = format!("{}/*", glob_escape(&upload_path_key(path)));
+            let mut conn = self.conn();
+            let mut iter = conn
+                .scan_match::<_, String>(pattern)
+                .await
+                .map_err(|e| anyhow!("{e}"))?;
+
+            while let Some(key) = iter.next_item().await {
+                keys.push(key.map_err(|e| anyhow!("{e}"))?);
+            }
+        }
+
+        let mut sessions = vec![];
+        for key in keys {
+            let ids: Vec<String> = self
+                .conn()
+                .smembers(&key)
+                .await
+                .map_err(|e| anyhow!("{e}"))?;
+            for id in ids {
+                self.remove_upload_info(&id).await?;
+                sessions.push(id);
+            }
+
+            self.conn()
+                .del::<_, Value>(&key)
+                .await
+                .map_err(|e| anyhow!("{e}"))?;
+        }
+
+        Ok(sessions)
+    }
+
+    /// Ids of in-flight upload sessions targeting `path`.
+    pub async fn uploads_to(&self, path: &str) -> anyhow::Result<Vec<String>> {
+        let ids = self
+            .conn()
+            .smembers(upload_path_key(path))
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+        Ok(ids)
+    }
+
+    /// Remove an upload session, along with its entry in the set of uploads to its path.
     pub async fn remove_upload_info(&self, session_id: &str) -> anyhow::Result<()> {
-        self.conn().del::<_, String>(session_id).await.map_err(|e| anyhow!("{e}"))?;
+        if let Ok(UploadInfo {
+            config: Some(config),
+            ..
+        }) = self.get_upload_info(session_id).await
+        {
+            let key = upload_path_key(&config.path);
+            self.conn()
+                .srem::<_, _, Value>(&key, session_id)
+                .await
+                .map_err(|e| anyhow!("{e}"))?;
+        }
+
+        self.conn()
+            .del::<_, String>(session_id)
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Publish an object change event to every server instance.
+    pub async fn publish_event(&self, event: &ObjectEvent) -> anyhow::Result<()> {
+        let data = serde_json::to_string(event)?;
+        self.conn()
+            .publish::<_, _, Value>(EVENTS_CHANNEL, data)
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+        Ok(())
+    }
+
+    /// Subscribe to object change events published by every server instance.
+    pub async fn subscribe_events(&self) -> anyhow::Result<PubSubStream> {
+        let mut pubsub = self
+            .client
+            .get_async_pubsub()
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+        pubsub
+            .subscribe(EVENTS_CHANNEL)
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+        Ok(pubsub.into_on_message())
+    }
+}
+
+/// Key of the set holding ids of upload sessions targeting `path`.
+fn upload_path_key(path: &str) -> String {
+    format!("upload-path:{}", path.trim_matches('/'))
+}
+
+/// Escape the characters `SCAN MATCH` patterns give a meaning to.
+fn glob_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::broker::glob_escape;
+
+    #[test]
+    fn test_glob_escape() {
+        assert_eq!(glob_escape("upload-path:docs"), "upload-path:docs");
+        assert_eq!(glob_escape("x*"), "x\\*");
+        assert_eq!(glob_escape("a?[b]\\c"), "a\\?\\[b\\]\\\\c");
+    }
+}
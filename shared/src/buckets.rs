@@ -1,12 +1,173 @@
+use crate::acl::Visibility;
 use crate::db::Database;
-use crate::{generate_nano_id, sql_safe};
-use crate::utils::{asset_owner_id, instance_as_string, AssetOwnerName};
+use crate::lock::{self, DefaultRetention, RetentionMode};
+use crate::tags::TagFilter;
+use crate::utils::{DbBool, asset_owner_id, instance_as_string};
+use crate::{assets, generate_nano_id, quota, sql_safe, versions};
+use anyhow::anyhow;
+use serde::Serialize;
+use sqlx::FromRow;
+use std::path::{Path, PathBuf};
+
+pub use crate::utils::AssetOwnerName;
+
+/// Folder (relative to storage root) holding bucket namespaces.
+pub const BUCKETS_DIR: &str = "buckets";
 
 pub struct CreateBucketData {
+    /// Maximum size (in bytes) of the bucket's content.
+    pub size: Option<i64>,
+    /// Content types the bucket accepts, e.g. `image/*` or `application/pdf`. Any content is
+    /// accepted when empty.
+    pub accepts: Option<Vec<String>>,
+    pub owner_type: AssetOwnerName,
+    pub owner_id: i32,
+    pub versioning: bool,
+    /// Default visibility of the bucket's objects.
+    pub visibility: Visibility,
+    /// Objects matching this tag filter are public, unless they set their own visibility.
+    pub public_tag: Option<TagFilter>,
+    /// Retention applied to files written to the bucket.
+    pub retention: Option<DefaultRetention>,
+    /// Write brotli and gzip copies of compressible files when they're uploaded.
+    pub precompress: bool,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct Bucket {
+    #[serde(skip)]
+    id: i32,
+    pid: String,
     size: Option<i64>,
-    accepts: Option<Vec<&'static str>>,
-    owner_type: AssetOwnerName,
+    accepts: Option<String>,
+    created_at: String,
+    #[serde(skip)]
     owner_id: i32,
+    #[sqlx(try_from = "i16")]
+    versioning: DbBool,
+    #[sqlx(try_from = "i16")]
+    visibility: Visibility,
+    public_tag: Option<String>,
+    #[serde(skip)]
+    lock_mode: Option<i16>,
+    #[serde(skip)]
+    lock_days: Option<i32>,
+    #[sqlx(try_from = "i16")]
+    precompress: DbBool,
+}
+
+impl Bucket {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn size(&self) -> Option<i64> {
+        self.size
+    }
+
+    pub fn accepts(&self) -> Option<&str> {
+        self.accepts.as_deref()
+    }
+
+    pub fn created_at(&self) -> &str {
+        &self.created_at
+    }
+
+    pub fn owner_id(&self) -> i32 {
+        self.owner_id
+    }
+
+    /// When enabled, overwritten and deleted objects are kept as numbered versions.
+    pub fn versioning(&self) -> bool {
+        self.versioning.get()
+    }
+
+    /// Default visibility of the bucket's objects.
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    /// Tag filter (`key=value` or `key`) making matching objects public.
+    pub fn public_tag(&self) -> Option<&str> {
+        self.public_tag.as_deref()
+    }
+
+    /// Retention applied to files written to the bucket.
+    pub fn default_retention(&self) -> Option<DefaultRetention> {
+        match (self.lock_mode, self.lock_days) {
+            (Some(mode), Some(days)) => Some(DefaultRetention {
+                mode: RetentionMode::from(mode),
+                days: days as u32,
+            }),
+            _ => None,
+        }
+    }
+
+    /// When enabled, brotli and gzip copies of compressible files are written on upload.
+    pub fn precompress(&self) -> bool {
+        self.precompress.get()
+    }
+
+    /// Check whether the bucket accepts content of the given type.
+    pub fn accepts_type(&self, content_type: Option<&str>) -> bool {
+        let accepts = match self.accepts() {
+            Some(accepts) if !accepts.is_empty() => accepts,
+            _ => return true,
+        };
+
+        let content_type = content_type.unwrap_or("application/octet-stream");
+        accepts
+            .split(',')
+            .map(str::trim)
+            .any(|pattern| match pattern.strip_suffix("/*") {
+                Some(kind) => content_type.split('/').next() == Some(kind),
+                None => pattern == "*/*" || pattern.eq_ignore_ascii_case(content_type),
+            })
+    }
+
+    /// Check whether `bytes` more fit in the bucket without exceeding its size.
+    pub async fn has_room(&self, db: &Database, bytes: u64) -> anyhow::Result<bool> {
+        match self.size {
+            Some(size) => Ok(usage(db, self.id).await? as u64 + bytes <= size as u64),
+            None => Ok(true),
+        }
+    }
+}
+
+/// Path (relative to storage root) of an object stored in a bucket. Paths already within the
+/// bucket are returned as is.
+pub fn object_path(pid: &str, path: &str) -> String {
+    let path = assets::normalize(path);
+    match path.is_empty() {
+        true => format!("{BUCKETS_DIR}/{pid}"),
+        false if pid_of(&path) == Some(pid) => path,
+        false => format!("{BUCKETS_DIR}/{pid}/{path}"),
+    }
+}
+
+/// pid of the bucket a storage path belongs to, if any.
+pub fn pid_of(path: &str) -> Option<&str> {
+    let rest = path.trim_start_matches('/').strip_prefix(BUCKETS_DIR)?;
+    rest.strip_prefix('/')?
+        .split('/')
+        .next()
+        .filter(|pid| !pid.is_empty())
+}
+
+/// Check whether a storage path is the buckets folder or a bucket's root folder. These are
+/// never indexed as assets.
+pub fn is_namespace(path: &str) -> bool {
+    let path = path.trim_matches('/');
+    path == BUCKETS_DIR || pid_of(path).is_some_and(|pid| path == format!("{BUCKETS_DIR}/{pid}"))
+}
+
+/// Directory holding a bucket's objects.
+pub fn bucket_dir(root_dir: &Path, pid: &str) -> PathBuf {
+    root_dir.join(BUCKETS_DIR).join(pid)
 }
 
 pub async fn create(data: CreateBucketData, db: &Database) -> anyhow::Result<String> {
@@ -15,21 +176,333 @@ pub async fn create(data: CreateBucketData, db: &Database) -> anyhow::Result<Str
         accepts,
         owner_type,
         owner_id,
+        versioning,
+        visibility,
+        public_tag,
+        retention,
+        precompress,
     } = data;
-    
+
+    if size.is_some_and(|size| size <= 0) {
+        return Err(anyhow!("bucket size must be greater than 0"));
+    }
+
+    let accepts = accepts
+        .map(|accepts| check_accepts(&accepts).map(|_| accepts.join(",")))
+        .transpose()?;
+
+    if let AssetOwnerName::Client = owner_type
+        && let Some(limit) = quota::limit(db, owner_id).await?
+    {
+        let owner_id = asset_owner_id(AssetOwnerName::Client, owner_id, db).await?;
+        let allocated = allocated(db, owner_id).await? as u64;
+        if allocated + size.unwrap_or_default() as u64 > limit {
+            return Err(anyhow!("bucket size exceeds the client's max_bucket_size"));
+        }
+    }
+
     let owner_id = asset_owner_id(owner_type, owner_id, db).await?;
     let pid = generate_nano_id(32);
-    let accepts = accepts.map(|s| s.join(","));
     let created_at = instance_as_string()?;
 
-    let mut placeholders = Vec::with_capacity(5);
-    for idx in 1..6 {
+    let mut placeholders = Vec::with_capacity(11);
+    for idx in 1..12 {
         placeholders.push(db.placeholder(idx))
     }
 
     let placeholders = placeholders.join(",");
-    let query = sql_safe!("INSERT INTO buckets (pid, size, accepts, created_at, owner_id) VALUES({placeholders})");
-    sqlx::query(query).bind(&pid).bind(size).bind(accepts).bind(created_at).bind(owner_id).execute(&**db).await?;
+    let query = sql_safe!(
+        "INSERT INTO buckets (pid, size, accepts, created_at, owner_id, versioning, visibility, public_tag, lock_mode, lock_days, precompress) VALUES({placeholders})"
+    );
+    sqlx::query(query)
+        .bind(&pid)
+        .bind(size)
+        .bind(accepts)
+        .bind(created_at)
+        .bind(owner_id)
+        .bind(i16::from(DbBool::from(versioning)))
+        .bind(i16::from(visibility))
+        .bind(public_tag.map(|tag| tag.to_string()))
+        .bind(retention.map(|r| i16::from(r.mode)))
+        .bind(retention.map(|r| r.days as i32))
+        .bind(i16::from(DbBool::from(precompress)))
+        .execute(&**db)
+        .await?;
     
     Ok(pid)
 }
+
+pub async fn get(pid: &str, db: &Database) -> anyhow::Result<Bucket> {
+    let query = sql_safe!(
+        "SELECT * FROM buckets WHERE pid = {} LIMIT 1",
+        db.placeholder(1)
+    );
+    let bucket = sqlx::query_as(query).bind(pid).fetch_one(&**db).await?;
+
+    Ok(bucket)
+}
+
+pub async fn get_by_id(id: i32, db: &Database) -> anyhow::Result<Bucket> {
+    let query = sql_safe!(
+        "SELECT * FROM buckets WHERE id = {} LIMIT 1",
+        db.placeholder(1)
+    );
+    let bucket = sqlx::query_as(query).bind(id).fetch_one(&**db).await?;
+
+    Ok(bucket)
+}
+
+/// Turn object versioning on or off for a bucket. Existing versions are kept when versioning is
+/// turned off.
+pub async fn set_versioning(pid: &str, enabled: bool, db: &Database) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "UPDATE buckets SET versioning = {} WHERE pid = {}",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    sqlx::query(query)
+        .bind(i16::from(DbBool::from(enabled)))
+        .bind(pid)
+        .execute(&**db)
+        .await?;
+    Ok(())
+}
+
+/// Turn precompression of uploaded files on or off for a bucket. Copies already written are
+/// kept when it's turned off.
+pub async fn set_precompress(pid: &str, enabled: bool, db: &Database) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "UPDATE buckets SET precompress = {} WHERE pid = {}",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    sqlx::query(query)
+        .bind(i16::from(DbBool::from(enabled)))
+        .bind(pid)
+        .execute(&**db)
+        .await?;
+    Ok(())
+}
+
+/// Change a bucket's default visibility and public tag.
+pub async fn set_visibility(
+    pid: &str,
+    visibility: Visibility,
+    public_tag: Option<&TagFilter>,
+    db: &Database,
+) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "UPDATE buckets SET visibility = {}, public_tag = {} WHERE pid = {}",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3)
+    );
+
+    sqlx::query(query)
+        .bind(i16::from(visibility))
+        .bind(public_tag.map(TagFilter::to_string))
+        .bind(pid)
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+/// Change the retention applied to files written to a bucket. Files already in the bucket keep
+/// their retention.
+pub async fn set_retention(
+    pid: &str,
+    retention: Option<DefaultRetention>,
+    db: &Database,
+) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "UPDATE buckets SET lock_mode = {}, lock_days = {} WHERE pid = {}",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3)
+    );
+
+    sqlx::query(query)
+        .bind(retention.map(|r| i16::from(r.mode)))
+        .bind(retention.map(|r| r.days as i32))
+        .bind(pid)
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+/// List an owner's buckets.
+pub async fn list(db: &Database, owner_id: i32) -> anyhow::Result<Vec<Bucket>> {
+    let query = sql_safe!(
+        "SELECT * FROM buckets WHERE owner_id = {} ORDER BY created_at",
+        db.placeholder(1)
+    );
+
+    let buckets = sqlx::query_as(query)
+        .bind(owner_id)
+        .fetch_all(&**db)
+        .await?;
+    Ok(buckets)
+}
+
+/// Bytes stored in a bucket.
+pub async fn usage(db: &Database, bucket_id: i32) -> anyhow::Result<i64> {
+    let query = sql_safe!(
+        "SELECT COALESCE(SUM(size), 0) FROM assets WHERE bucket_id = {}",
+        db.placeholder(1)
+    );
+
+    let size = sqlx::query_scalar(query)
+        .bind(bucket_id)
+        .fetch_one(&**db)
+        .await?;
+    Ok(size)
+}
+
+/// Number of assets stored in a bucket.
+pub async fn count(db: &Database, bucket_id: i32) -> anyhow::Result<i64> {
+    let query = sql_safe!(
+        "SELECT COUNT(*) FROM assets WHERE bucket_id = {}",
+        db.placeholder(1)
+    );
+
+    let count = sqlx::query_scalar(query)
+        .bind(bucket_id)
+        .fetch_one(&**db)
+        .await?;
+    Ok(count)
+}
+
+/// Delete a bucket along with its objects and their versions. Non-empty buckets are only
+/// deleted when `force` is set, and never while they hold locked objects. Governance retention
+/// doesn't apply when `bypass_governance` is set.
+pub async fn delete(
+    db: &Database,
+    root_dir: &Path,
+    bucket: &Bucket,
+    force: bool,
+    bypass_governance: bool,
+) -> anyhow::Result<()> {
+    if !force && count(db, bucket.id).await? > 0 {
+        return Err(anyhow!("bucket is not empty"));
+    }
+
+    let path = object_path(&bucket.pid, "");
+    if let Some(locked) = lock::find_locked(db, &path, bypass_governance).await? {
+        return Err(anyhow!("bucket holds a locked object. {locked}"));
+    }
+
+    versions::remove_all(db, root_dir, bucket.id).await?;
+    let dir = bucket_dir(root_dir, &bucket.pid);
+    if dir.exists() {
+        tokio::fs::remove_dir_all(&dir).await?;
+    }
+
+    let query = sql_safe!(
+        "SELECT pid FROM assets WHERE bucket_id = {}",
+        db.placeholder(1)
+    );
+    let pids: Vec<String> = sqlx::query_scalar(query)
+        .bind(bucket.id)
+        .fetch_all(&**db)
+        .await?;
+    assets::remove_variants(root_dir, &pids).await?;
+
+    // assets and versions are removed by cascade.
+    let query = sql_safe!("DELETE FROM buckets WHERE id = {}", db.placeholder(1));
+    sqlx::query(query).bind(bucket.id).execute(&**db).await?;
+
+    Ok(())
+}
+
+/// Total size (in bytes) allocated to an owner's buckets.
+async fn allocated(db: &Database, owner_id: i32) -> anyhow::Result<i64> {
+    let query = sql_safe!(
+        "SELECT COALESCE(SUM(size), 0) FROM buckets WHERE owner_id = {}",
+        db.placeholder(1)
+    );
+
+    let size = sqlx::query_scalar(query)
+        .bind(owner_id)
+        .fetch_one(&**db)
+        .await?;
+    Ok(size)
+}
+
+fn check_accepts(accepts: &[String]) -> anyhow::Result<()> {
+    for pattern in accepts {
+        let valid = match pattern.split_once('/') {
+            Some((kind, subtype)) => {
+                !kind.is_empty() && !subtype.is_empty() && !pattern.contains(',')
+            }
+            None => false,
+        };
+
+        if !valid {
+            return Err(anyhow!("invalid content type \"{pattern}\" in accepts"));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::acl::Visibility;
+    use crate::buckets::{self, CreateBucketData};
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::secrets::AppSecrets;
+    use crate::utils::AssetOwnerName;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_bucket_limits() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Buckets Test", Some(1.0)).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+
+        let data = |size: i64| CreateBucketData {
+            size: Some(size),
+            accepts: Some(vec!["image/*".to_string(), "application/pdf".to_string()]),
+            owner_type: AssetOwnerName::Client,
+            owner_id: client_id,
+            versioning: false,
+            visibility: Visibility::Private,
+            public_tag: None,
+            retention: None,
+            precompress: false,
+        };
+
+        let pid = buckets::create(data(512_000), &db).await?;
+        assert!(buckets::create(data(1_000_000), &db).await.is_err());
+
+        let bucket = buckets::get(&pid, &db).await?;
+        assert!(bucket.accepts_type(Some("image/png")));
+        assert!(bucket.accepts_type(Some("application/pdf")));
+        assert!(!bucket.accepts_type(Some("text/plain")));
+        assert!(bucket.has_room(&db, 512_000).await?);
+        assert!(!bucket.has_room(&db, 512_001).await?);
+
+        let owner_id = client::owner_id(&db, client_id).await?;
+        assert_eq!(buckets::list(&db, owner_id).await?.len(), 1);
+
+        let object = buckets::object_path(&pid, "/photos/cat.png");
+        assert_eq!(buckets::pid_of(&object), Some(pid.as_str()));
+        assert!(buckets::is_namespace(&format!("buckets/{pid}")));
+        assert!(!buckets::is_namespace(&object));
+
+        let root_dir = crate::root_dir()?.join("test-assets/buckets");
+        buckets::delete(&db, &root_dir, &bucket, false, false).await?;
+        assert!(buckets::get(&pid, &db).await.is_err());
+
+        Ok(())
+    }
+}
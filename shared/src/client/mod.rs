@@ -1,11 +1,18 @@
+use crate::assets;
+use crate::buckets::BUCKETS_DIR;
 use crate::db::Database;
 use crate::tools::secrets::AppSecrets;
+use crate::utils::{AssetOwnerName, asset_owner_id};
 use chacha20poly1305::aead::Aead;
 use chacha20poly1305::{Key, KeyInit, XChaCha20Poly1305, XNonce};
 use models::{Client, ClientInsertArgs};
+use std::path::{Path, PathBuf};
 
 pub(crate) mod models;
 
+/// Folder (relative to storage root) holding client namespaces.
+pub const CLIENTS_DIR: &str = "clients";
+
 /// generate a cipher token for client's id.
 fn client_token(secrets: &AppSecrets, client_key: &str) -> anyhow::Result<String> {
     let key = secrets.secret_key();
@@ -88,6 +95,58 @@ pub async fn get_key(db: &Database, pid: &str) -> anyhow::Result<String> {
     Client::get_key(db, pid).await
 }
 
+/// get a client's id from its pid.
+pub async fn id_by_pid(db: &Database, pid: &str) -> anyhow::Result<i32> {
+    Client::id_by_pid(db, pid).await
+}
+
+/// get the `asset_owner` id for a given client id.
+pub async fn owner_id(db: &Database, id: i32) -> anyhow::Result<i32> {
+    asset_owner_id(AssetOwnerName::Client, id, db).await
+}
+
+/// get the pid of the client behind an `asset_owner` id. Returns `None` for other owners.
+pub async fn pid_by_owner(db: &Database, owner_id: i32) -> anyhow::Result<Option<String>> {
+    Client::pid_by_owner(db, owner_id).await
+}
+
+/// Directory holding the objects a client stores outside of buckets.
+pub fn namespace_dir(root_dir: &Path, pid: &str) -> PathBuf {
+    root_dir.join(CLIENTS_DIR).join(pid)
+}
+
+/// Path (relative to storage root) of an object in a client's namespace. Paths already within
+/// the client's namespace or within a bucket are returned as is, so resolving is idempotent.
+pub fn object_path(pid: &str, path: &str) -> String {
+    let path = assets::normalize(path);
+    let namespace = format!("{CLIENTS_DIR}/{pid}");
+    if path.is_empty() {
+        return namespace;
+    }
+
+    if path == namespace
+        || path.starts_with(&format!("{namespace}/"))
+        || path.starts_with(&format!("{BUCKETS_DIR}/"))
+    {
+        return path;
+    }
+
+    format!("{namespace}/{path}")
+}
+
+/// Check whether a storage path is the clients folder or a client's namespace folder. These are
+/// never indexed as assets.
+pub fn is_namespace(path: &str) -> bool {
+    let path = path.trim_matches('/');
+    match path.strip_prefix(CLIENTS_DIR) {
+        Some("") => true,
+        Some(rest) => rest
+            .strip_prefix('/')
+            .is_some_and(|pid| !pid.is_empty() && !pid.contains('/')),
+        None => false,
+    }
+}
+
 pub struct ClientDetails {
     id: String,
     token: String,
@@ -110,6 +110,33 @@ impl Client {
         Ok(id)
     }
 
+    pub async fn id_by_pid(db: &Database, pid: &str) -> anyhow::Result<i32> {
+        let query = sql_safe!(
+            "SELECT id FROM clients WHERE pid = {} LIMIT 1",
+            db.placeholder(1)
+        );
+
+        let id = sqlx::query_scalar(query).bind(pid).fetch_one(&**db).await?;
+        Ok(id)
+    }
+
+    /// get the pid of the client behind an `asset_owner` id, if the owner is a client.
+    pub async fn pid_by_owner(db: &Database, owner_id: i32) -> anyhow::Result<Option<String>> {
+        let query = sql_safe!(
+            "SELECT c.pid FROM clients c JOIN asset_owner o ON o.owner_id = c.id WHERE o.id = {} AND o.name = {} LIMIT 1",
+            db.placeholder(1),
+            db.placeholder(2)
+        );
+
+        let pid = sqlx::query_scalar(query)
+            .bind(owner_id)
+            .bind(i16::from(AssetOwnerName::Client))
+            .fetch_optional(&**db)
+            .await?;
+
+        Ok(pid)
+    }
+
     pub async fn update_key(db: &Database, id: &str) -> anyhow::Result<String> {
         let key = Self::generate_nano();
         let query = sql_safe!(
@@ -130,6 +157,22 @@ impl Client {
     pub fn generate_nano() -> String {
         generate_nano_id(32)
     }
+
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn max_bucket_size(&self) -> Option<f64> {
+        self.max_bucket_size
+    }
+
+    pub fn created_at(&self) -> &str {
+        &self.created_at
+    }
 }
 
 #[derive(Serialize)]
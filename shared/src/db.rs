@@ -26,10 +26,19 @@ impl Database {
         };
 
         let pool = AnyPool::connect(url).await?;
-        migrate!("../migrations").run(&pool).await?;
+        match engine {
+            DbEngine::Sqlite => migrate!("../migrations/sqlite").run(&pool).await?,
+            DbEngine::Postgres => migrate!("../migrations/postgres").run(&pool).await?,
+            DbEngine::Mysql => migrate!("../migrations/mysql").run(&pool).await?,
+        }
+
         Ok(Self { pool, engine })
     }
 
+    pub fn engine(&self) -> &DbEngine {
+        &self.engine
+    }
+
     pub fn placeholder(&self, idx: u8) -> String {
         match self.engine {
             DbEngine::Mysql => "?".to_string(),
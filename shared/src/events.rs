@@ -0,0 +1,69 @@
+use crate::assets::{Asset, AssetType};
+use crate::utils::unix_timestamp;
+use serde::{Deserialize, Serialize};
+
+/// Change an [ObjectEvent] reports.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ObjectEventKind {
+    Created,
+    Updated,
+    Deleted,
+    Moved,
+}
+
+impl ObjectEventKind {
+    /// Name of the event, as sent to event stream subscribers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectEventKind::Created => "created",
+            ObjectEventKind::Updated => "updated",
+            ObjectEventKind::Deleted => "deleted",
+            ObjectEventKind::Moved => "moved",
+        }
+    }
+}
+
+/// A change to a file or folder, published to subscribers of every server instance.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ObjectEvent {
+    pub kind: ObjectEventKind,
+    /// Storage path of the object. Moved objects are reported at their new path.
+    pub path: String,
+    /// Previous storage path of a moved object.
+    pub from: Option<String>,
+    pub asset_type: AssetType,
+    pub owner_id: i32,
+    pub bucket_id: Option<i32>,
+    /// Unix time (in seconds) the change happened at.
+    pub time: i64,
+}
+
+impl ObjectEvent {
+    pub fn new(kind: ObjectEventKind, asset: &Asset) -> anyhow::Result<Self> {
+        Ok(Self {
+            kind,
+            path: asset.path().to_string(),
+            from: None,
+            asset_type: asset.asset_type(),
+            owner_id: asset.owner_id(),
+            bucket_id: asset.bucket_id(),
+            time: unix_timestamp()?,
+        })
+    }
+
+    /// Event for `asset`, moved from `from`.
+    pub fn moved(asset: &Asset, from: &str) -> anyhow::Result<Self> {
+        let mut event = Self::new(ObjectEventKind::Moved, asset)?;
+        event.from = Some(from.to_string());
+        Ok(event)
+    }
+
+    /// Check whether the event concerns an object at or under `prefix`, before or after a move.
+    pub fn matches(&self, prefix: &str) -> bool {
+        self.path.starts_with(prefix)
+            || self
+                .from
+                .as_deref()
+                .is_some_and(|from| from.starts_with(prefix))
+    }
+}
@@ -0,0 +1,322 @@
+use crate::assets::{self, Asset, AssetType, StorageClass};
+use crate::buckets::{self, BUCKETS_DIR};
+use crate::client::{self, CLIENTS_DIR};
+use crate::db::Database;
+use crate::sql_safe;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Age after which an entry of the upload staging folder is considered abandoned.
+pub const STALE_TMP_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum FsckIssue {
+    /// Stored, but not indexed.
+    Orphan,
+    /// Indexed, but not stored.
+    Missing,
+    /// The stored file's size differs from the indexed one.
+    SizeMismatch,
+    /// An upload chunk nobody has written to for [STALE_TMP_AGE].
+    StaleTmp,
+}
+
+#[derive(Serialize)]
+pub struct FsckFinding {
+    pub path: String,
+    pub issue: FsckIssue,
+    /// Whether the issue was fixed.
+    pub fixed: bool,
+}
+
+/// Issues [check] fixes. Nothing is changed by default.
+#[derive(Default)]
+pub struct FsckFixes {
+    /// Index orphans under their namespace's owner.
+    pub adopt: bool,
+    /// Delete orphans from storage.
+    pub delete: bool,
+    /// Re-index files whose size changed, and forget missing assets.
+    pub reindex: bool,
+    /// Remove stale upload chunks.
+    pub clean_tmp: bool,
+}
+
+/// Reconcile the asset index with client and bucket namespaces under `root_dir`, and look for
+/// stale entries in the upload staging folder `tmp_dir`.
+pub async fn check(
+    db: &Database,
+    root_dir: &Path,
+    tmp_dir: &Path,
+    fixes: &FsckFixes,
+) -> anyhow::Result<Vec<FsckFinding>> {
+    let indexed = index(db).await?;
+    let mut findings = vec![];
+
+    for asset in indexed.values() {
+        let stored = root_dir.join(asset.path());
+        let issue = match asset.asset_type() {
+            // stored in the cold volume.
+            _ if asset.storage_class() == StorageClass::Cold => continue,
+            AssetType::File if !stored.is_file() => FsckIssue::Missing,
+            AssetType::Folder if !stored.is_dir() => FsckIssue::Missing,
+            AssetType::File if stored.metadata()?.len() != asset.size() as u64 => {
+                FsckIssue::SizeMismatch
+            }
+            _ => continue,
+        };
+
+        let fixed = fixes.reindex && reindex(db, root_dir, asset, issue).await?;
+        findings.push(FsckFinding {
+            path: asset.path().to_string(),
+            issue,
+            fixed,
+        });
+    }
+
+    for namespace in [CLIENTS_DIR, BUCKETS_DIR] {
+        let dir = root_dir.join(namespace);
+        if !dir.is_dir() {
+            continue;
+        }
+
+        let mut rd = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = rd.next_entry().await? {
+            let pid = entry.file_name().to_string_lossy().to_string();
+            let owner = match namespace {
+                CLIENTS_DIR => client_owner(db, &pid).await,
+                _ => bucket_owner(db, &pid).await,
+            };
+
+            let path = format!("{namespace}/{pid}");
+            let walk = Walk {
+                db,
+                root_dir,
+                indexed: &indexed,
+                fixes,
+                owner,
+            };
+
+            walk.orphans(&path, &mut findings).await?;
+        }
+    }
+
+    stale_tmp(tmp_dir, fixes.clean_tmp, &mut findings).await?;
+    Ok(findings)
+}
+
+/// Every indexed asset, keyed by path.
+async fn index(db: &Database) -> anyhow::Result<HashMap<String, Asset>> {
+    let query = sql_safe!("SELECT * FROM assets ORDER BY path");
+    let assets: Vec<Asset> = sqlx::query_as(query).fetch_all(&**db).await?;
+
+    Ok(assets
+        .into_iter()
+        .map(|asset| (asset.path().to_string(), asset))
+        .collect())
+}
+
+async fn reindex(
+    db: &Database,
+    root_dir: &Path,
+    asset: &Asset,
+    issue: FsckIssue,
+) -> anyhow::Result<bool> {
+    match issue {
+        FsckIssue::Missing => assets::remove(db, root_dir, asset.path()).await?,
+        _ => {
+            let (owner_id, bucket_id) = (asset.owner_id(), asset.bucket_id());
+            assets::index_file(db, root_dir, asset.path(), owner_id, bucket_id).await?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Owner id and bucket id assets of a namespace are indexed under. `None` when the namespace's
+/// client or bucket doesn't exist.
+type NamespaceOwner = Option<(i32, Option<i32>)>;
+
+async fn client_owner(db: &Database, pid: &str) -> NamespaceOwner {
+    let client_id = client::id_by_pid(db, pid).await.ok()?;
+    let owner_id = client::owner_id(db, client_id).await.ok()?;
+
+    Some((owner_id, None))
+}
+
+async fn bucket_owner(db: &Database, pid: &str) -> NamespaceOwner {
+    let bucket = buckets::get(pid, db).await.ok()?;
+    Some((bucket.owner_id(), Some(bucket.id())))
+}
+
+struct Walk<'a> {
+    db: &'a Database,
+    root_dir: &'a Path,
+    indexed: &'a HashMap<String, Asset>,
+    fixes: &'a FsckFixes,
+    owner: NamespaceOwner,
+}
+
+impl Walk<'_> {
+    /// Look for orphans under the folder at `path`, recursively.
+    async fn orphans(&self, path: &str, findings: &mut Vec<FsckFinding>) -> anyhow::Result<()> {
+        let mut rd = tokio::fs::read_dir(self.root_dir.join(path)).await?;
+        let mut entries = vec![];
+        while let Some(entry) = rd.next_entry().await? {
+            entries.push(entry);
+        }
+
+        // parents are adopted before their children.
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let child = format!("{path}/{}", entry.file_name().to_string_lossy());
+            let is_dir = entry.file_type().await?.is_dir();
+            if self.indexed.contains_key(&child) {
+                if is_dir {
+                    Box::pin(self.orphans(&child, findings)).await?;
+                }
+
+                continue;
+            }
+
+            let fixed = self.fix_orphan(&child, is_dir).await?;
+            findings.push(FsckFinding {
+                path: child.clone(),
+                issue: FsckIssue::Orphan,
+                fixed,
+            });
+
+            // an adopted folder's content is adopted as well, while a deleted folder's is gone.
+            if is_dir && !(fixed && self.fixes.delete) {
+                Box::pin(self.orphans(&child, findings)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn fix_orphan(&self, path: &str, is_dir: bool) -> anyhow::Result<bool> {
+        let stored = self.root_dir.join(path);
+        if self.fixes.delete {
+            match is_dir {
+                true => tokio::fs::remove_dir_all(stored).await?,
+                false => tokio::fs::remove_file(stored).await?,
+            }
+
+            return Ok(true);
+        }
+
+        let Some((owner_id, bucket_id)) = self.owner.filter(|_| self.fixes.adopt) else {
+            return Ok(false);
+        };
+
+        match is_dir {
+            true => assets::index_folder(self.db, path, owner_id, bucket_id).await?,
+            false => assets::index_file(self.db, self.root_dir, path, owner_id, bucket_id).await?,
+        };
+
+        Ok(true)
+    }
+}
+
+async fn stale_tmp(
+    tmp_dir: &Path,
+    clean: bool,
+    findings: &mut Vec<FsckFinding>,
+) -> anyhow::Result<()> {
+    if !tmp_dir.is_dir() {
+        return Ok(());
+    }
+
+    let mut rd = tokio::fs::read_dir(tmp_dir).await?;
+    while let Some(entry) = rd.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        let age = SystemTime::now()
+            .duration_since(metadata.modified()?)
+            .unwrap_or_default();
+
+        if age < STALE_TMP_AGE {
+            continue;
+        }
+
+        if clean {
+            match metadata.is_dir() {
+                true => tokio::fs::remove_dir_all(entry.path()).await?,
+                false => tokio::fs::remove_file(entry.path()).await?,
+            }
+        }
+
+        findings.push(FsckFinding {
+            path: entry.path().to_string_lossy().to_string(),
+            issue: FsckIssue::StaleTmp,
+            fixed: clean,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets;
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::fsck::{self, FsckFixes, FsckIssue};
+    use crate::generate_nano_id;
+    use crate::secrets::AppSecrets;
+
+    #[tokio::test]
+    async fn test_fsck() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("ppdrive-fsck-{}", generate_nano_id(8)));
+        let (root_dir, tmp_dir) = (dir.join("root"), dir.join("tmp"));
+        tokio::fs::create_dir_all(&tmp_dir).await?;
+        let db = Database::new(&format!("sqlite:{}", dir.join("fsck.db").display())).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Fsck Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let namespace = client::namespace_dir(&root_dir, details.id());
+        tokio::fs::create_dir_all(namespace.join("docs")).await?;
+        for file in ["changed.txt", "gone.txt", "docs/orphan.txt"] {
+            tokio::fs::write(namespace.join(file), "content").await?;
+        }
+
+        for file in ["changed.txt", "gone.txt"] {
+            let path = client::object_path(details.id(), file);
+            assets::index_file(&db, &root_dir, &path, owner_id, None).await?;
+        }
+
+        tokio::fs::write(namespace.join("changed.txt"), "new content").await?;
+        tokio::fs::remove_file(namespace.join("gone.txt")).await?;
+
+        let issues = async |fixes: &FsckFixes| -> anyhow::Result<Vec<(String, FsckIssue)>> {
+            let findings = fsck::check(&db, &root_dir, &tmp_dir, fixes).await?;
+            Ok(findings.into_iter().map(|f| (f.path, f.issue)).collect())
+        };
+
+        let path = |file: &str| client::object_path(details.id(), file);
+        let found = issues(&FsckFixes::default()).await?;
+        assert_eq!(found.len(), 4);
+        assert!(found.contains(&(path("changed.txt"), FsckIssue::SizeMismatch)));
+        assert!(found.contains(&(path("gone.txt"), FsckIssue::Missing)));
+        assert!(found.contains(&(path("docs"), FsckIssue::Orphan)));
+        assert!(found.contains(&(path("docs/orphan.txt"), FsckIssue::Orphan)));
+
+        let fixes = FsckFixes {
+            adopt: true,
+            reindex: true,
+            ..Default::default()
+        };
+
+        issues(&fixes).await?;
+        assert!(issues(&FsckFixes::default()).await?.is_empty());
+        assert!(assets::get(&db, &path("docs/orphan.txt")).await?.is_some());
+
+        tokio::fs::remove_dir_all(dir).await?;
+        Ok(())
+    }
+}
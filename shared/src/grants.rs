@@ -0,0 +1,92 @@
+use crate::db::Database;
+use crate::sql_safe;
+use crate::utils::unix_timestamp;
+
+/// Track a download token limited to `max_downloads` uses. `expires_at` is the token's
+/// expiration (unix seconds), after which the grant can be swept.
+pub async fn create(
+    db: &Database,
+    pid: &str,
+    max_downloads: u32,
+    expires_at: i64,
+) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "INSERT INTO download_grants (pid, max_downloads, expires_at) VALUES ({}, {}, {})",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3)
+    );
+
+    sqlx::query(query)
+        .bind(pid)
+        .bind(max_downloads as i32)
+        .bind(expires_at)
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+/// Check whether a grant has downloads left, without using one up.
+pub async fn available(db: &Database, pid: &str) -> anyhow::Result<bool> {
+    let query = sql_safe!(
+        "SELECT COUNT(*) FROM download_grants WHERE pid = {} AND downloads < max_downloads",
+        db.placeholder(1)
+    );
+
+    let count: i64 = sqlx::query_scalar(query).bind(pid).fetch_one(&**db).await?;
+    Ok(count > 0)
+}
+
+/// Count a download against a grant. Returns `false` when the grant is used up or unknown.
+pub async fn consume(db: &Database, pid: &str) -> anyhow::Result<bool> {
+    let query = sql_safe!(
+        "UPDATE download_grants SET downloads = downloads + 1 WHERE pid = {} AND downloads < max_downloads",
+        db.placeholder(1)
+    );
+
+    let result = sqlx::query(query).bind(pid).execute(&**db).await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Remove grants whose token has expired. Returns the number of grants removed.
+pub async fn sweep(db: &Database) -> anyhow::Result<u64> {
+    let query = sql_safe!(
+        "DELETE FROM download_grants WHERE expires_at <= {}",
+        db.placeholder(1)
+    );
+
+    let result = sqlx::query(query)
+        .bind(unix_timestamp()?)
+        .execute(&**db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::Database;
+    use crate::{generate_nano_id, grants};
+    use std::env;
+
+    #[tokio::test]
+    async fn test_consume_grant() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let pid = generate_nano_id(32);
+        grants::create(&db, &pid, 2, 0).await?;
+
+        assert!(grants::consume(&db, &pid).await?);
+        assert!(grants::available(&db, &pid).await?);
+        assert!(grants::consume(&db, &pid).await?);
+        assert!(!grants::available(&db, &pid).await?);
+        assert!(!grants::consume(&db, &pid).await?);
+        assert!(!grants::consume(&db, "unknown").await?);
+
+        assert!(grants::sweep(&db).await? >= 1);
+        Ok(())
+    }
+}
@@ -1,11 +1,27 @@
+pub mod acl;
+pub mod archive;
+pub mod assets;
+pub mod audit;
 pub mod broker;
+pub mod buckets;
 pub mod client;
 pub mod db;
+pub mod events;
+pub mod fsck;
+pub mod grants;
+pub mod lifecycle;
+pub mod listing;
+pub mod lock;
+pub mod quota;
+pub mod scrub;
+pub mod search;
 #[cfg(feature = "server")]
 pub mod server;
+pub mod tags;
+pub mod trash;
 pub mod user;
-pub mod buckets;
 mod utils;
+pub mod versions;
 
 mod tools;
-pub use tools::*;
\ No newline at end of file
+pub use tools::*;
@@ -0,0 +1,600 @@
+use crate::assets::StorageClass;
+use crate::buckets::{self, Bucket};
+use crate::db::Database;
+use crate::tags::{self, TagFilter};
+use crate::utils::instance_as_string;
+use crate::{assets, audit, client, generate_nano_id, lock, move_path, sql_safe, trash, versions};
+use anyhow::anyhow;
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Longest age (in days) a rule may wait for, about a hundred years.
+pub const MAX_AGE: u32 = 36_500;
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+pub enum LifecycleAction {
+    Delete,
+    #[default]
+    Trash,
+    /// Move objects to the configured `cold_volume`, at the same path relative to it as under
+    /// the storage root. They stay indexed as [StorageClass::Cold] objects, listed and served
+    /// from there, until [restore_cold] brings them back.
+    Transition,
+}
+
+impl From<i16> for LifecycleAction {
+    fn from(value: i16) -> Self {
+        use LifecycleAction::*;
+
+        match value {
+            0 => Delete,
+            1 => Trash,
+            2 => Transition,
+            _ => Default::default(),
+        }
+    }
+}
+
+impl From<LifecycleAction> for i16 {
+    fn from(value: LifecycleAction) -> Self {
+        use LifecycleAction::*;
+
+        match value {
+            Delete => 0,
+            Trash => 1,
+            Transition => 2,
+        }
+    }
+}
+
+/// A rule that applies `action` to objects older than `age` days whose path matches `prefix`
+/// and/or `glob`. When `tag` is set, only objects carrying the tag are affected.
+#[derive(FromRow, Serialize)]
+pub struct LifecycleRule {
+    #[serde(skip)]
+    id: i32,
+    pid: String,
+    #[serde(skip)]
+    owner_id: i32,
+    #[serde(skip)]
+    bucket_id: Option<i32>,
+    prefix: Option<String>,
+    glob: Option<String>,
+    age: i32,
+    #[sqlx(try_from = "i16")]
+    action: LifecycleAction,
+    created_at: String,
+    tag: Option<String>,
+}
+
+impl LifecycleRule {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn owner_id(&self) -> i32 {
+        self.owner_id
+    }
+
+    pub fn bucket_id(&self) -> Option<i32> {
+        self.bucket_id
+    }
+
+    pub fn action(&self) -> LifecycleAction {
+        self.action
+    }
+
+    fn tag_filter(&self) -> anyhow::Result<Option<TagFilter>> {
+        match &self.tag {
+            Some(tag) => Ok(Some(tag.parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn matcher(&self) -> anyhow::Result<Option<GlobMatcher>> {
+        match &self.glob {
+            Some(glob) => Ok(Some(compile_glob(glob)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn matches(&self, path: &str, glob: Option<&GlobMatcher>) -> bool {
+        let prefix = self.prefix.as_deref().unwrap_or_default();
+        path.starts_with(prefix.trim_start_matches("/"))
+            && glob.map(|g| g.is_match(path)).unwrap_or(true)
+    }
+}
+
+pub struct LifecycleRuleArgs {
+    /// Limit the rule to a bucket's objects. The rule applies to all of the owner's objects
+    /// otherwise.
+    pub bucket_id: Option<i32>,
+    pub prefix: Option<String>,
+    pub glob: Option<String>,
+    /// Limit the rule to objects tagged `key=value` (or just `key`).
+    pub tag: Option<TagFilter>,
+    /// Age (in days) after which the rule applies to an object.
+    pub age: u32,
+    pub action: LifecycleAction,
+}
+
+/// An action taken on an object by a lifecycle rule.
+pub struct LifecycleOutcome {
+    pub rule: String,
+    pub path: String,
+    pub action: LifecycleAction,
+    /// Why the action failed, if it did.
+    pub error: Option<String>,
+}
+
+pub async fn create(
+    db: &Database,
+    owner_id: i32,
+    args: LifecycleRuleArgs,
+) -> anyhow::Result<String> {
+    let LifecycleRuleArgs {
+        bucket_id,
+        prefix,
+        glob,
+        tag,
+        age,
+        action,
+    } = args;
+
+    if prefix.is_none() && glob.is_none() && tag.is_none() {
+        return Err(anyhow!(
+            "a lifecycle rule requires a prefix, a glob or a tag"
+        ));
+    }
+
+    if !(1..=MAX_AGE).contains(&age) {
+        return Err(anyhow!(
+            "a lifecycle rule's age must be between 1 and {MAX_AGE} days"
+        ));
+    }
+
+    if let Some(glob) = &glob {
+        compile_glob(glob)?;
+    }
+
+    let pid = generate_nano_id(32);
+    let now = instance_as_string()?;
+
+    let mut placeholders = Vec::with_capacity(9);
+    for idx in 1..10 {
+        placeholders.push(db.placeholder(idx))
+    }
+
+    let placeholders = placeholders.join(",");
+    let query = sql_safe!(
+        "INSERT INTO lifecycle_rules (pid, owner_id, bucket_id, prefix, glob, tag, age, action, created_at) VALUES ({placeholders})"
+    );
+
+    sqlx::query(query)
+        .bind(&pid)
+        .bind(owner_id)
+        .bind(bucket_id)
+        .bind(prefix)
+        .bind(glob)
+        .bind(tag.map(|tag| tag.to_string()))
+        .bind(age as i32)
+        .bind(i16::from(action))
+        .bind(now)
+        .execute(&**db)
+        .await?;
+
+    Ok(pid)
+}
+
+pub async fn get(db: &Database, pid: &str) -> anyhow::Result<LifecycleRule> {
+    let query = sql_safe!(
+        "SELECT * FROM lifecycle_rules WHERE pid = {} LIMIT 1",
+        db.placeholder(1)
+    );
+
+    let rule = sqlx::query_as(query).bind(pid).fetch_one(&**db).await?;
+    Ok(rule)
+}
+
+pub async fn list(db: &Database, owner_id: i32) -> anyhow::Result<Vec<LifecycleRule>> {
+    let query = sql_safe!(
+        "SELECT * FROM lifecycle_rules WHERE owner_id = {}",
+        db.placeholder(1)
+    );
+
+    let rules = sqlx::query_as(query)
+        .bind(owner_id)
+        .fetch_all(&**db)
+        .await?;
+    Ok(rules)
+}
+
+pub async fn all(db: &Database) -> anyhow::Result<Vec<LifecycleRule>> {
+    let rules = sqlx::query_as("SELECT * FROM lifecycle_rules")
+        .fetch_all(&**db)
+        .await?;
+
+    Ok(rules)
+}
+
+pub async fn delete(db: &Database, rule: &LifecycleRule) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "DELETE FROM lifecycle_rules WHERE id = {}",
+        db.placeholder(1)
+    );
+    sqlx::query(query).bind(rule.id()).execute(&**db).await?;
+
+    Ok(())
+}
+
+/// Apply a rule to every matching object, recording each action in the owner's audit trail.
+pub async fn apply(
+    db: &Database,
+    root_dir: &Path,
+    cold_volume: Option<&Path>,
+    rule: &LifecycleRule,
+) -> anyhow::Result<Vec<LifecycleOutcome>> {
+    let bucket = match rule.bucket_id() {
+        Some(id) => Some(buckets::get_by_id(id, db).await?),
+        None => None,
+    };
+
+    let scope = match &bucket {
+        Some(bucket) => buckets::bucket_dir(root_dir, bucket.pid()),
+        None => match client::pid_by_owner(db, rule.owner_id()).await? {
+            Some(pid) => client::namespace_dir(root_dir, &pid),
+            None => return Ok(vec![]),
+        },
+    };
+
+    if !scope.exists() {
+        return Ok(vec![]);
+    }
+
+    // rules whose age can't be turned into a point in time are skipped.
+    let cutoff = u64::try_from(rule.age)
+        .ok()
+        .and_then(|days| days.checked_mul(24 * 60 * 60))
+        .and_then(|secs| SystemTime::now().checked_sub(Duration::from_secs(secs)))
+        .ok_or(anyhow!("invalid age of {} days", rule.age))?;
+    let glob = rule.matcher()?;
+    let mut expired = vec![];
+    collect_expired(&scope, &scope, rule, glob.as_ref(), cutoff, &mut expired).await?;
+
+    let tag = rule.tag_filter()?;
+    let mut outcomes = Vec::with_capacity(expired.len());
+    for path in expired {
+        let stored_path = scope.join(&path);
+        let stored_path = stored_path
+            .strip_prefix(root_dir)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or(path.clone());
+
+        if let Some(tag) = &tag
+            && !tags::matches(db, &stored_path, std::slice::from_ref(tag)).await?
+        {
+            continue;
+        }
+
+        // retained and held files outlive lifecycle rules.
+        if lock::find_locked(db, &stored_path, false).await?.is_some() {
+            continue;
+        }
+
+        // an object the action fails on is reported, and the rule moves on to the next one.
+        let action = rule.action();
+        let result = apply_action(
+            db,
+            root_dir,
+            &scope,
+            cold_volume,
+            rule,
+            bucket.as_ref(),
+            &path,
+        )
+        .await;
+
+        if result.is_ok() {
+            let audit_action = format!("lifecycle:{action:?}").to_lowercase();
+            let detail = Some(format!("rule {}", rule.pid()));
+            audit::record(db, rule.owner_id(), &audit_action, &stored_path, detail).await?;
+        }
+
+        outcomes.push(LifecycleOutcome {
+            rule: rule.pid().to_string(),
+            path: stored_path,
+            action,
+            error: result.err().map(|err| err.to_string()),
+        });
+    }
+
+    Ok(outcomes)
+}
+
+async fn apply_action(
+    db: &Database,
+    root_dir: &Path,
+    scope: &Path,
+    cold_volume: Option<&Path>,
+    rule: &LifecycleRule,
+    bucket: Option<&Bucket>,
+    path: &str,
+) -> anyhow::Result<()> {
+    let source = scope.join(path);
+    let root_path = source
+        .strip_prefix(root_dir)
+        .map_err(|_| anyhow!("object is outside of storage root"))?
+        .to_string_lossy()
+        .to_string();
+
+    match rule.action() {
+        LifecycleAction::Delete => match bucket {
+            Some(bucket) if bucket.versioning() => {
+                versions::mark_deleted(db, root_dir, bucket.id(), &root_path).await?;
+            }
+            _ => {
+                tokio::fs::remove_file(source).await?;
+                assets::remove(db, root_dir, &root_path).await?;
+            }
+        },
+        LifecycleAction::Trash => {
+            trash::move_to_trash(db, root_dir, rule.owner_id(), &root_path).await?;
+        }
+        LifecycleAction::Transition => {
+            let cold_volume = cold_volume.ok_or(anyhow!("cold_volume is not configured"))?;
+            transition(db, root_dir, cold_volume, &root_path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Move the file at `path` to the cold volume. It stays indexed, as a [StorageClass::Cold] file.
+pub async fn transition(
+    db: &Database,
+    root_dir: &Path,
+    cold_volume: &Path,
+    path: &str,
+) -> anyhow::Result<()> {
+    move_path(&root_dir.join(path), &cold_volume.join(path)).await?;
+    assets::set_storage_class(db, path, StorageClass::Cold).await
+}
+
+/// Move files at or under `path` back from the cold volume into the storage root. Returns the
+/// number of files moved.
+pub async fn restore_cold(
+    db: &Database,
+    root_dir: &Path,
+    cold_volume: Option<&Path>,
+    path: &str,
+) -> anyhow::Result<usize> {
+    let files = assets::cold_files(db, path).await?;
+    for file in &files {
+        let source = file.stored_path(root_dir, cold_volume)?;
+        move_path(&source, &root_dir.join(file.path())).await?;
+        assets::set_storage_class(db, file.path(), StorageClass::Standard).await?;
+    }
+
+    Ok(files.len())
+}
+
+/// Recursively collect (scope-relative) paths of files matching `rule` which were last modified
+/// before `cutoff`. Reserved folders such as `.trash` and `.versions` are skipped.
+async fn collect_expired(
+    scope: &Path,
+    dir: &Path,
+    rule: &LifecycleRule,
+    glob: Option<&GlobMatcher>,
+    cutoff: SystemTime,
+    expired: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    let mut rd = tokio::fs::read_dir(dir).await?;
+
+    while let Ok(Some(entry)) = rd.next_entry().await {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = entry.path();
+        let metadata = entry.metadata().await?;
+        if metadata.is_dir() {
+            Box::pin(collect_expired(scope, &path, rule, glob, cutoff, expired)).await?;
+            continue;
+        }
+
+        let relative = relative_path(scope, &path)?;
+        if metadata.modified()? <= cutoff && rule.matches(&relative, glob) {
+            expired.push(relative);
+        }
+    }
+
+    Ok(())
+}
+
+fn relative_path(scope: &Path, path: &Path) -> anyhow::Result<String> {
+    let relative = path.strip_prefix(scope)?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+
+    Ok(parts.join("/"))
+}
+
+fn compile_glob(glob: &str) -> anyhow::Result<GlobMatcher> {
+    let glob = GlobBuilder::new(glob.trim_start_matches("/"))
+        .literal_separator(true)
+        .build()?;
+
+    Ok(glob.compile_matcher())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets::{self, StorageClass};
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::lifecycle::{self, LifecycleAction, LifecycleRuleArgs};
+    use crate::secrets::AppSecrets;
+    use crate::{audit, trash};
+    use std::env;
+    use std::time::{Duration, SystemTime};
+
+    #[tokio::test]
+    async fn test_apply_lifecycle_rule() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Lifecycle Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+
+        let owner_id = client::owner_id(&db, client_id).await?;
+        let root_dir = crate::root_dir()?
+            .join("test-assets/lifecycle")
+            .join(details.id());
+        let namespace = client::namespace_dir(&root_dir, details.id());
+        tokio::fs::create_dir_all(namespace.join("exports")).await?;
+        tokio::fs::create_dir_all(namespace.join("keep")).await?;
+
+        let old = SystemTime::now() - Duration::from_secs(10 * 24 * 60 * 60);
+        for path in ["exports/old.csv", "exports/new.csv", "keep/old.csv"] {
+            tokio::fs::write(namespace.join(path), "a,b,c").await?;
+        }
+
+        for path in ["exports/old.csv", "keep/old.csv"] {
+            let file = std::fs::File::options()
+                .write(true)
+                .open(namespace.join(path))?;
+            file.set_modified(old)?;
+        }
+
+        let args = LifecycleRuleArgs {
+            bucket_id: None,
+            prefix: Some("exports/".to_string()),
+            glob: Some("**/*.csv".to_string()),
+            tag: None,
+            age: 7,
+            action: LifecycleAction::Trash,
+        };
+
+        let too_old = LifecycleRuleArgs {
+            bucket_id: None,
+            prefix: Some("exports/".to_string()),
+            glob: None,
+            tag: None,
+            age: 3_000_000_000,
+            action: LifecycleAction::Delete,
+        };
+        assert!(lifecycle::create(&db, owner_id, too_old).await.is_err());
+
+        let pid = lifecycle::create(&db, owner_id, args).await?;
+        let rule = lifecycle::get(&db, &pid).await?;
+        let outcomes = lifecycle::apply(&db, &root_dir, None, &rule).await?;
+
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(
+            outcomes[0].path,
+            client::object_path(details.id(), "exports/old.csv")
+        );
+        assert!(!namespace.join("exports/old.csv").exists());
+        assert!(namespace.join("exports/new.csv").exists());
+        assert!(namespace.join("keep/old.csv").exists());
+
+        assert_eq!(trash::list(&db, owner_id).await?.len(), 1);
+        let entries = audit::list(&db, owner_id, 10).await?;
+        assert_eq!(entries[0].action(), "lifecycle:trash");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transition_to_cold_volume() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Transition Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let test_dir = crate::root_dir()?
+            .join("test-assets/transition")
+            .join(details.id());
+        let (root_dir, cold_volume) = (test_dir.join("root"), test_dir.join("cold"));
+        let namespace = client::namespace_dir(&root_dir, details.id());
+        tokio::fs::create_dir_all(namespace.join("logs")).await?;
+
+        let old = SystemTime::now() - Duration::from_secs(10 * 24 * 60 * 60);
+        let mut paths = vec![];
+        for name in ["logs/a.log", "logs/b.log"] {
+            tokio::fs::write(namespace.join(name), "entry").await?;
+            let file = std::fs::File::options()
+                .write(true)
+                .open(namespace.join(name))?;
+            file.set_modified(old)?;
+
+            let path = client::object_path(details.id(), name);
+            assets::index_file(&db, &root_dir, &path, owner_id, None).await?;
+            paths.push(path);
+        }
+
+        let args = LifecycleRuleArgs {
+            bucket_id: None,
+            prefix: Some("logs/".to_string()),
+            glob: None,
+            tag: None,
+            age: 7,
+            action: LifecycleAction::Transition,
+        };
+
+        let pid = lifecycle::create(&db, owner_id, args).await?;
+        let rule = lifecycle::get(&db, &pid).await?;
+
+        // without a cold volume, each object fails on its own and the rule still runs to the end.
+        let outcomes = lifecycle::apply(&db, &root_dir, None, &rule).await?;
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|outcome| outcome.error.is_some()));
+        assert!(namespace.join("logs/a.log").exists());
+
+        let outcomes = lifecycle::apply(&db, &root_dir, Some(&cold_volume), &rule).await?;
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|outcome| outcome.error.is_none()));
+
+        for path in &paths {
+            let asset = assets::get(&db, path)
+                .await?
+                .expect("transitioned file is indexed");
+            assert_eq!(asset.storage_class(), StorageClass::Cold);
+            assert_eq!(
+                asset.stored_path(&root_dir, Some(&cold_volume))?,
+                cold_volume.join(path)
+            );
+            assert!(cold_volume.join(path).is_file());
+            assert!(!root_dir.join(path).exists());
+        }
+
+        let folder = client::object_path(details.id(), "logs");
+        let restored = lifecycle::restore_cold(&db, &root_dir, Some(&cold_volume), &folder).await?;
+        assert_eq!(restored, 2);
+
+        for path in &paths {
+            let asset = assets::get(&db, path)
+                .await?
+                .expect("restored file is indexed");
+            assert_eq!(asset.storage_class(), StorageClass::Standard);
+            assert!(root_dir.join(path).is_file());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+use crate::assets::{Asset, AssetType, Metadata, like_escape, relative_path};
+use crate::db::{Database, DbEngine};
+use crate::tags::{self, TagFilter};
+use crate::{buckets, sql_safe};
+use anyhow::anyhow;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE;
+use serde::Serialize;
+
+pub const DEFAULT_MAX_KEYS: usize = 1000;
+
+/// Options of an S3 ListObjectsV2 like listing.
+#[derive(Default)]
+pub struct ListOptions {
+    /// Only list keys beginning with `prefix`.
+    pub prefix: String,
+    /// Keys containing `delimiter` after the prefix are rolled up into a common prefix.
+    pub delimiter: Option<String>,
+    /// Token returned as `next_continuation_token` by a previous page.
+    pub continuation_token: Option<String>,
+    /// Only list keys after this key.
+    pub start_after: Option<String>,
+    /// Maximum number of keys and common prefixes returned. Defaults to [DEFAULT_MAX_KEYS].
+    pub max_keys: Option<usize>,
+    pub bucket_id: Option<i32>,
+    pub tags: Vec<TagFilter>,
+}
+
+#[derive(Serialize)]
+pub struct ListEntry {
+    /// Asset path within its client or bucket namespace. Folder keys end with `/`.
+    pub key: String,
+    /// pid of the bucket holding the asset, if any.
+    pub bucket: Option<String>,
+    pub asset_type: AssetType,
+    pub size: i64,
+    pub content_type: Option<String>,
+    pub last_modified: String,
+    pub checksum: Option<String>,
+    pub metadata: Metadata,
+}
+
+impl From<Asset> for ListEntry {
+    fn from(asset: Asset) -> Self {
+        Self {
+            key: relative_key(&key(&asset)),
+            bucket: buckets::pid_of(asset.path()).map(str::to_string),
+            asset_type: asset.asset_type(),
+            size: asset.size(),
+            content_type: asset.content_type().map(str::to_string),
+            last_modified: asset.updated_at().to_string(),
+            checksum: asset.checksum().map(str::to_string),
+            metadata: asset.metadata().clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ListResult {
+    pub prefix: String,
+    pub delimiter: Option<String>,
+    pub max_keys: usize,
+    /// Number of contents and common prefixes returned.
+    pub key_count: usize,
+    pub is_truncated: bool,
+    pub contents: Vec<ListEntry>,
+    pub common_prefixes: Vec<String>,
+    pub continuation_token: Option<String>,
+    pub next_continuation_token: Option<String>,
+}
+
+/// List an owner's indexed assets, one page at a time.
+pub async fn list(
+    db: &Database,
+    owner_id: i32,
+    options: ListOptions,
+) -> anyhow::Result<ListResult> {
+    let max_keys = options
+        .max_keys
+        .unwrap_or(DEFAULT_MAX_KEYS)
+        .clamp(1, DEFAULT_MAX_KEYS);
+
+    let prefix = options.prefix.trim_start_matches('/').to_string();
+    let delimiter = options.delimiter.filter(|d| !d.is_empty());
+
+    let mut marker = match &options.continuation_token {
+        Some(token) => decode_token(token)?,
+        None => String::new(),
+    };
+
+    if let Some(start_after) = &options.start_after {
+        let start_after = start_after.trim_matches('/');
+        if start_after > marker.as_str() {
+            marker = start_after.to_string();
+        }
+    }
+
+    let mut contents = vec![];
+    let mut common_prefixes: Vec<String> = vec![];
+    let mut is_truncated = false;
+
+    'pages: loop {
+        let batch = fetch(
+            db,
+            owner_id,
+            &prefix,
+            &marker,
+            options.bucket_id,
+            &options.tags,
+            max_keys,
+        )
+        .await?;
+        let exhausted = batch.len() < max_keys;
+
+        for asset in batch {
+            let key = key(&asset);
+            // keys under a common prefix come right after it, and are rolled up into it.
+            if common_prefixes
+                .last()
+                .is_some_and(|common_prefix| key.starts_with(common_prefix.as_str()))
+            {
+                continue;
+            }
+
+            if !key.starts_with(&prefix) || key == prefix {
+                marker = key;
+                continue;
+            }
+
+            let common_prefix = delimiter.as_ref().and_then(|delimiter| {
+                let rest = &key[prefix.len()..];
+                rest.find(delimiter.as_str())
+                    .map(|idx| format!("{prefix}{}", &rest[..idx + delimiter.len()]))
+            });
+
+            if contents.len() + common_prefixes.len() == max_keys {
+                is_truncated = true;
+                break 'pages;
+            }
+
+            match common_prefix {
+                Some(common_prefix) => {
+                    // carry on past every key under the common prefix.
+                    marker = format!("{common_prefix}{}", char::MAX);
+                    common_prefixes.push(common_prefix);
+                }
+                None => {
+                    marker = key;
+                    contents.push(ListEntry::from(asset));
+                }
+            }
+        }
+
+        if exhausted {
+            break;
+        }
+    }
+
+    let next_continuation_token = is_truncated.then(|| encode_token(&marker));
+
+    Ok(ListResult {
+        prefix: relative_key(&prefix),
+        delimiter,
+        max_keys,
+        key_count: contents.len() + common_prefixes.len(),
+        is_truncated,
+        contents,
+        common_prefixes: common_prefixes.iter().map(|p| relative_key(p)).collect(),
+        continuation_token: options.continuation_token,
+        next_continuation_token,
+    })
+}
+
+async fn fetch(
+    db: &Database,
+    owner_id: i32,
+    prefix: &str,
+    marker: &str,
+    bucket_id: Option<i32>,
+    filters: &[TagFilter],
+    limit: usize,
+) -> anyhow::Result<Vec<Asset>> {
+    let mut conditions = String::new();
+    let mut idx = 4;
+    if bucket_id.is_some() {
+        conditions.push_str(&format!(" AND bucket_id = {}", db.placeholder(idx)));
+        idx += 1;
+    }
+
+    conditions.push_str(&tags::filter_conditions(db, filters, idx));
+    let key = key_column(db);
+    let query = sql_safe!(
+        "SELECT * FROM assets WHERE owner_id = {} AND path LIKE {} ESCAPE '!' AND {key} > {}{conditions} ORDER BY {key} LIMIT {limit}",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3)
+    );
+
+    let mut query = sqlx::query_as(query)
+        .bind(owner_id)
+        .bind(format!("{}%", like_escape(prefix.trim_end_matches('/'))))
+        .bind(marker);
+
+    if let Some(bucket_id) = bucket_id {
+        query = query.bind(bucket_id);
+    }
+
+    for value in tags::filter_values(filters) {
+        query = query.bind(value);
+    }
+
+    let assets = query.fetch_all(&**db).await?;
+    Ok(assets)
+}
+
+fn key(asset: &Asset) -> String {
+    match asset.asset_type() {
+        AssetType::File => asset.path().to_string(),
+        AssetType::Folder => format!("{}/", asset.path()),
+    }
+}
+
+/// SQL expression of the listing key of an asset, compared byte-wise so that keys under a common
+/// prefix sort together.
+fn key_column(db: &Database) -> String {
+    let folder = i16::from(AssetType::Folder);
+    match db.engine() {
+        DbEngine::Sqlite => {
+            format!("(CASE WHEN asset_type = {folder} THEN path || '/' ELSE path END)")
+        }
+        DbEngine::Postgres => {
+            format!(
+                "(CASE WHEN asset_type = {folder} THEN path || '/' ELSE path END) COLLATE \"C\""
+            )
+        }
+        DbEngine::Mysql => {
+            format!("(CASE WHEN asset_type = {folder} THEN CONCAT(path, '/') ELSE path END)")
+        }
+    }
+}
+
+/// A key (or prefix) within its client or bucket namespace, keeping the trailing `/` of folders.
+pub(crate) fn relative_key(key: &str) -> String {
+    match relative_path(key) {
+        relative if key.ends_with('/') && !relative.is_empty() => format!("{relative}/"),
+        relative => relative.to_string(),
+    }
+}
+
+fn encode_token(path: &str) -> String {
+    URL_SAFE.encode(path)
+}
+
+fn decode_token(token: &str) -> anyhow::Result<String> {
+    let decoded = URL_SAFE
+        .decode(token)
+        .map_err(|_| anyhow!("invalid continuation token"))?;
+
+    Ok(String::from_utf8(decoded)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets;
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::listing::{self, ListOptions};
+    use crate::secrets::AppSecrets;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_list_with_delimiter_and_pages() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Listing Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let root = details.id();
+        for folder in ["docs", "docs-x", "docs/old", "photos"] {
+            assets::index_folder(&db, &format!("{root}/{folder}"), owner_id, None).await?;
+        }
+
+        for file in [
+            "a.txt",
+            "b.txt",
+            "docs/c.txt",
+            "docs-x/f.txt",
+            "docs/old/d.txt",
+            "photos/e.jpg",
+        ] {
+            let record = assets::AssetRecord {
+                path: format!("{root}/{file}"),
+                asset_type: assets::AssetType::File,
+                size: 1,
+                content_type: None,
+                checksum: None,
+                owner_id,
+                bucket_id: None,
+            };
+
+            assets::upsert(&db, record).await?;
+        }
+
+        let options = |token: Option<String>| ListOptions {
+            prefix: format!("{root}/"),
+            delimiter: Some("/".to_string()),
+            continuation_token: token,
+            max_keys: Some(2),
+            ..Default::default()
+        };
+
+        let page = listing::list(&db, owner_id, options(None)).await?;
+        assert!(page.is_truncated);
+        let keys: Vec<_> = page.contents.iter().map(|e| e.key.clone()).collect();
+        assert_eq!(keys, vec![format!("{root}/a.txt"), format!("{root}/b.txt")]);
+
+        // `docs-x/` sorts before `docs/`, and each is listed once
+        let page = listing::list(&db, owner_id, options(page.next_continuation_token)).await?;
+        assert!(page.is_truncated);
+        assert!(page.contents.is_empty());
+        assert_eq!(
+            page.common_prefixes,
+            vec![format!("{root}/docs-x/"), format!("{root}/docs/")]
+        );
+
+        let page = listing::list(&db, owner_id, options(page.next_continuation_token)).await?;
+        assert!(!page.is_truncated);
+        assert_eq!(page.common_prefixes, vec![format!("{root}/photos/")]);
+
+        let everything = ListOptions {
+            prefix: format!("{root}/"),
+            delimiter: Some("/".to_string()),
+            ..Default::default()
+        };
+
+        let page = listing::list(&db, owner_id, everything).await?;
+        assert_eq!(page.key_count, 5);
+
+        let all = ListOptions {
+            prefix: format!("{root}/docs"),
+            ..Default::default()
+        };
+
+        let page = listing::list(&db, owner_id, all).await?;
+        assert_eq!(page.key_count, 6);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+use crate::assets::{self, Asset, AssetType};
+use crate::buckets::Bucket;
+use crate::db::Database;
+use crate::sql_safe;
+use crate::utils::{DbBool, unix_timestamp};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::FromRow;
+use std::fmt::Display;
+
+/// How strictly a retention period is enforced.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+pub enum RetentionMode {
+    /// The owner may shorten or remove the retention, or alter the object, by explicitly
+    /// bypassing governance.
+    #[default]
+    Governance,
+    /// Nobody can shorten the retention or alter the object until it expires.
+    Compliance,
+}
+
+impl From<i16> for RetentionMode {
+    fn from(value: i16) -> Self {
+        use RetentionMode::*;
+
+        match value {
+            0 => Governance,
+            1 => Compliance,
+            _ => Default::default(),
+        }
+    }
+}
+
+impl From<RetentionMode> for i16 {
+    fn from(value: RetentionMode) -> Self {
+        use RetentionMode::*;
+
+        match value {
+            Governance => 0,
+            Compliance => 1,
+        }
+    }
+}
+
+/// Retention applied to files written to a bucket.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DefaultRetention {
+    pub mode: RetentionMode,
+    pub days: u32,
+}
+
+/// Retention of a single object.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Retention {
+    pub mode: RetentionMode,
+    /// Unix time (in seconds) until which the object is retained.
+    pub retain_until: i64,
+}
+
+/// Retention and legal hold of an object. Locked objects can't be overwritten, moved or
+/// deleted.
+#[derive(FromRow, Serialize, Clone, Default)]
+pub struct ObjectLock {
+    #[serde(serialize_with = "serialize_mode")]
+    retention_mode: Option<i16>,
+    /// Unix time (in seconds) until which the object is retained.
+    retain_until: Option<i64>,
+    #[sqlx(try_from = "i16")]
+    legal_hold: DbBool,
+}
+
+impl ObjectLock {
+    pub fn retention_mode(&self) -> Option<RetentionMode> {
+        self.retention_mode.map(RetentionMode::from)
+    }
+
+    pub fn retain_until(&self) -> Option<i64> {
+        self.retain_until
+    }
+
+    pub fn legal_hold(&self) -> bool {
+        self.legal_hold.get()
+    }
+
+    /// Why the object can't be altered right now, if it can't. Governance retention doesn't
+    /// apply when `bypass_governance` is set.
+    pub fn violation(&self, bypass_governance: bool) -> anyhow::Result<Option<&'static str>> {
+        if self.legal_hold() {
+            return Ok(Some("object is under legal hold"));
+        }
+
+        match (self.retention_mode(), self.retain_until) {
+            (Some(mode), Some(until)) if until > unix_timestamp()? => match mode {
+                RetentionMode::Compliance => Ok(Some("object is under compliance retention")),
+                RetentionMode::Governance if !bypass_governance => {
+                    Ok(Some("object is under governance retention"))
+                }
+                RetentionMode::Governance => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+}
+
+fn serialize_mode<S: Serializer>(mode: &Option<i16>, serializer: S) -> Result<S::Ok, S::Error> {
+    mode.map(RetentionMode::from).serialize(serializer)
+}
+
+/// An object that can't be altered, and why.
+#[derive(Debug)]
+pub struct Locked {
+    pub path: String,
+    pub reason: &'static str,
+}
+
+impl Display for Locked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+/// Find a locked file at or under `path`. Returns `None` when everything may be altered.
+pub async fn find_locked(
+    db: &Database,
+    path: &str,
+    bypass_governance: bool,
+) -> anyhow::Result<Option<Locked>> {
+    let mut assets = assets::descendants(db, path).await?;
+    if let Some(asset) = assets::get(db, path).await? {
+        assets.push(asset);
+    }
+
+    for asset in assets {
+        if let Some(reason) = asset.lock().violation(bypass_governance)? {
+            return Ok(Some(Locked {
+                path: asset.path().to_string(),
+                reason,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Change the retention of a file. Retention can always be extended, while shortening it or
+/// weakening its mode requires governance retention and `bypass_governance`.
+pub async fn set_retention(
+    db: &Database,
+    asset: &Asset,
+    retention: Retention,
+    bypass_governance: bool,
+) -> anyhow::Result<Asset> {
+    let Retention { mode, retain_until } = retention;
+    if asset.asset_type() != AssetType::File {
+        return Err(anyhow!("only files can be retained"));
+    }
+
+    let lock = asset.lock();
+    let now = unix_timestamp()?;
+    if let (Some(current), Some(until)) = (lock.retention_mode(), lock.retain_until())
+        && until > now
+    {
+        let weakened = retain_until < until
+            || (current == RetentionMode::Compliance && mode == RetentionMode::Governance);
+
+        if weakened && (current == RetentionMode::Compliance || !bypass_governance) {
+            return Err(anyhow!("retention can only be extended"));
+        }
+    }
+
+    update_retention(db, asset.path(), Some(mode), Some(retain_until)).await
+}
+
+/// Place or lift a legal hold on a file.
+pub async fn set_legal_hold(db: &Database, asset: &Asset, hold: bool) -> anyhow::Result<Asset> {
+    if asset.asset_type() != AssetType::File {
+        return Err(anyhow!("only files can be held"));
+    }
+
+    let query = sql_safe!(
+        "UPDATE assets SET legal_hold = {} WHERE path = {}",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    sqlx::query(query)
+        .bind(i16::from(DbBool::from(hold)))
+        .bind(asset.path())
+        .execute(&**db)
+        .await?;
+
+    reload(db, asset.path()).await
+}
+
+/// Lock a newly written file: `retention` when given, else the default retention of its bucket.
+pub async fn apply(
+    db: &Database,
+    path: &str,
+    bucket: Option<&Bucket>,
+    retention: Option<Retention>,
+    legal_hold: bool,
+) -> anyhow::Result<()> {
+    let default = bucket.and_then(Bucket::default_retention);
+    let retention = match (retention, default) {
+        (Some(retention), _) => Some(retention),
+        (None, Some(DefaultRetention { mode, days })) => Some(Retention {
+            mode,
+            retain_until: unix_timestamp()? + days as i64 * 24 * 60 * 60,
+        }),
+        (None, None) => None,
+    };
+
+    if let Some(Retention { mode, retain_until }) = retention {
+        update_retention(db, path, Some(mode), Some(retain_until)).await?;
+    }
+
+    if legal_hold {
+        let asset = reload(db, path).await?;
+        set_legal_hold(db, &asset, true).await?;
+    }
+
+    Ok(())
+}
+
+async fn update_retention(
+    db: &Database,
+    path: &str,
+    mode: Option<RetentionMode>,
+    retain_until: Option<i64>,
+) -> anyhow::Result<Asset> {
+    let query = sql_safe!(
+        "UPDATE assets SET retention_mode = {}, retain_until = {} WHERE path = {}",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3)
+    );
+
+    sqlx::query(query)
+        .bind(mode.map(i16::from))
+        .bind(retain_until)
+        .bind(path)
+        .execute(&**db)
+        .await?;
+
+    reload(db, path).await
+}
+
+async fn reload(db: &Database, path: &str) -> anyhow::Result<Asset> {
+    assets::get(db, path)
+        .await?
+        .ok_or(anyhow!("asset {path} is not indexed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets::{self, AssetRecord, AssetType};
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::lock::{self, Retention, RetentionMode};
+    use crate::secrets::AppSecrets;
+    use crate::utils::unix_timestamp;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_retention_and_legal_hold() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Lock Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let folder = client::object_path(details.id(), "records");
+        let record = AssetRecord {
+            path: format!("{folder}/2025.csv"),
+            asset_type: AssetType::File,
+            size: 1,
+            content_type: None,
+            checksum: None,
+            owner_id,
+            bucket_id: None,
+        };
+
+        assets::index_folder(&db, &folder, owner_id, None).await?;
+        let file = assets::upsert(&db, record).await?;
+        let day = 24 * 60 * 60;
+        let until = unix_timestamp()? + day;
+
+        let retention = |mode, retain_until| Retention { mode, retain_until };
+        let governance = retention(RetentionMode::Governance, until);
+        let file = lock::set_retention(&db, &file, governance, false).await?;
+        assert!(lock::find_locked(&db, &folder, false).await?.is_some());
+        assert!(lock::find_locked(&db, &folder, true).await?.is_none());
+
+        // Governance can be shortened when bypassed, compliance can't.
+        let shortened = retention(RetentionMode::Governance, until - 1);
+        assert!(
+            lock::set_retention(&db, &file, shortened, false)
+                .await
+                .is_err()
+        );
+        let file = lock::set_retention(&db, &file, shortened, true).await?;
+
+        let compliance = retention(RetentionMode::Compliance, until);
+        let file = lock::set_retention(&db, &file, compliance, false).await?;
+        assert!(lock::find_locked(&db, file.path(), true).await?.is_some());
+
+        let shortened = retention(RetentionMode::Compliance, until - 1);
+        assert!(
+            lock::set_retention(&db, &file, shortened, true)
+                .await
+                .is_err()
+        );
+
+        let extended = retention(RetentionMode::Compliance, until + day);
+        let file = lock::set_retention(&db, &file, extended, false).await?;
+        assert_eq!(file.lock().retain_until(), Some(until + day));
+
+        let file = lock::set_legal_hold(&db, &file, true).await?;
+        let locked = lock::find_locked(&db, file.path(), true).await?.unwrap();
+        assert_eq!(locked.reason, "object is under legal hold");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use crate::client;
+use crate::db::Database;
+use crate::{assets, mb_to_bytes, sql_safe, trash};
+
+/// Bytes counted against a client's quota: its indexed files plus what's in its trash.
+pub async fn usage(db: &Database, client_id: i32) -> anyhow::Result<i64> {
+    let owner_id = client::owner_id(db, client_id).await?;
+    let stored = assets::usage(db, owner_id).await?;
+    let trashed = trash::usage(db, owner_id).await?;
+
+    Ok(stored + trashed)
+}
+
+/// Check whether a client can store `bytes` more without exceeding its `max_bucket_size`.
+/// Clients without `max_bucket_size` have no quota.
+pub async fn allows(db: &Database, client_id: i32, bytes: u64) -> anyhow::Result<bool> {
+    match limit(db, client_id).await? {
+        Some(limit) => {
+            let used = usage(db, client_id).await? as u64;
+            Ok(used + bytes <= limit)
+        }
+        None => Ok(true),
+    }
+}
+
+/// A client's `max_bucket_size` in bytes, if any.
+pub async fn limit(db: &Database, client_id: i32) -> anyhow::Result<Option<u64>> {
+    let query = sql_safe!(
+        "SELECT max_bucket_size FROM clients WHERE id = {} LIMIT 1",
+        db.placeholder(1)
+    );
+
+    let max_size: Option<f64> = sqlx::query_scalar(query)
+        .bind(client_id)
+        .fetch_one(&**db)
+        .await?;
+
+    Ok(max_size.map(|max_size| mb_to_bytes(max_size) as u64))
+}
@@ -0,0 +1,364 @@
+use crate::assets::{self, Asset, AssetType, StorageClass};
+use crate::db::Database;
+use crate::utils::unix_timestamp;
+use crate::{audit, sql_safe};
+use serde::Serialize;
+use sqlx::FromRow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncReadExt;
+
+/// Rate (in bytes per second) at which objects are read when none is configured.
+pub const DEFAULT_RATE: u64 = 8 * 1024 * 1000;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum ScrubStatus {
+    Healthy,
+    /// The file doesn't match its recorded checksum.
+    Corrupted,
+    /// The file is indexed but missing from storage.
+    Missing,
+    /// The file was corrupted or missing, and restored from a replica.
+    Repaired,
+    /// The file couldn't be checked or repaired, e.g. because it's unreadable.
+    Failed,
+}
+
+#[derive(Serialize)]
+pub struct ScrubFinding {
+    pub path: String,
+    pub status: ScrubStatus,
+    /// Why the file couldn't be checked or repaired.
+    pub error: Option<String>,
+}
+
+/// Outcome of a [scrub] batch.
+#[derive(Serialize, Default)]
+pub struct ScrubReport {
+    /// Number of files checked.
+    pub scanned: usize,
+    /// Files which weren't healthy.
+    pub findings: Vec<ScrubFinding>,
+    /// Whether the batch reached the last file, completing the pass.
+    pub completed: bool,
+}
+
+/// Where the current scrub pass stands. Saved after every file so a pass resumes where it
+/// stopped.
+#[derive(FromRow, Serialize, Default)]
+pub struct ScrubProgress {
+    /// Path of the last checked file of the current pass.
+    pub last_path: Option<String>,
+    /// Unix time (in seconds) the current pass started, if one is underway.
+    pub started_at: Option<i64>,
+    /// Unix time (in seconds) the last pass completed.
+    pub finished_at: Option<i64>,
+}
+
+pub async fn progress(db: &Database) -> anyhow::Result<ScrubProgress> {
+    let query =
+        sql_safe!("SELECT last_path, started_at, finished_at FROM scrub_progress WHERE id = 1");
+    let progress = sqlx::query_as(query).fetch_optional(&**db).await?;
+
+    Ok(progress.unwrap_or_default())
+}
+
+/// Forget the current pass, so that the next batch starts from the first file.
+pub async fn restart(db: &Database) -> anyhow::Result<()> {
+    save_progress(db, None, None).await
+}
+
+/// Check up to `limit` indexed files following the last checked one, reading at most `rate`
+/// bytes per second. Damaged files are restored from the first replica holding a healthy copy,
+/// and every finding is recorded in the owner's audit trail.
+pub async fn scrub(
+    db: &Database,
+    root_dir: &Path,
+    replicas: &[PathBuf],
+    rate: u64,
+    limit: usize,
+) -> anyhow::Result<ScrubReport> {
+    let progress = progress(db).await?;
+    let started_at = match progress.started_at {
+        Some(started_at) => started_at,
+        None => unix_timestamp()?,
+    };
+
+    let after = progress.last_path.unwrap_or_default();
+    let files = next_files(db, &after, limit).await?;
+
+    let mut report = ScrubReport {
+        completed: files.len() < limit,
+        ..Default::default()
+    };
+
+    for file in files {
+        // a file that can't be read is reported and skipped, so the pass still moves on.
+        let (status, error) = match check(db, root_dir, &file, replicas, rate).await {
+            Ok(status) => (status, None),
+            Err(err) => (ScrubStatus::Failed, Some(err.to_string())),
+        };
+
+        if status != ScrubStatus::Healthy {
+            let action = format!("scrub:{status:?}").to_lowercase();
+            audit::record(db, file.owner_id(), &action, file.path(), error.clone()).await?;
+
+            report.findings.push(ScrubFinding {
+                path: file.path().to_string(),
+                status,
+                error,
+            });
+        }
+
+        report.scanned += 1;
+        save_progress(db, Some(file.path()), Some(started_at)).await?;
+    }
+
+    if report.completed {
+        finish(db).await?;
+    }
+
+    Ok(report)
+}
+
+/// Check a file against its recorded checksum, repairing it from a replica when damaged. Files
+/// without a checksum are considered healthy, and so are files replaced since `file` was
+/// fetched: the next pass checks their new content.
+pub async fn check(
+    db: &Database,
+    root_dir: &Path,
+    file: &Asset,
+    replicas: &[PathBuf],
+    rate: u64,
+) -> anyhow::Result<ScrubStatus> {
+    let Some(expected) = file.checksum() else {
+        return Ok(ScrubStatus::Healthy);
+    };
+
+    let path = root_dir.join(file.path());
+    let modified = modified(&path).await;
+    let status = if !path.is_file() {
+        ScrubStatus::Missing
+    } else if throttled_checksum(&path, rate).await? != expected {
+        ScrubStatus::Corrupted
+    } else {
+        return Ok(ScrubStatus::Healthy);
+    };
+
+    for replica in replicas {
+        let copy = replica.join(file.path());
+        if copy.is_file() && throttled_checksum(&copy, rate).await? == expected {
+            // uploads move new content in place before indexing it, so the mismatch may be a
+            // fresh upload rather than damage.
+            if replaced(db, file, &path, modified).await? {
+                return Ok(ScrubStatus::Healthy);
+            }
+
+            repair(&copy, &path).await?;
+            return Ok(ScrubStatus::Repaired);
+        }
+    }
+
+    Ok(status)
+}
+
+/// Check whether a file was written or reindexed since `file` was fetched from the index, and
+/// its modification time read.
+async fn replaced(
+    db: &Database,
+    file: &Asset,
+    path: &Path,
+    modified_before: Option<SystemTime>,
+) -> anyhow::Result<bool> {
+    let reindexed = match assets::get(db, file.path()).await? {
+        Some(current) => {
+            current.checksum() != file.checksum() || current.updated_at() != file.updated_at()
+        }
+        None => true,
+    };
+
+    Ok(reindexed || modified(path).await != modified_before)
+}
+
+async fn modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+/// Replace `target` with a healthy copy. The copy is staged next to the target then renamed
+/// over it, so readers never see a partial file.
+async fn repair(copy: &Path, target: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let staged = target.with_file_name(format!(".{name}.scrub-repair"));
+    tokio::fs::copy(copy, &staged).await?;
+    tokio::fs::rename(&staged, target).await?;
+
+    Ok(())
+}
+
+/// Compute the hex encoded blake3 digest of a file, reading at most `rate` bytes per second.
+async fn throttled_checksum(path: &Path, rate: u64) -> anyhow::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0; 64 * 1024];
+    let started = Instant::now();
+    let mut total = 0;
+
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..read]);
+        total += read as u64;
+
+        let due = Duration::from_secs_f64(total as f64 / rate.max(1) as f64);
+        if let Some(ahead) = due.checked_sub(started.elapsed()) {
+            tokio::time::sleep(ahead).await;
+        }
+    }
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Files after `after`, in path order. Files in the cold volume aren't scrubbed, since replicas
+/// mirror the storage root only.
+async fn next_files(db: &Database, after: &str, limit: usize) -> anyhow::Result<Vec<Asset>> {
+    let query = sql_safe!(
+        "SELECT * FROM assets WHERE asset_type = {} AND storage_class = {} AND path > {} ORDER BY path LIMIT {limit}",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3)
+    );
+
+    let files = sqlx::query_as(query)
+        .bind(i16::from(AssetType::File))
+        .bind(i16::from(StorageClass::Standard))
+        .bind(after)
+        .fetch_all(&**db)
+        .await?;
+
+    Ok(files)
+}
+
+async fn save_progress(
+    db: &Database,
+    last_path: Option<&str>,
+    started_at: Option<i64>,
+) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "UPDATE scrub_progress SET last_path = {}, started_at = {} WHERE id = 1",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    sqlx::query(query)
+        .bind(last_path)
+        .bind(started_at)
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+async fn finish(db: &Database) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "UPDATE scrub_progress SET last_path = NULL, started_at = NULL, finished_at = {} WHERE id = 1",
+        db.placeholder(1)
+    );
+
+    sqlx::query(query)
+        .bind(unix_timestamp()?)
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets;
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::root_dir;
+    use crate::scrub::{self, DEFAULT_RATE, ScrubStatus};
+    use crate::secrets::AppSecrets;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_scrub_and_repair() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Scrub Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let root_dir = root_dir()?;
+        let replica = root_dir
+            .join("tmp")
+            .join(format!("replica-{}", details.id()));
+        let path = client::object_path(details.id(), "scrub.txt");
+        tokio::fs::create_dir_all(client::namespace_dir(&root_dir, details.id())).await?;
+        tokio::fs::write(root_dir.join(&path), "healthy").await?;
+        let file = assets::index_file(&db, &root_dir, &path, owner_id, None).await?;
+
+        let replicas = [replica.clone()];
+        let check = async || scrub::check(&db, &root_dir, &file, &replicas, DEFAULT_RATE).await;
+        assert_eq!(check().await?, ScrubStatus::Healthy);
+
+        tokio::fs::write(root_dir.join(&path), "rotten!").await?;
+        assert_eq!(check().await?, ScrubStatus::Corrupted);
+
+        tokio::fs::remove_file(root_dir.join(&path)).await?;
+        assert_eq!(check().await?, ScrubStatus::Missing);
+
+        tokio::fs::create_dir_all(client::namespace_dir(&replica, details.id())).await?;
+        tokio::fs::write(replica.join(&path), "healthy").await?;
+        assert_eq!(check().await?, ScrubStatus::Repaired);
+        assert_eq!(
+            tokio::fs::read_to_string(root_dir.join(&path)).await?,
+            "healthy"
+        );
+        assert_eq!(check().await?, ScrubStatus::Healthy);
+
+        // Files replaced since they were fetched aren't repaired over
+        tokio::fs::write(root_dir.join(&path), "updated").await?;
+        assets::index_file(&db, &root_dir, &path, owner_id, None).await?;
+        assert_eq!(check().await?, ScrubStatus::Healthy);
+        assert_eq!(
+            tokio::fs::read_to_string(root_dir.join(&path)).await?,
+            "updated"
+        );
+
+        // Files that can't be repaired are reported without stopping the pass
+        let broken = client::object_path(details.id(), "a-broken.txt");
+        tokio::fs::write(root_dir.join(&broken), "healthy").await?;
+        assets::index_file(&db, &root_dir, &broken, owner_id, None).await?;
+        tokio::fs::remove_file(root_dir.join(&broken)).await?;
+        tokio::fs::create_dir_all(root_dir.join(&broken).join("taken")).await?;
+        tokio::fs::write(replica.join(&broken), "healthy").await?;
+
+        let namespace = client::object_path(details.id(), "");
+        super::save_progress(&db, Some(&namespace), None).await?;
+        let report = scrub::scrub(&db, &root_dir, &replicas, DEFAULT_RATE, 2).await?;
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].path, broken);
+        assert_eq!(report.findings[0].status, ScrubStatus::Failed);
+        assert!(report.findings[0].error.is_some());
+
+        let progress = scrub::progress(&db).await?;
+        assert_eq!(progress.last_path, Some(path));
+
+        scrub::restart(&db).await?;
+        tokio::fs::remove_dir_all(replica).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use crate::assets::{Asset, like_escape, relative_path};
+use crate::db::{Database, DbEngine};
+use crate::listing::{ListEntry, relative_key};
+use crate::{sql_safe, tags};
+use anyhow::anyhow;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE;
+use serde::Serialize;
+
+pub const DEFAULT_MAX_RESULTS: usize = 50;
+pub const MAX_RESULTS: usize = 1000;
+
+/// Shortest word InnoDB full-text indexes hold (`innodb_ft_min_token_size`).
+const MYSQL_MIN_TOKEN_LEN: usize = 3;
+/// Words InnoDB full-text indexes leave out by default (`INNODB_FT_DEFAULT_STOPWORD`).
+const MYSQL_STOPWORDS: &[&str] = &[
+    "a", "about", "an", "are", "as", "at", "be", "by", "com", "de", "en", "for", "from", "how",
+    "i", "in", "is", "it", "la", "of", "on", "or", "that", "the", "this", "to", "was", "what",
+    "when", "where", "who", "will", "with", "und", "www",
+];
+
+/// Options of a search over an owner's assets.
+#[derive(Default)]
+pub struct SearchOptions {
+    /// Words to look for. Assets match when they contain every word, or a word starting with it.
+    pub query: String,
+    /// Only search assets whose path begins with `prefix`.
+    pub prefix: String,
+    pub bucket_id: Option<i32>,
+    /// Token returned as `next_continuation_token` by a previous page.
+    pub continuation_token: Option<String>,
+    /// Maximum number of results returned. Defaults to [DEFAULT_MAX_RESULTS].
+    pub max_results: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResult {
+    pub query: String,
+    pub prefix: String,
+    pub max_results: usize,
+    pub is_truncated: bool,
+    /// Matching assets, most relevant first.
+    pub results: Vec<ListEntry>,
+    pub continuation_token: Option<String>,
+    pub next_continuation_token: Option<String>,
+}
+
+/// Write the search document of the asset with id `asset_id`, from its path, metadata and tags.
+pub async fn index(db: &Database, asset_id: i32) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "SELECT * FROM assets WHERE id = {} LIMIT 1",
+        db.placeholder(1)
+    );
+    let asset: Option<Asset> = sqlx::query_as(query)
+        .bind(asset_id)
+        .fetch_optional(&**db)
+        .await?;
+    let Some(asset) = asset else {
+        return Ok(());
+    };
+
+    let tags = tags::get(db, asset_id).await?;
+    let mut text = vec![relative_path(asset.path()).to_string()];
+    for (key, value) in asset.metadata().iter().chain(tags.iter()) {
+        text.push(key.clone());
+        text.push(value.clone());
+    }
+
+    let document = words(&text.join(" ")).join(" ");
+    let (p1, p2) = (db.placeholder(1), db.placeholder(2));
+    // replaced in a single statement, as the asset may be indexed concurrently.
+    let query = match db.engine() {
+        DbEngine::Sqlite => {
+            sql_safe!("INSERT OR REPLACE INTO asset_search (rowid, document) VALUES ({p1}, {p2})")
+        }
+        DbEngine::Postgres => sql_safe!(
+            "INSERT INTO asset_search (asset_id, document) VALUES ({p1}, {p2}) ON CONFLICT (asset_id) DO UPDATE SET document = excluded.document"
+        ),
+        DbEngine::Mysql => sql_safe!(
+            "INSERT INTO asset_search (asset_id, document) VALUES ({p1}, {p2}) ON DUPLICATE KEY UPDATE document = VALUES(document)"
+        ),
+    };
+    sqlx::query(query)
+        .bind(asset_id)
+        .bind(document)
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+/// Index every asset missing from the search index, e.g. after a restore or an upgrade. Returns
+/// the number of assets indexed.
+pub async fn backfill(db: &Database) -> anyhow::Result<usize> {
+    let id_column = id_column(db);
+    let query = sql_safe!(
+        "SELECT id FROM assets WHERE id NOT IN (SELECT {id_column} FROM asset_search) ORDER BY id"
+    );
+
+    let ids: Vec<i32> = sqlx::query_scalar(query).fetch_all(&**db).await?;
+    for id in &ids {
+        index(db, *id).await?;
+    }
+
+    Ok(ids.len())
+}
+
+/// Search an owner's assets, one page at a time.
+pub async fn search(
+    db: &Database,
+    owner_id: i32,
+    options: SearchOptions,
+) -> anyhow::Result<SearchResult> {
+    let max_results = options
+        .max_results
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+        .clamp(1, MAX_RESULTS);
+
+    let terms = words(&options.query);
+    if terms.is_empty() {
+        return Err(anyhow!("search query must contain letters or digits"));
+    }
+
+    let offset = match &options.continuation_token {
+        Some(token) => decode_token(token)?,
+        None => 0,
+    };
+
+    let prefix = options.prefix.trim_start_matches('/').to_string();
+    let mut conditions = String::new();
+    if options.bucket_id.is_some() {
+        conditions.push_str(&format!(" AND assets.bucket_id = {}", db.placeholder(4)));
+    }
+
+    // InnoDB doesn't index short words and stopwords, so on MySQL those are matched against the
+    // search document with `LIKE` instead, as prefixes of its words.
+    let (terms, unindexed): (Vec<_>, Vec<_>) = match db.engine() {
+        DbEngine::Mysql => terms.into_iter().partition(|term| mysql_indexes(term)),
+        _ => (terms, vec![]),
+    };
+
+    for _ in &unindexed {
+        conditions.push_str(" AND CONCAT(' ', asset_search.document) LIKE ?");
+    }
+
+    // one more result than requested tells whether there's another page.
+    let limit = max_results + 1;
+    let (query, matched) = match db.engine() {
+        DbEngine::Sqlite => (
+            sql_safe!(
+                "SELECT assets.* FROM asset_search JOIN assets ON assets.id = asset_search.rowid WHERE asset_search MATCH {} AND assets.owner_id = {} AND assets.path LIKE {} ESCAPE '!'{conditions} ORDER BY asset_search.rank, assets.path LIMIT {limit} OFFSET {offset}",
+                db.placeholder(1),
+                db.placeholder(2),
+                db.placeholder(3)
+            ),
+            terms
+                .iter()
+                .map(|term| format!("{term}*"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        DbEngine::Postgres => (
+            sql_safe!(
+                "SELECT assets.* FROM asset_search JOIN assets ON assets.id = asset_search.asset_id WHERE asset_search.terms @@ to_tsquery('simple', {}) AND assets.owner_id = {} AND assets.path LIKE {} ESCAPE '!'{conditions} ORDER BY ts_rank(asset_search.terms, to_tsquery('simple', {})) DESC, assets.path LIMIT {limit} OFFSET {offset}",
+                db.placeholder(1),
+                db.placeholder(2),
+                db.placeholder(3),
+                db.placeholder(1)
+            ),
+            terms
+                .iter()
+                .map(|term| format!("{term}:*"))
+                .collect::<Vec<_>>()
+                .join(" & "),
+        ),
+        DbEngine::Mysql if terms.is_empty() => (
+            sql_safe!(
+                "SELECT assets.* FROM asset_search JOIN assets ON assets.id = asset_search.asset_id WHERE assets.owner_id = ? AND assets.path LIKE ? ESCAPE '!'{conditions} ORDER BY assets.path LIMIT {limit} OFFSET {offset}"
+            ),
+            String::new(),
+        ),
+        DbEngine::Mysql => (
+            sql_safe!(
+                "SELECT assets.* FROM asset_search JOIN assets ON assets.id = asset_search.asset_id WHERE MATCH (asset_search.document) AGAINST ({} IN BOOLEAN MODE) AND assets.owner_id = {} AND assets.path LIKE {} ESCAPE '!'{conditions} ORDER BY MATCH (asset_search.document) AGAINST ({} IN BOOLEAN MODE) DESC, assets.path LIMIT {limit} OFFSET {offset}",
+                db.placeholder(1),
+                db.placeholder(2),
+                db.placeholder(3),
+                db.placeholder(5)
+            ),
+            terms
+                .iter()
+                .map(|term| format!("+{term}*"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    };
+
+    let mut query = sqlx::query_as(query);
+    if !terms.is_empty() {
+        query = query.bind(matched.clone());
+    }
+
+    query = query
+        .bind(owner_id)
+        .bind(format!("{}%", like_escape(&prefix)));
+
+    if let Some(bucket_id) = options.bucket_id {
+        query = query.bind(bucket_id);
+    }
+
+    // words are letters and digits only, so they need no escaping.
+    for term in &unindexed {
+        query = query.bind(format!("% {term}%"));
+    }
+
+    // mysql placeholders are positional, so the relevance clause needs its own value.
+    if let DbEngine::Mysql = db.engine()
+        && !terms.is_empty()
+    {
+        query = query.bind(matched);
+    }
+
+    let mut assets: Vec<Asset> = query.fetch_all(&**db).await?;
+    let is_truncated = assets.len() > max_results;
+    assets.truncate(max_results);
+
+    let next_continuation_token = match is_truncated {
+        true => Some(encode_token(offset + max_results)),
+        false => None,
+    };
+
+    Ok(SearchResult {
+        query: options.query,
+        prefix: relative_key(&prefix),
+        max_results,
+        is_truncated,
+        results: assets.into_iter().map(ListEntry::from).collect(),
+        continuation_token: options.continuation_token,
+        next_continuation_token,
+    })
+}
+
+/// Lowercase words of `text`. Anything but letters and digits separates words, so that paths
+/// and file names are searchable by their parts.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Whether InnoDB's full-text index holds `word`, with the default server settings.
+fn mysql_indexes(word: &str) -> bool {
+    word.chars().count() >= MYSQL_MIN_TOKEN_LEN && !MYSQL_STOPWORDS.contains(&word)
+}
+
+/// Column the search index refers to assets by. FTS5 tables are keyed by rowid.
+fn id_column(db: &Database) -> &'static str {
+    match db.engine() {
+        DbEngine::Sqlite => "rowid",
+        _ => "asset_id",
+    }
+}
+
+fn encode_token(offset: usize) -> String {
+    URL_SAFE.encode(offset.to_string())
+}
+
+fn decode_token(token: &str) -> anyhow::Result<usize> {
+    let decoded = URL_SAFE
+        .decode(token)
+        .map_err(|_| anyhow!("invalid continuation token"))?;
+
+    String::from_utf8(decoded)?
+        .parse()
+        .map_err(|_| anyhow!("invalid continuation token"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::mysql_indexes;
+
+    #[test]
+    fn test_mysql_indexes() {
+        assert!(mysql_indexes("year"));
+        assert!(mysql_indexes("été"));
+        assert!(!mysql_indexes("q3"));
+        assert!(!mysql_indexes("the"));
+    }
+}
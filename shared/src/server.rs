@@ -1,11 +1,14 @@
+use crate::acl::Visibility;
+use crate::assets::Metadata;
 use crate::client::models::Client;
 use crate::db::Database;
 use crate::hasher::{Hashable, Hasher, errors::PayloadVerificationError};
+use crate::lock::Retention;
 use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Sha3_256};
 use std::time::{SystemTime, UNIX_EPOCH};
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 pub fn seconds_from_now(seconds: i64) -> anyhow::Result<i64> {
     let now = SystemTime::now()
@@ -52,8 +55,257 @@ impl UploadInfo {
 }
 
 impl Hashable for UploadInfo {
-    fn key(&self, db: &Database) -> impl Future<Output = anyhow::Result<String>> {
-        async { Client::get_key(db, &self.client_id).await }
+    const KIND: &'static str = "upload";
+
+    async fn key(&self, db: &Database) -> anyhow::Result<String> {
+        Client::get_key(db, &self.client_id).await
+    }
+
+    fn expires(&self) -> i64 {
+        self.exp
+    }
+}
+
+/// Response headers a download should be served with, overriding defaults.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ResponseOverrides {
+    /// `inline` (default) or `attachment`.
+    pub disposition: Option<String>,
+    /// File name suggested to the browser. Defaults to the asset's name.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+}
+
+/// Signed, expiring grant to download a client's file at `path`, or any of its files under
+/// `prefix`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct DownloadInfo {
+    /// Identifies the token, e.g. to count downloads when `max_downloads` is set.
+    pub id: String,
+    pub client_id: String,
+    pub path: Option<String>,
+    /// Storage path of the folder the grant covers, with a trailing `/`.
+    pub prefix: Option<String>,
+    pub max_downloads: Option<u32>,
+    pub overrides: ResponseOverrides,
+    pub exp: i64,
+}
+
+impl DownloadInfo {
+    pub fn sign(&self, key: &str, hasher: &Hasher) -> anyhow::Result<String> {
+        hasher.hash(key, self)
+    }
+
+    pub async fn verify(
+        signed: &str,
+        db: &Database,
+        hasher: &Hasher,
+    ) -> Result<DownloadInfo, PayloadVerificationError> {
+        hasher.verify(signed, db).await
+    }
+
+    /// Check whether the grant covers `path`. Prefixes are folders, so `docs` doesn't cover
+    /// `docs-secret/…`.
+    pub fn allows(&self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        match (&self.path, &self.prefix) {
+            (Some(allowed), _) => allowed.trim_matches('/') == path,
+            (None, Some(prefix)) => {
+                let prefix = prefix.trim_matches('/');
+                !prefix.is_empty() && path.starts_with(&format!("{prefix}/"))
+            }
+            (None, None) => false,
+        }
+    }
+}
+
+impl Hashable for DownloadInfo {
+    const KIND: &'static str = "download";
+
+    async fn key(&self, db: &Database) -> anyhow::Result<String> {
+        Client::get_key(db, &self.client_id).await
+    }
+
+    fn expires(&self) -> i64 {
+        self.exp
+    }
+}
+
+/// Signed grant allowing anyone holding it to list a client's assets under `prefix`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ListInfo {
+    pub client_id: String,
+    pub prefix: String,
+    /// pid of the bucket the listing is limited to.
+    pub bucket: Option<String>,
+    pub exp: i64,
+}
+
+impl ListInfo {
+    pub fn sign(&self, key: &str, hasher: &Hasher) -> anyhow::Result<String> {
+        hasher.hash(key, self)
+    }
+
+    pub async fn verify(
+        signed: &str,
+        db: &Database,
+        hasher: &Hasher,
+    ) -> Result<ListInfo, PayloadVerificationError> {
+        hasher.verify(signed, db).await
+    }
+}
+
+impl Hashable for ListInfo {
+    const KIND: &'static str = "list";
+
+    async fn key(&self, db: &Database) -> anyhow::Result<String> {
+        Client::get_key(db, &self.client_id).await
+    }
+
+    fn expires(&self) -> i64 {
+        self.exp
+    }
+}
+
+/// How a resized image fills the box given by the requested width and height.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum ImageFit {
+    /// Scale to fit within the box, keeping the aspect ratio.
+    #[default]
+    Contain,
+    /// Scale to cover the box, keeping the aspect ratio, then crop the overflow.
+    Cover,
+    /// Stretch to the box's exact dimensions.
+    Fill,
+    /// Like [ImageFit::Contain], but never enlarge the image.
+    Inside,
+}
+
+/// Format an image is converted to before it's downloaded.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum OutputFormat {
+    /// AVIF or WebP when the request's `Accept` header lists them, else the source format.
+    Auto,
+    Jpeg,
+    Png,
+    WebP,
+    Avif,
+}
+
+/// Transformations applied to an image before it's downloaded. The image is cropped first,
+/// then rotated, then resized.
+#[derive(Serialize, Deserialize, Validate, Clone, Default, PartialEq, Debug)]
+pub struct ImageTransform {
+    /// Width (in CSS pixels) of the resized image.
+    #[validate(range(min = 1, max = 4096))]
+    pub w: Option<u32>,
+    /// Height (in CSS pixels) of the resized image.
+    #[validate(range(min = 1, max = 4096))]
+    pub h: Option<u32>,
+    pub fit: Option<ImageFit>,
+    /// Region of the source image to keep, as `x,y,width,height`.
+    #[validate(custom(function = "validate_crop"))]
+    pub crop: Option<String>,
+    /// Clockwise rotation in degrees: 90, 180 or 270.
+    #[validate(custom(function = "validate_rotate"))]
+    pub rotate: Option<u16>,
+    /// Encoding quality of lossy formats.
+    #[validate(range(min = 1, max = 100))]
+    pub quality: Option<u8>,
+    /// Device pixel ratio `w` and `h` are multiplied by.
+    #[validate(range(min = 1.0, max = 4.0))]
+    pub dpr: Option<f32>,
+    /// Format to convert the image to. Defaults to the source format.
+    pub format: Option<OutputFormat>,
+}
+
+impl ImageTransform {
+    /// Whether no transformation was requested.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Parse `crop` into `(x, y, width, height)`.
+    pub fn crop_region(&self) -> anyhow::Result<Option<(u32, u32, u32, u32)>> {
+        let Some(crop) = &self.crop else {
+            return Ok(None);
+        };
+
+        let values = crop
+            .split(',')
+            .map(|value| value.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow!("crop must be x,y,width,height"))?;
+
+        match values[..] {
+            [x, y, width, height] if width > 0 && height > 0 => Ok(Some((x, y, width, height))),
+            _ => Err(anyhow!(
+                "crop must be x,y,width,height with a non-zero size"
+            )),
+        }
+    }
+
+    /// Short digest identifying the transformation, e.g. to name its cached output.
+    pub fn key(&self) -> anyhow::Result<String> {
+        let canonical = serde_json::to_string(self)?;
+        let digest = blake3::hash(canonical.as_bytes()).to_hex();
+
+        Ok(digest[..16].to_string())
+    }
+}
+
+fn validate_crop(crop: &str) -> Result<(), ValidationError> {
+    let transform = ImageTransform {
+        crop: Some(crop.to_string()),
+        ..Default::default()
+    };
+
+    transform
+        .crop_region()
+        .map(|_| ())
+        .map_err(|err| ValidationError::new("crop").with_message(err.to_string().into()))
+}
+
+fn validate_rotate(rotate: u16) -> Result<(), ValidationError> {
+    match rotate {
+        90 | 180 | 270 => Ok(()),
+        _ => {
+            Err(ValidationError::new("rotate").with_message("rotate must be 90, 180 or 270".into()))
+        }
+    }
+}
+
+/// Signed grant allowing anyone holding it to download the image at `path` with `transform`
+/// applied. Without it, only the owning client may request transformations, so that third
+/// parties can't make the server render and cache an unbounded number of variants.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct TransformInfo {
+    pub client_id: String,
+    pub path: String,
+    pub transform: ImageTransform,
+    pub exp: i64,
+}
+
+impl TransformInfo {
+    pub fn sign(&self, key: &str, hasher: &Hasher) -> anyhow::Result<String> {
+        hasher.hash(key, self)
+    }
+
+    pub async fn verify(
+        signed: &str,
+        db: &Database,
+        hasher: &Hasher,
+    ) -> Result<TransformInfo, PayloadVerificationError> {
+        hasher.verify(signed, db).await
+    }
+}
+
+impl Hashable for TransformInfo {
+    const KIND: &'static str = "transform";
+
+    async fn key(&self, db: &Database) -> anyhow::Result<String> {
+        Client::get_key(db, &self.client_id).await
     }
 
     fn expires(&self) -> i64 {
@@ -89,6 +341,26 @@ pub struct UploadUrlConfig {
     /// overwrite asset if it already exists.
     pub overwrite: Option<bool>,
     pub resumable: Option<bool>,
+    /// pid of the bucket the asset belongs to. Bucket settings such as versioning apply to the
+    /// asset when provided.
+    pub bucket: Option<String>,
+    /// user-defined metadata stored with the asset. Keys may include the `x-ppdrive-meta-` prefix.
+    #[validate(custom(function = "validate_metadata"))]
+    pub metadata: Option<Metadata>,
+    /// Visibility of the asset. Inherits the bucket's visibility when not provided.
+    pub visibility: Option<Visibility>,
+    /// Retention of the file. Defaults to the bucket's retention, if any.
+    pub retention: Option<Retention>,
+    /// Place the file under legal hold.
+    pub legal_hold: Option<bool>,
+    /// Overwrite a file under governance retention.
+    pub bypass_governance: Option<bool>,
+}
+
+pub fn validate_metadata(metadata: &Metadata) -> Result<(), ValidationError> {
+    metadata
+        .check()
+        .map_err(|err| ValidationError::new("metadata").with_message(err.to_string().into()))
 }
 
 impl UploadUrlConfig {
@@ -110,12 +382,7 @@ pub enum UploadUrlMethod {
     Put,
 }
 
-#[derive(Serialize, Deserialize, Default, Clone)]
-pub enum AssetType {
-    #[default]
-    File,
-    Folder,
-}
+pub use crate::assets::AssetType;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,292 @@
+use crate::assets::Asset;
+use crate::db::Database;
+use crate::{search, sql_safe};
+use anyhow::anyhow;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+pub const MAX_TAGS: usize = 10;
+pub const MAX_TAG_KEY_LEN: usize = 128;
+pub const MAX_TAG_VALUE_LEN: usize = 256;
+
+/// Tags of an asset, keyed by tag key.
+pub type TagSet = BTreeMap<String, String>;
+
+/// Matches assets having a tag `key`, and optionally a given `value`. Parsed from `key=value`
+/// or `key`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TagFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl TagFilter {
+    pub fn matches(&self, tags: &TagSet) -> bool {
+        match (tags.get(&self.key), &self.value) {
+            (Some(found), Some(value)) => found == value,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+impl FromStr for TagFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = match s.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_string())),
+            None => (s, None),
+        };
+
+        check_key(key)?;
+        if let Some(value) = &value {
+            check_value(key, value)?;
+        }
+
+        Ok(Self {
+            key: key.to_string(),
+            value,
+        })
+    }
+}
+
+impl Display for TagFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={value}", self.key),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
+
+/// Check a tag set against tag limits. Keys and values may contain alphanumerics, spaces and
+/// `+ - . _ : / @`. Values may also contain `=`.
+pub fn check(tags: &TagSet) -> anyhow::Result<()> {
+    if tags.len() > MAX_TAGS {
+        return Err(anyhow!("an asset can't have more than {MAX_TAGS} tags"));
+    }
+
+    for (key, value) in tags {
+        check_key(key)?;
+        check_value(key, value)?;
+    }
+
+    Ok(())
+}
+
+/// Replace the tags of an asset.
+pub async fn put(db: &Database, asset_id: i32, tags: &TagSet) -> anyhow::Result<()> {
+    check(tags)?;
+    clear(db, asset_id).await?;
+
+    for (key, value) in tags {
+        let query = sql_safe!(
+            "INSERT INTO asset_tags (asset_id, tag_key, tag_value) VALUES ({}, {}, {})",
+            db.placeholder(1),
+            db.placeholder(2),
+            db.placeholder(3)
+        );
+
+        sqlx::query(query)
+            .bind(asset_id)
+            .bind(key)
+            .bind(value)
+            .execute(&**db)
+            .await?;
+    }
+
+    search::index(db, asset_id).await
+}
+
+pub async fn get(db: &Database, asset_id: i32) -> anyhow::Result<TagSet> {
+    let query = sql_safe!(
+        "SELECT tag_key, tag_value FROM asset_tags WHERE asset_id = {}",
+        db.placeholder(1)
+    );
+
+    let tags: Vec<(String, String)> = sqlx::query_as(query)
+        .bind(asset_id)
+        .fetch_all(&**db)
+        .await?;
+
+    Ok(tags.into_iter().collect())
+}
+
+/// Remove every tag of an asset.
+pub async fn delete(db: &Database, asset_id: i32) -> anyhow::Result<()> {
+    clear(db, asset_id).await?;
+    search::index(db, asset_id).await
+}
+
+async fn clear(db: &Database, asset_id: i32) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "DELETE FROM asset_tags WHERE asset_id = {}",
+        db.placeholder(1)
+    );
+    sqlx::query(query).bind(asset_id).execute(&**db).await?;
+
+    Ok(())
+}
+
+/// Check whether the asset indexed at `path` matches every filter. Assets which aren't indexed
+/// never match.
+pub async fn matches(db: &Database, path: &str, filters: &[TagFilter]) -> anyhow::Result<bool> {
+    let asset = match crate::assets::get(db, path).await? {
+        Some(asset) => asset,
+        None => return Ok(false),
+    };
+
+    let tags = get(db, asset.id()).await?;
+    Ok(filters.iter().all(|filter| filter.matches(&tags)))
+}
+
+/// List an owner's assets matching every filter.
+pub async fn find(
+    db: &Database,
+    owner_id: i32,
+    filters: &[TagFilter],
+) -> anyhow::Result<Vec<Asset>> {
+    let conditions = filter_conditions(db, filters, 2);
+    let query = sql_safe!(
+        "SELECT * FROM assets WHERE owner_id = {}{conditions} ORDER BY path",
+        db.placeholder(1)
+    );
+
+    let mut query = sqlx::query_as(query).bind(owner_id);
+    for value in filter_values(filters) {
+        query = query.bind(value);
+    }
+
+    let assets = query.fetch_all(&**db).await?;
+    Ok(assets)
+}
+
+/// SQL conditions (each prefixed with ` AND `) restricting `assets` rows to those matching
+/// every filter. Placeholders start at `first_idx`; bind [filter_values] in order.
+pub(crate) fn filter_conditions(db: &Database, filters: &[TagFilter], first_idx: u8) -> String {
+    let mut idx = first_idx;
+    let mut conditions = String::new();
+
+    for filter in filters {
+        let mut condition = format!(
+            " AND EXISTS (SELECT 1 FROM asset_tags t WHERE t.asset_id = assets.id AND t.tag_key = {}",
+            db.placeholder(idx)
+        );
+
+        idx += 1;
+        if filter.value.is_some() {
+            condition.push_str(&format!(" AND t.tag_value = {}", db.placeholder(idx)));
+            idx += 1;
+        }
+
+        condition.push(')');
+        conditions.push_str(&condition);
+    }
+
+    conditions
+}
+
+/// Values to bind for [filter_conditions].
+pub(crate) fn filter_values(filters: &[TagFilter]) -> Vec<String> {
+    let mut values = vec![];
+    for filter in filters {
+        values.push(filter.key.clone());
+        if let Some(value) = &filter.value {
+            values.push(value.clone());
+        }
+    }
+
+    values
+}
+
+fn check_key(key: &str) -> anyhow::Result<()> {
+    if key.is_empty() || key.len() > MAX_TAG_KEY_LEN {
+        return Err(anyhow!(
+            "tag keys must be between 1 and {MAX_TAG_KEY_LEN} characters"
+        ));
+    }
+
+    if !key.chars().all(|c| c != '=' && allowed_char(c)) {
+        return Err(anyhow!("invalid tag key \"{key}\""));
+    }
+
+    Ok(())
+}
+
+fn check_value(key: &str, value: &str) -> anyhow::Result<()> {
+    if value.len() > MAX_TAG_VALUE_LEN {
+        return Err(anyhow!(
+            "tag \"{key}\" exceeds {MAX_TAG_VALUE_LEN} characters"
+        ));
+    }
+
+    if !value.chars().all(allowed_char) {
+        return Err(anyhow!("invalid value for tag \"{key}\""));
+    }
+
+    Ok(())
+}
+
+fn allowed_char(c: char) -> bool {
+    c.is_alphanumeric() || " +-=._:/@".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets;
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::secrets::AppSecrets;
+    use crate::tags::{self, TagFilter, TagSet};
+    use std::env;
+
+    #[tokio::test]
+    async fn test_put_and_find_tags() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Tags Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+        let owner_id = client::owner_id(&db, client_id).await?;
+
+        let alpha = format!("{}/alpha", details.id());
+        let beta = format!("{}/beta", details.id());
+        let alpha = assets::index_folder(&db, &alpha, owner_id, None).await?;
+        let beta = assets::index_folder(&db, &beta, owner_id, None).await?;
+
+        let tag_set = |entries: &[(&str, &str)]| -> TagSet {
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        tags::put(
+            &db,
+            alpha.id(),
+            &tag_set(&[("project", "alpha"), ("status", "draft")]),
+        )
+        .await?;
+        tags::put(&db, beta.id(), &tag_set(&[("project", "beta")])).await?;
+        assert_eq!(tags::get(&db, alpha.id()).await?.len(), 2);
+
+        let filters: Vec<TagFilter> = vec!["project=alpha".parse()?];
+        let found = tags::find(&db, owner_id, &filters).await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path(), alpha.path());
+
+        let filters: Vec<TagFilter> = vec!["project".parse()?];
+        assert_eq!(tags::find(&db, owner_id, &filters).await?.len(), 2);
+        assert!(tags::matches(&db, beta.path(), &filters).await?);
+
+        tags::delete(&db, alpha.id()).await?;
+        assert!(tags::get(&db, alpha.id()).await?.is_empty());
+        assert!("bad key!=value".parse::<TagFilter>().is_err());
+
+        Ok(())
+    }
+}
@@ -15,6 +15,15 @@ pub struct AppConfig {
     pub message_broker: Option<String>,
     pub static_folders: Vec<StaticFolder>,
     pub hasher: Hasher,
+    /// Number of days deleted assets stay in trash before they're permanently removed.
+    pub trash_retention: Option<u64>,
+    /// Folder that lifecycle rules move cold objects to. They stay indexed and are served from
+    /// there.
+    pub cold_volume: Option<String>,
+    /// Folders mirroring the storage root. The scrubber repairs damaged objects from them.
+    pub replicas: Option<Vec<String>>,
+    /// Rate (in MB per second) at which the scrubber reads objects.
+    pub scrub_rate: Option<f64>,
 }
 
 impl AppConfig {
@@ -34,6 +43,20 @@ impl AppConfig {
             None => Ok(root_dir()?),
         }
     }
+
+    pub fn cold_volume(&self) -> anyhow::Result<Option<PathBuf>> {
+        match &self.cold_volume {
+            Some(dir) => Ok(Some(root_dir()?.join(dir))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn replicas(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let root_dir = root_dir()?;
+        let replicas = self.replicas.iter().flatten();
+
+        Ok(replicas.map(|dir| root_dir.join(dir)).collect())
+    }
 }
 
 impl Default for AppConfig {
@@ -47,6 +70,10 @@ impl Default for AppConfig {
             message_broker: None,
             static_folders: vec![],
             hasher: Hasher::HMAC256,
+            trash_retention: None,
+            cold_volume: None,
+            replicas: None,
+            scrub_rate: None,
         }
     }
 }
@@ -14,13 +14,14 @@ pub enum Hasher {
 }
 
 impl Hasher {
-    pub fn hash<T: Serialize>(&self, key: &str, message: &T) -> anyhow::Result<String> {
+    pub fn hash<T: Serialize + Hashable>(&self, key: &str, message: &T) -> anyhow::Result<String> {
         use Hasher::*;
 
         let payload = serde_json::to_string(message)?;
+        let signed_payload = signed_payload::<T>(payload.as_bytes());
         let mut hash = match self {
-            HMAC256 => hmac256::hash(key, &payload)?,
-            Blake3 => blake3::hash(key, &payload)?,
+            HMAC256 => hmac256::hash(key, &signed_payload)?,
+            Blake3 => blake3::hash(key, &signed_payload)?,
         };
 
         let payload = payload.as_bytes();
@@ -52,13 +53,14 @@ impl Hasher {
         );
 
         let (payload, hash) = data.split_at(payload_len as usize);
-        let result: T = serde_json::from_slice(&payload)?;
+        let result: T = serde_json::from_slice(payload)?;
         let key = result.key(db).await?;
 
         match self {
-            HMAC256 => hmac256::verify(&key, payload, hash)?,
+            HMAC256 => hmac256::verify(&key, &signed_payload::<T>(payload), hash)?,
             Blake3 => {
                 let payload = serde_json::to_string(&result)?;
+                let payload = signed_payload::<T>(payload.as_bytes());
                 blake3::verify(&key, &payload, hash)?
             },
         }
@@ -76,7 +78,18 @@ impl Hasher {
     }
 }
 
+/// Bytes a payload's hash is computed over. Prefixing the payload with its kind keeps a token
+/// issued for one purpose from verifying as another, since every kind is signed with the same key.
+fn signed_payload<T: Hashable>(payload: &[u8]) -> Vec<u8> {
+    let mut data = format!("{}:", T::KIND).into_bytes();
+    data.extend_from_slice(payload);
+    data
+}
+
 pub trait Hashable {
+    /// Identifies what the payload grants, e.g. `download`.
+    const KIND: &'static str;
+
     /// Describe how to retrieve the key
     fn key(&self, db: &Database) -> impl Future<Output = anyhow::Result<String>>;
 
@@ -90,9 +103,9 @@ mod hmac256 {
 
     type HmacSha256 = Hmac<Sha256>;
 
-    pub fn hash(key: &str, payload: &str) -> anyhow::Result<Vec<u8>> {
+    pub fn hash(key: &str, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
         let mut mac = HmacSha256::new_from_slice(key.as_bytes())?;
-        mac.update(payload.as_bytes());
+        mac.update(payload);
 
         let result = mac.finalize();
         Ok(result.into_bytes().to_vec())
@@ -112,22 +125,22 @@ mod blake3 {
     use anyhow::anyhow;
     use blake3;
 
-    pub fn hash(key: &str, payload: &str) -> anyhow::Result<Vec<u8>> {
+    pub fn hash(key: &str, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
         let hash = blake3::keyed_hash(
             key.as_bytes()
                 .try_into()
                 .map_err(|_| anyhow!("Key must be a 32-bit long string"))?,
 
-            payload.as_bytes(),
+            payload,
         );
 
         let res = hash.as_bytes().to_vec();
         Ok(res)
     }
 
-    pub fn verify(key: &str, payload: &str, hash_raw: &[u8]) -> anyhow::Result<()> {
+    pub fn verify(key: &str, payload: &[u8], hash_raw: &[u8]) -> anyhow::Result<()> {
         let hash = hash(key, payload)?;
-        if &hash != hash_raw {
+        if hash != hash_raw {
             return Err(anyhow!("Blake3: verification failed."));
         }
 
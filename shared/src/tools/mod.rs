@@ -3,6 +3,7 @@ pub mod secrets;
 pub mod hasher;
 
 use anyhow::anyhow;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 pub fn root_dir() -> anyhow::Result<PathBuf> {
     let path = if cfg!(debug_assertions) {
@@ -46,11 +47,56 @@ pub async fn get_folder_size(folder_path: &str, size: &mut u64) -> anyhow::Resul
     Ok(())
 }
 
-pub fn mb_to_bytes(value: f64) -> usize {
-    let bytes = (value * 1024.0 * 1000.0).round();
-    let bytes = bytes.to_le_bytes();
+/// Recursively copy a file or folder, creating the parent folders of `to`.
+pub async fn copy_path(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = to.parent()
+        && !parent.exists()
+    {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    if from.is_file() {
+        tokio::fs::copy(from, to).await?;
+        return Ok(());
+    }
+
+    tokio::fs::create_dir_all(to).await?;
+    let mut rd = tokio::fs::read_dir(from).await?;
+
+    while let Ok(Some(entry)) = rd.next_entry().await {
+        Box::pin(copy_path(&entry.path(), &to.join(entry.file_name()))).await?;
+    }
+
+    Ok(())
+}
 
-    usize::from_le_bytes(bytes)
+/// Move a file or folder. This is an atomic rename when both paths are on the same volume,
+/// otherwise the content is copied then removed.
+pub async fn move_path(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = to.parent()
+        && !parent.exists()
+    {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    match tokio::fs::rename(from, to).await {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+            copy_path(from, to).await?;
+            if from.is_file() {
+                tokio::fs::remove_file(from).await?;
+            } else {
+                tokio::fs::remove_dir_all(from).await?;
+            }
+
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn mb_to_bytes(value: f64) -> usize {
+    (value * 1024.0 * 1000.0).round() as usize
 }
 
 pub fn generate_nano_id(size: usize) -> String {
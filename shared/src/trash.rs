@@ -0,0 +1,363 @@
+use crate::acl::{self, Visibility};
+use crate::assets::{self, Asset, Metadata};
+use crate::db::Database;
+use crate::lock::{self, Retention};
+use crate::tags::{self, TagSet};
+use crate::utils::{DbBool, unix_timestamp};
+use crate::{buckets, generate_nano_id, get_folder_size, sql_safe};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::path::{Path, PathBuf};
+
+/// Folder (relative to storage root) where deleted assets are kept until they are purged.
+pub const TRASH_DIR: &str = ".trash";
+
+/// Number of days deleted assets are kept when `trash_retention` is not configured.
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+#[derive(FromRow, Serialize)]
+pub struct TrashItem {
+    #[serde(skip)]
+    id: i32,
+    pid: String,
+    #[serde(skip)]
+    owner_id: i32,
+    original_path: String,
+    #[sqlx(try_from = "i16")]
+    folder: DbBool,
+    size: i64,
+    deleted_at: i64,
+    #[serde(skip)]
+    bucket_id: Option<i32>,
+    /// [TrashedAttributes] of the asset and its descendants, as JSON.
+    #[serde(skip)]
+    attributes: Option<String>,
+}
+
+/// Attributes of a trashed file or folder which aren't kept on disk, put back on restore.
+#[derive(Serialize, Deserialize)]
+struct TrashedAttributes {
+    /// Path relative to the trashed asset. Empty for the asset itself.
+    path: String,
+    metadata: Metadata,
+    tags: TagSet,
+    visibility: Option<Visibility>,
+    retention: Option<Retention>,
+    legal_hold: bool,
+}
+
+impl TrashItem {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn owner_id(&self) -> i32 {
+        self.owner_id
+    }
+
+    pub fn original_path(&self) -> &str {
+        &self.original_path
+    }
+
+    pub fn is_folder(&self) -> bool {
+        self.folder.get()
+    }
+
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    /// Unix time (seconds) the asset was moved to trash.
+    pub fn deleted_at(&self) -> i64 {
+        self.deleted_at
+    }
+
+    pub fn bucket_id(&self) -> Option<i32> {
+        self.bucket_id
+    }
+
+    /// Location of the trashed content on disk.
+    pub fn data_path(&self, root_dir: &Path) -> PathBuf {
+        trash_dir(root_dir).join(&self.pid)
+    }
+}
+
+pub fn trash_dir(root_dir: &Path) -> PathBuf {
+    root_dir.join(TRASH_DIR)
+}
+
+/// Move a file or folder into the owner's trash, keeping its original path so it can be
+/// restored later.
+pub async fn move_to_trash(
+    db: &Database,
+    root_dir: &Path,
+    owner_id: i32,
+    path: &str,
+) -> anyhow::Result<TrashItem> {
+    let path = path.trim_start_matches("/");
+    let source = root_dir.join(path);
+    if !source.exists() {
+        return Err(anyhow!("asset not found"));
+    }
+
+    let folder = source.is_dir();
+    let size = if folder {
+        let mut size = 0;
+        let folder_path = source.to_str().ok_or(anyhow!("invalid asset path"))?;
+        get_folder_size(folder_path, &mut size).await?;
+        size
+    } else {
+        tokio::fs::metadata(&source).await?.len()
+    };
+
+    let dir = trash_dir(root_dir);
+    if !dir.exists() {
+        tokio::fs::create_dir_all(&dir).await?;
+    }
+
+    let mut indexed = assets::descendants(db, path).await?;
+    if let Some(asset) = assets::get(db, path).await? {
+        indexed.push(asset);
+    }
+
+    let bucket_id = indexed.iter().find_map(Asset::bucket_id);
+    let mut attributes = Vec::with_capacity(indexed.len());
+    for asset in &indexed {
+        attributes.push(trashed_attributes(db, path, asset).await?);
+    }
+
+    let pid = generate_nano_id(32);
+    tokio::fs::rename(&source, dir.join(&pid)).await?;
+    assets::remove(db, root_dir, path).await?;
+
+    let mut placeholders = Vec::with_capacity(8);
+    for idx in 1..9 {
+        placeholders.push(db.placeholder(idx))
+    }
+
+    let placeholders = placeholders.join(",");
+    let query = sql_safe!(
+        "INSERT INTO trash (pid, owner_id, original_path, folder, size, deleted_at, bucket_id, attributes) VALUES ({placeholders})"
+    );
+
+    sqlx::query(query)
+        .bind(&pid)
+        .bind(owner_id)
+        .bind(path)
+        .bind(i16::from(DbBool::from(folder)))
+        .bind(size as i64)
+        .bind(unix_timestamp()?)
+        .bind(bucket_id)
+        .bind(serde_json::to_string(&attributes)?)
+        .execute(&**db)
+        .await?;
+
+    get(db, &pid).await
+}
+
+async fn trashed_attributes(
+    db: &Database,
+    root_path: &str,
+    asset: &Asset,
+) -> anyhow::Result<TrashedAttributes> {
+    let lock = asset.lock();
+    let retention = lock
+        .retention_mode()
+        .zip(lock.retain_until())
+        .map(|(mode, retain_until)| Retention { mode, retain_until });
+
+    Ok(TrashedAttributes {
+        path: asset.path()[assets::normalize(root_path).len()..].to_string(),
+        metadata: asset.metadata().clone(),
+        tags: tags::get(db, asset.id()).await?,
+        visibility: asset.visibility(),
+        retention,
+        legal_hold: lock.legal_hold(),
+    })
+}
+
+/// Move a trashed asset back to its original path, in its original bucket, with the metadata,
+/// tags, visibility and lock it had. Fails if the path has been taken, or the bucket deleted,
+/// since the asset was deleted.
+pub async fn restore(db: &Database, root_dir: &Path, item: &TrashItem) -> anyhow::Result<Asset> {
+    let target = root_dir.join(item.original_path());
+    if target.exists() {
+        return Err(anyhow!(
+            "an asset already exists at {}",
+            item.original_path()
+        ));
+    }
+
+    if let Some(bucket_id) = item.bucket_id() {
+        buckets::get_by_id(bucket_id, db)
+            .await
+            .map_err(|_| anyhow!("the asset's bucket no longer exists"))?;
+    }
+
+    if let Some(parent) = target.parent()
+        && !parent.exists()
+    {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let path = item.original_path();
+    tokio::fs::rename(item.data_path(root_dir), target).await?;
+    assets::index_parents(db, path, item.owner_id(), item.bucket_id()).await?;
+    assets::index_tree(db, root_dir, path, item.owner_id(), item.bucket_id()).await?;
+
+    let attributes: Vec<TrashedAttributes> = match &item.attributes {
+        Some(attributes) => serde_json::from_str(attributes)?,
+        None => Vec::new(),
+    };
+
+    for attributes in attributes {
+        let path = format!("{path}{}", attributes.path);
+        let Some(asset) = assets::get(db, &path).await? else {
+            continue;
+        };
+
+        if !attributes.metadata.is_empty() {
+            assets::set_metadata(db, &path, &attributes.metadata).await?;
+        }
+
+        if !attributes.tags.is_empty() {
+            tags::put(db, asset.id(), &attributes.tags).await?;
+        }
+
+        if attributes.visibility.is_some() {
+            acl::set(db, &path, attributes.visibility).await?;
+        }
+
+        if attributes.retention.is_some() || attributes.legal_hold {
+            lock::apply(db, &path, None, attributes.retention, attributes.legal_hold).await?;
+        }
+    }
+
+    remove(db, item).await?;
+    assets::get(db, path)
+        .await?
+        .ok_or(anyhow!("asset {path} is not indexed"))
+}
+
+/// Permanently delete a trashed asset.
+pub async fn purge(db: &Database, root_dir: &Path, item: &TrashItem) -> anyhow::Result<()> {
+    let path = item.data_path(root_dir);
+    if path.is_dir() {
+        tokio::fs::remove_dir_all(path).await?;
+    } else if path.exists() {
+        tokio::fs::remove_file(path).await?;
+    }
+
+    remove(db, item).await
+}
+
+/// Purge every item that has been in trash longer than `retention_days`. Returns the number of
+/// purged items.
+pub async fn sweep(db: &Database, root_dir: &Path, retention_days: u64) -> anyhow::Result<usize> {
+    let cutoff = unix_timestamp()? - (retention_days * 24 * 60 * 60) as i64;
+    let query = sql_safe!(
+        "SELECT * FROM trash WHERE deleted_at <= {}",
+        db.placeholder(1)
+    );
+
+    let items: Vec<TrashItem> = sqlx::query_as(query).bind(cutoff).fetch_all(&**db).await?;
+    for item in &items {
+        purge(db, root_dir, item).await?;
+    }
+
+    Ok(items.len())
+}
+
+pub async fn get(db: &Database, pid: &str) -> anyhow::Result<TrashItem> {
+    let query = sql_safe!(
+        "SELECT * FROM trash WHERE pid = {} LIMIT 1",
+        db.placeholder(1)
+    );
+    let item = sqlx::query_as(query).bind(pid).fetch_one(&**db).await?;
+
+    Ok(item)
+}
+
+/// List an owner's trash, most recently deleted first.
+pub async fn list(db: &Database, owner_id: i32) -> anyhow::Result<Vec<TrashItem>> {
+    let query = sql_safe!(
+        "SELECT * FROM trash WHERE owner_id = {} ORDER BY deleted_at DESC",
+        db.placeholder(1)
+    );
+
+    let items = sqlx::query_as(query)
+        .bind(owner_id)
+        .fetch_all(&**db)
+        .await?;
+    Ok(items)
+}
+
+/// Total bytes held in an owner's trash. Trashed assets count toward the owner's quota until
+/// they are purged.
+pub async fn usage(db: &Database, owner_id: i32) -> anyhow::Result<i64> {
+    let query = sql_safe!(
+        "SELECT COALESCE(SUM(size), 0) FROM trash WHERE owner_id = {}",
+        db.placeholder(1)
+    );
+
+    let size = sqlx::query_scalar(query)
+        .bind(owner_id)
+        .fetch_one(&**db)
+        .await?;
+    Ok(size)
+}
+
+async fn remove(db: &Database, item: &TrashItem) -> anyhow::Result<()> {
+    let query = sql_safe!("DELETE FROM trash WHERE id = {}", db.placeholder(1));
+    sqlx::query(query).bind(item.id()).execute(&**db).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::secrets::AppSecrets;
+    use crate::trash;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_trash_restore_and_purge() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Trash Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+
+        let owner_id = client::owner_id(&db, client_id).await?;
+        let root_dir = crate::root_dir()?
+            .join("test-assets/trash")
+            .join(details.id());
+        tokio::fs::create_dir_all(root_dir.join("reports")).await?;
+        tokio::fs::write(root_dir.join("reports/q1.csv"), "a,b,c").await?;
+
+        let item = trash::move_to_trash(&db, &root_dir, owner_id, "reports").await?;
+        assert!(item.is_folder());
+        assert!(!root_dir.join("reports").exists());
+        assert_eq!(trash::usage(&db, owner_id).await?, 5);
+
+        trash::restore(&db, &root_dir, &item).await?;
+        assert!(root_dir.join("reports/q1.csv").is_file());
+        assert_eq!(trash::list(&db, owner_id).await?.len(), 0);
+
+        let item = trash::move_to_trash(&db, &root_dir, owner_id, "reports/q1.csv").await?;
+        trash::purge(&db, &root_dir, &item).await?;
+        assert!(!item.data_path(&root_dir).exists());
+        assert_eq!(trash::usage(&db, owner_id).await?, 0);
+
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
-use time::OffsetDateTime;
 /// Utilities used by database queries
 // use crate::sql_safe;
 use crate::db::Database;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use time::OffsetDateTime;
 
 #[macro_export]
 macro_rules! sql_safe {
@@ -13,10 +15,22 @@ macro_rules! sql_safe {
     }};
 }
 
-pub async fn asset_owner_id(owner_name: AssetOwnerName, owner_id: i32, db: &Database) -> anyhow::Result<i32> {
-    let query = sql_safe!("SELECT id FROM asset_owners WHERE name = {} AND owner_id = {}", db.placeholder(1), db.placeholder(2));
-    let id = sqlx::query_scalar(query).bind(i16::from(owner_name)).bind(owner_id).fetch_one(&**db).await?;
-    
+pub async fn asset_owner_id(
+    owner_name: AssetOwnerName,
+    owner_id: i32,
+    db: &Database,
+) -> anyhow::Result<i32> {
+    let query = sql_safe!(
+        "SELECT id FROM asset_owner WHERE name = {} AND owner_id = {}",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+    let id = sqlx::query_scalar(query)
+        .bind(i16::from(owner_name))
+        .bind(owner_id)
+        .fetch_one(&**db)
+        .await?;
+
     Ok(id)
 }
 
@@ -50,6 +64,36 @@ impl From<AssetOwnerName> for i16 {
     }
 }
 
+/// A boolean stored in a `SMALLINT` column. The Any driver is unable to decode sqlite booleans,
+/// so flags are kept as `0` or `1`.
+#[derive(Serialize, Clone, Copy, Default)]
+#[serde(transparent)]
+pub struct DbBool(bool);
+
+impl DbBool {
+    pub fn get(&self) -> bool {
+        self.0
+    }
+}
+
+impl From<i16> for DbBool {
+    fn from(value: i16) -> Self {
+        Self(value != 0)
+    }
+}
+
+impl From<bool> for DbBool {
+    fn from(value: bool) -> Self {
+        Self(value)
+    }
+}
+
+impl From<DbBool> for i16 {
+    fn from(value: DbBool) -> Self {
+        value.0 as i16
+    }
+}
+
 pub struct SqlSafe<T> {
     inner: sqlx::AssertSqlSafe<T>
 }
@@ -67,4 +111,10 @@ impl<T> SqlSafe<T> {
 pub fn instance_as_string() -> anyhow::Result<String> {
     let now = OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339)?;
     Ok(now)
-}
\ No newline at end of file
+}
+
+/// Current unix time in seconds.
+pub fn unix_timestamp() -> anyhow::Result<i64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    Ok(now)
+}
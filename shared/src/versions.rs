@@ -0,0 +1,332 @@
+use crate::db::Database;
+use crate::utils::{DbBool, instance_as_string};
+use crate::{assets, buckets, generate_nano_id, sql_safe};
+use anyhow::anyhow;
+use serde::Serialize;
+use sqlx::FromRow;
+use std::path::{Path, PathBuf};
+
+/// Folder (relative to storage root) where prior versions of objects are kept.
+pub const VERSIONS_DIR: &str = ".versions";
+
+/// Number of times recording a version is attempted when concurrent writers race for the same
+/// version number.
+const INSERT_ATTEMPTS: usize = 5;
+
+#[derive(FromRow, Serialize)]
+pub struct AssetVersion {
+    #[serde(skip)]
+    id: i32,
+    pid: String,
+    #[serde(skip)]
+    bucket_id: i32,
+    path: String,
+    version: i32,
+    size: i64,
+    #[sqlx(try_from = "i16")]
+    delete_marker: DbBool,
+    created_at: String,
+}
+
+impl AssetVersion {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    pub fn bucket_id(&self) -> i32 {
+        self.bucket_id
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    pub fn is_delete_marker(&self) -> bool {
+        self.delete_marker.get()
+    }
+
+    /// Location of the version's content on disk.
+    pub fn data_path(&self, root_dir: &Path) -> PathBuf {
+        versions_dir(root_dir).join(&self.pid)
+    }
+}
+
+pub fn versions_dir(root_dir: &Path) -> PathBuf {
+    root_dir.join(VERSIONS_DIR)
+}
+
+/// Move the current content of `path` into the versions folder and record it as the next
+/// version of the object.
+pub async fn archive(
+    db: &Database,
+    root_dir: &Path,
+    bucket_id: i32,
+    path: &str,
+) -> anyhow::Result<AssetVersion> {
+    let path = path.trim_start_matches("/");
+    let source = root_dir.join(path);
+    if !source.is_file() {
+        return Err(anyhow!("only files can be versioned"));
+    }
+
+    let size = tokio::fs::metadata(&source).await?.len() as i64;
+    let pid = generate_nano_id(32);
+
+    let dir = versions_dir(root_dir);
+    if !dir.exists() {
+        tokio::fs::create_dir_all(&dir).await?;
+    }
+
+    tokio::fs::rename(&source, dir.join(&pid)).await?;
+    insert(db, &pid, bucket_id, path, size, false).await
+}
+
+/// Record a delete marker for `path`. If the object still exists, its content is archived first
+/// so that deleting never destroys history.
+pub async fn mark_deleted(
+    db: &Database,
+    root_dir: &Path,
+    bucket_id: i32,
+    path: &str,
+) -> anyhow::Result<AssetVersion> {
+    let path = path.trim_start_matches("/");
+    if root_dir.join(path).is_file() {
+        archive(db, root_dir, bucket_id, path).await?;
+    }
+
+    assets::remove(db, root_dir, path).await?;
+    let pid = generate_nano_id(32);
+    insert(db, &pid, bucket_id, path, 0, true).await
+}
+
+/// Make a prior version the current content of its object. The content being replaced is
+/// archived as a new version.
+pub async fn restore(db: &Database, root_dir: &Path, version: &AssetVersion) -> anyhow::Result<()> {
+    if version.is_delete_marker() {
+        return Err(anyhow!("a delete marker cannot be restored"));
+    }
+
+    let target = root_dir.join(version.path());
+    if target.is_file() {
+        archive(db, root_dir, version.bucket_id(), version.path()).await?;
+    }
+
+    if let Some(parent) = target.parent()
+        && !parent.exists()
+    {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    tokio::fs::copy(version.data_path(root_dir), target).await?;
+
+    let bucket = buckets::get_by_id(version.bucket_id(), db).await?;
+    let owner_id = bucket.owner_id();
+    let bucket_id = Some(bucket.id());
+    assets::index_parents(db, version.path(), owner_id, bucket_id).await?;
+    assets::index_file(db, root_dir, version.path(), owner_id, bucket_id).await?;
+
+    Ok(())
+}
+
+pub async fn get(db: &Database, pid: &str) -> anyhow::Result<AssetVersion> {
+    let query = sql_safe!(
+        "SELECT * FROM asset_versions WHERE pid = {} LIMIT 1",
+        db.placeholder(1)
+    );
+
+    let version = sqlx::query_as(query).bind(pid).fetch_one(&**db).await?;
+    Ok(version)
+}
+
+/// List versions of an object, newest first.
+pub async fn list(db: &Database, bucket_id: i32, path: &str) -> anyhow::Result<Vec<AssetVersion>> {
+    let query = sql_safe!(
+        "SELECT * FROM asset_versions WHERE bucket_id = {} AND path = {} ORDER BY version DESC",
+        db.placeholder(1),
+        db.placeholder(2)
+    );
+
+    let versions = sqlx::query_as(query)
+        .bind(bucket_id)
+        .bind(path.trim_start_matches("/"))
+        .fetch_all(&**db)
+        .await?;
+
+    Ok(versions)
+}
+
+/// Remove the stored content of every version kept for a bucket. Version records are removed
+/// with the bucket.
+pub async fn remove_all(db: &Database, root_dir: &Path, bucket_id: i32) -> anyhow::Result<()> {
+    let query = sql_safe!(
+        "SELECT * FROM asset_versions WHERE bucket_id = {}",
+        db.placeholder(1)
+    );
+
+    let versions: Vec<AssetVersion> = sqlx::query_as(query)
+        .bind(bucket_id)
+        .fetch_all(&**db)
+        .await?;
+    for version in versions {
+        let data_path = version.data_path(root_dir);
+        if data_path.is_file() {
+            tokio::fs::remove_file(data_path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn insert(
+    db: &Database,
+    pid: &str,
+    bucket_id: i32,
+    path: &str,
+    size: i64,
+    delete_marker: bool,
+) -> anyhow::Result<AssetVersion> {
+    let now = instance_as_string()?;
+    let mut attempt = 1;
+    loop {
+        match insert_next(db, pid, bucket_id, path, size, delete_marker, &now).await {
+            // a concurrent writer took the version number, take the next one.
+            Err(sqlx::Error::Database(err))
+                if err.is_unique_violation() && attempt < INSERT_ATTEMPTS =>
+            {
+                attempt += 1
+            }
+            result => break result?,
+        }
+    }
+
+    get(db, pid).await
+}
+
+/// Record `pid` as the next version of the object. The latest version is read by the insert
+/// itself, so it's never stale by the time the row is written.
+async fn insert_next(
+    db: &Database,
+    pid: &str,
+    bucket_id: i32,
+    path: &str,
+    size: i64,
+    delete_marker: bool,
+    now: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sql_safe!(
+        "INSERT INTO asset_versions (pid, bucket_id, path, version, size, delete_marker, created_at) SELECT {}, {}, {}, COALESCE(MAX(version), 0) + 1, {}, {}, {} FROM asset_versions WHERE bucket_id = {} AND path = {}",
+        db.placeholder(1),
+        db.placeholder(2),
+        db.placeholder(3),
+        db.placeholder(4),
+        db.placeholder(5),
+        db.placeholder(6),
+        db.placeholder(7),
+        db.placeholder(8)
+    );
+
+    sqlx::query(query)
+        .bind(pid)
+        .bind(bucket_id)
+        .bind(path)
+        .bind(size)
+        .bind(i16::from(DbBool::from(delete_marker)))
+        .bind(now)
+        .bind(bucket_id)
+        .bind(path)
+        .execute(&**db)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::acl::Visibility;
+    use crate::buckets::{self, CreateBucketData};
+    use crate::client::{self, create_client};
+    use crate::db::Database;
+    use crate::secrets::AppSecrets;
+    use crate::utils::AssetOwnerName;
+    use crate::versions;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_archive_and_restore() -> anyhow::Result<()> {
+        dotenvy::dotenv()?;
+        let url = env::var("DATABASE_URL")?;
+        let db = Database::new(&url).await?;
+
+        let secrets = AppSecrets::read().await?;
+        let details = create_client(&db, &secrets, "Versions Test", None).await?;
+        let client_id = client::id_by_pid(&db, details.id()).await?;
+
+        let data = CreateBucketData {
+            size: None,
+            accepts: None,
+            owner_type: AssetOwnerName::Client,
+            owner_id: client_id,
+            versioning: true,
+            visibility: Visibility::Private,
+            public_tag: None,
+            retention: None,
+            precompress: false,
+        };
+
+        let bucket = buckets::create(data, &db).await?;
+        let bucket = buckets::get(&bucket, &db).await?;
+
+        let root_dir = crate::root_dir()?.join("test-assets/versions");
+        tokio::fs::create_dir_all(&root_dir).await?;
+
+        let path = "notes.txt";
+        tokio::fs::write(root_dir.join(path), "first").await?;
+        versions::archive(&db, &root_dir, bucket.id(), path).await?;
+
+        tokio::fs::write(root_dir.join(path), "second").await?;
+        let marker = versions::mark_deleted(&db, &root_dir, bucket.id(), path).await?;
+        assert!(marker.is_delete_marker());
+        assert!(!root_dir.join(path).exists());
+
+        let list = versions::list(&db, bucket.id(), path).await?;
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].version(), 3);
+
+        let first = &list[2];
+        versions::restore(&db, &root_dir, first).await?;
+        let content = tokio::fs::read_to_string(root_dir.join(path)).await?;
+        assert_eq!(content, "first");
+
+        // Concurrent writers get distinct version numbers
+        let mut writers = Vec::new();
+        for _ in 0..8 {
+            let (db, root_dir) = (db.clone(), root_dir.clone());
+            let bucket_id = bucket.id();
+            writers.push(tokio::spawn(async move {
+                versions::mark_deleted(&db, &root_dir, bucket_id, "concurrent.txt").await
+            }));
+        }
+
+        for writer in writers {
+            writer.await??;
+        }
+
+        let list = versions::list(&db, bucket.id(), "concurrent.txt").await?;
+        let numbers: Vec<i32> = list.iter().map(|version| version.version()).collect();
+        assert_eq!(numbers, (1..=8).rev().collect::<Vec<_>>());
+
+        Ok(())
+    }
+}